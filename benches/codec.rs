@@ -0,0 +1,62 @@
+use bytes::BytesMut;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use muonline_packet::{crypto, Packet, PacketCodec, PacketCodecState, PacketKind, XOR_CIPHER};
+use tokio_io::codec::{Decoder, Encoder};
+
+fn state() -> PacketCodecState {
+  PacketCodecState::builder()
+    .cipher(&XOR_CIPHER)
+    .crypto(crypto::CLIENT.clone())
+    .build()
+}
+
+fn round_trip(c: &mut Criterion) {
+  let mut group = c.benchmark_group("codec");
+  for &size in &[8, 64, 512, 4096] {
+    let kind = PacketKind::from_size(size + 1, None).unwrap();
+    let mut packet = Packet::new(kind, 0x20);
+    packet.append(&vec![0xAB; size]);
+
+    group.throughput(Throughput::Bytes(size as u64));
+    group.bench_with_input(BenchmarkId::new("round_trip", size), &packet, |b, packet| {
+      let mut codec = PacketCodec::new(state(), state());
+      let mut buffer = BytesMut::new();
+
+      b.iter(|| {
+        codec.encode(packet.clone(), &mut buffer).unwrap();
+        codec.decode(&mut buffer).unwrap().unwrap()
+      })
+    });
+  }
+  group.finish();
+}
+
+fn segmented(c: &mut Criterion) {
+  let mut group = c.benchmark_group("codec");
+  let mut packet = Packet::new(PacketKind::C2, 0x20);
+  packet.append(&vec![0xAB; 4096]);
+
+  let mut encoder = PacketCodec::new(state(), PacketCodecState::new());
+  let mut frame = BytesMut::new();
+  encoder.encode(packet, &mut frame).unwrap();
+
+  // Simulates a large frame arriving in many TCP segments
+  group.throughput(Throughput::Bytes(frame.len() as u64));
+  group.bench_function("segmented", |b| {
+    b.iter(|| {
+      let mut codec = PacketCodec::new(PacketCodecState::new(), state());
+      let mut buffer = BytesMut::new();
+      for segment in frame.chunks(64) {
+        buffer.extend_from_slice(segment);
+        if let Some(packet) = codec.decode(&mut buffer).unwrap() {
+          return packet;
+        }
+      }
+      unreachable!()
+    })
+  });
+  group.finish();
+}
+
+criterion_group!(benches, round_trip, segmented);
+criterion_main!(benches);
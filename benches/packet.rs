@@ -0,0 +1,83 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use muonline_packet::{crypto, Packet, PacketKind, XOR_CIPHER};
+
+/// Payload sizes to benchmark, covering small C1 and large C2 packets.
+const SIZES: [usize; 4] = [8, 64, 512, 4096];
+
+fn payload(size: usize) -> Vec<u8> { (0..size).map(|index| index as u8).collect() }
+
+fn packet(size: usize) -> Packet {
+  let kind = PacketKind::from_size(size + 1, None).unwrap();
+  let mut packet = Packet::new(kind, 0x20);
+  packet.append(&payload(size));
+  packet
+}
+
+fn construct(c: &mut Criterion) {
+  let mut group = c.benchmark_group("construct");
+  for &size in &SIZES {
+    let data = payload(size);
+    let kind = PacketKind::from_size(size + 1, None).unwrap();
+
+    group.throughput(Throughput::Bytes(size as u64));
+    group.bench_with_input(BenchmarkId::new("append", size), &data, |b, data| {
+      b.iter(|| {
+        let mut packet = Packet::new(kind, 0x20);
+        packet.append(data);
+        packet
+      })
+    });
+  }
+  group.finish();
+}
+
+fn crypto(c: &mut Criterion) {
+  let mut group = c.benchmark_group("crypto");
+  for &size in &SIZES {
+    let data = payload(size);
+    let encrypted = crypto::CLIENT.encrypt(&data);
+
+    group.throughput(Throughput::Bytes(size as u64));
+    group.bench_with_input(BenchmarkId::new("encrypt", size), &data, |b, data| {
+      b.iter(|| crypto::CLIENT.encrypt(data))
+    });
+    group.bench_with_input(BenchmarkId::new("decrypt", size), &encrypted, |b, data| {
+      b.iter(|| crypto::CLIENT.decrypt(data).unwrap())
+    });
+  }
+  group.finish();
+}
+
+fn xor(c: &mut Criterion) {
+  let mut group = c.benchmark_group("xor");
+  for &size in &SIZES {
+    let packet = packet(size);
+
+    group.throughput(Throughput::Bytes(size as u64));
+    group.bench_with_input(BenchmarkId::new("to_bytes_ex", size), &packet, |b, packet| {
+      b.iter(|| packet.to_bytes_ex(Some(&XOR_CIPHER), None))
+    });
+  }
+  group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+  let mut group = c.benchmark_group("from_bytes_ex");
+  for &size in &SIZES {
+    let packet = packet(size);
+    let plain = packet.to_bytes();
+    let encrypted = packet.to_bytes_ex(Some(&XOR_CIPHER), Some((&crypto::CLIENT, 0)));
+
+    group.throughput(Throughput::Bytes(size as u64));
+    group.bench_with_input(BenchmarkId::new("plain", size), &plain, |b, bytes| {
+      b.iter(|| Packet::from_bytes(bytes).unwrap())
+    });
+    group.bench_with_input(BenchmarkId::new("encrypted", size), &encrypted, |b, bytes| {
+      b.iter(|| Packet::from_bytes_ex(bytes, Some(&XOR_CIPHER), Some(&crypto::CLIENT)).unwrap())
+    });
+  }
+  group.finish();
+}
+
+criterion_group!(benches, construct, crypto, xor, parse);
+criterion_main!(benches);
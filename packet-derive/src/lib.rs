@@ -1,25 +1,82 @@
 #[macro_use]
 extern crate quote;
 extern crate proc_macro;
+extern crate proc_macro2;
 #[macro_use]
 extern crate syn;
 
 use proc_macro::TokenStream;
-use syn::{AttrStyle, DeriveInput, Lit, Meta, NestedMeta};
+use syn::{AttrStyle, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
 
 struct PacketHeader {
   kind: String,
   code: u8,
   subcode: Vec<u8>,
+  builder: bool,
+  display: bool,
+  min_size: Option<u64>,
+  test_vectors: Vec<Vec<u8>>,
 }
 
-#[proc_macro_derive(Packet, attributes(packet))]
+/// Derives `PacketType` from a `packet` attribute.
+///
+/// Fields may be validated after decoding, using `mu` attributes:
+///
+/// - `#[mu(range = "1..=400")]`: the value must be within the range.
+/// - `#[mu(max_len = 60)]`: the value's length must not exceed the limit.
+///
+/// With `#[packet(builder)]`, a builder is generated for structs with named
+/// fields. All fields are required, except those marked `#[mu(reserved)]`,
+/// which default to `Default::default()`.
+///
+/// With `#[packet(display)]`, a compact `Display` implementation is generated
+/// for logging, in which fields marked `#[mu(redact)]` are hidden.
+///
+/// With `#[packet(min_size = 12)]`, packets smaller than the size (including
+/// their header) are rejected before being decoded.
+///
+/// With `#[packet(test_vector = "c1 06 f4 03 00 00")]`, which may be repeated,
+/// a test is generated asserting that each vector decodes to a value which
+/// encodes to the same bytes.
+///
+/// Runtime-only fields are marked `#[mu(skip)]`. Since the wire format is
+/// produced by serde, these must also be marked `#[serde(skip)]`, which fills
+/// them with `Default::default()` when decoding. Builders default them too.
+///
+/// Optional trailing fields are marked `#[mu(default)]`, along with
+/// `#[serde(with = "muonline_packet::serialize::trailing")]`, which fills them
+/// with `Default::default()` when the packet ends before them. They may only
+/// be followed by other optional or skipped fields. Builders default them.
+#[proc_macro_derive(Packet, attributes(packet, mu))]
 pub fn mu_packet(input: TokenStream) -> TokenStream {
   let ast = parse_macro_input!(input as DeriveInput);
 
   // Retrieve the packet header
   let header = get_packet_header(&ast);
 
+  // Ensure skipped fields are also skipped on the wire
+  for field in get_fields(&ast) {
+    if is_skipped(field) && !is_serde_skipped(field) {
+      panic!("#[derive(Packet)] field attribute 'skip' requires #[serde(skip)] as well");
+    }
+  }
+
+  // Ensure optional fields are decoded as such, and are trailing
+  let mut optional = false;
+  for field in get_fields(&ast) {
+    if is_defaulted(field) {
+      if !is_serde_trailing(field) {
+        panic!(concat!(
+          "#[derive(Packet)] field attribute 'default' requires ",
+          "#[serde(with = \"muonline_packet::serialize::trailing\")] as well"
+        ));
+      }
+      optional = true;
+    } else if optional && !is_skipped(field) {
+      panic!("#[derive(Packet)] field attribute 'default' is only allowed on trailing fields");
+    }
+  }
+
   // Build the impl
   generate(&ast, header)
 }
@@ -38,8 +95,12 @@ fn get_packet_header(ast: &syn::DeriveInput) -> PacketHeader {
         }
       }
       None
-    }).next()
-    .expect("#[derive(Packet)] requires a 'packet' list attribute");
+    }).flat_map(|items| items)
+    .collect::<Vec<_>>();
+
+  if items.is_empty() {
+    panic!("#[derive(Packet)] requires a 'packet' list attribute");
+  }
 
   let kind = items
     .iter()
@@ -55,21 +116,48 @@ fn get_packet_header(ast: &syn::DeriveInput) -> PacketHeader {
     .iter()
     .filter_map(|item| get_key_value("subcode", item))
     .next();
+  let builder = items.iter().any(|item| is_word("builder", item));
+  let display = items.iter().any(|item| is_word("display", item));
+  let min_size = items
+    .iter()
+    .filter_map(|item| get_key_int("min_size", item))
+    .next();
+  let test_vectors = items
+    .iter()
+    .filter_map(|item| get_key_value("test_vector", item))
+    .map(|vector| {
+      vector
+        .split_whitespace()
+        .map(|byte| {
+          u8::from_str_radix(byte, 16).expect(
+            "#[derive(Packet)] attribute field 'test_vector' must be space-separated hex values.",
+          )
+        }).collect()
+    }).collect();
+
+  let subcode: Vec<u8> = subcode
+    .map(|codes| {
+      codes
+        .split("|")
+        .map(|code| {
+          u8::from_str_radix(&code, 16).expect(
+            "#[derive(Packet)] attribute field 'subcode' must be pipe-separated hex values.",
+          )
+        }).collect()
+    }).unwrap_or_else(Vec::new);
+  if subcode.len() > 3 {
+    panic!("#[derive(Packet)] attribute field 'subcode' allows at most three values.");
+  }
 
   PacketHeader {
     kind,
     code: u8::from_str_radix(&code, 16)
       .expect("#[derive(Packet)] attribute field 'code' must be a hexadecimal."),
-    subcode: subcode
-      .map(|codes| {
-        codes
-          .split("|")
-          .map(|code| {
-            u8::from_str_radix(&code, 16).expect(
-              "#[derive(Packet)] attribute field 'subcode' must be pipe-separated hex values.",
-            )
-          }).collect()
-      }).unwrap_or_else(Vec::new),
+    subcode,
+    builder,
+    display,
+    min_size,
+    test_vectors,
   }
 }
 
@@ -78,6 +166,22 @@ fn generate(ast: &syn::DeriveInput, header: PacketHeader) -> TokenStream {
   let kind = syn::Ident::new(&header.kind, ast.ident.span());
   let code = header.code;
   let subcode = header.subcode;
+  let validations = get_validations(ast);
+  let builder = if header.builder {
+    generate_builder(ast)
+  } else {
+    quote!()
+  };
+  let tests = generate_tests(ast, &header.test_vectors);
+  let display = if header.display {
+    generate_display(ast)
+  } else {
+    quote!()
+  };
+  let min_size = header.min_size.map(|min_size| {
+    let min_size = min_size as usize;
+    quote!(fn min_size() -> usize { #min_size })
+  });
 
   (quote! {
       impl ::muonline_packet::PacketType for #name {
@@ -88,10 +192,284 @@ fn generate(ast: &syn::DeriveInput, header: PacketHeader) -> TokenStream {
             static CODES: &'static [u8] = &[#(#subcode),*];
             CODES
           }
+
+          #min_size
+
+          fn validate(&self) -> Result<(), ::muonline_packet::ValidationError> {
+            #(#validations)*
+            Ok(())
+          }
       }
+
+      #builder
+      #display
+      #tests
   }).into()
 }
 
+fn generate_display(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
+  let name = &ast.ident;
+  let title = name.to_string();
+  let fields = get_fields(ast);
+  let named = fields.iter().all(|field| field.ident.is_some());
+
+  let entries = fields.iter().enumerate().map(|(index, field)| {
+    let member = match field.ident {
+      Some(ref ident) => quote!(#ident),
+      None => {
+        let index = syn::Index::from(index);
+        quote!(#index)
+      },
+    };
+
+    let redacted = get_field_items(field)
+      .iter()
+      .any(|item| is_word("redact", item));
+    let value = if redacted {
+      quote!(&format_args!("***"))
+    } else {
+      quote!(&self.#member)
+    };
+
+    match field.ident {
+      Some(ref ident) => {
+        let label = ident.to_string();
+        quote!(.field(#label, #value))
+      },
+      None => quote!(.field(#value)),
+    }
+  });
+
+  let builder = if named && !fields.is_empty() {
+    quote!(debug_struct)
+  } else {
+    quote!(debug_tuple)
+  };
+
+  quote! {
+    impl ::std::fmt::Display for #name {
+      fn fmt(&self, fmt: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        fmt.#builder(#title) #(#entries)* .finish()
+      }
+    }
+  }
+}
+
+fn generate_tests(ast: &syn::DeriveInput, vectors: &[Vec<u8>]) -> proc_macro2::TokenStream {
+  if vectors.is_empty() {
+    return quote!();
+  }
+
+  let name = &ast.ident;
+  let module = syn::Ident::new(
+    &format!("__packet_test_vectors_{}", name.to_string().to_lowercase()),
+    name.span(),
+  );
+  let tests = vectors.iter().enumerate().map(|(index, vector)| {
+    let test = syn::Ident::new(&format!("test_vector_{}", index), name.span());
+    quote! {
+      #[test]
+      fn #test() {
+        use ::muonline_packet::{Packet, PacketDecodable, PacketEncodable};
+
+        let bytes: &[u8] = &[#(#vector),*];
+        let packet = Packet::from_bytes(bytes).expect("test vector is not a packet");
+        let value = <super::#name as PacketDecodable>::from_packet(&packet)
+          .expect("test vector does not decode");
+        let encoded = PacketEncodable::to_packet(&value).expect("test vector does not encode");
+        assert_eq!(encoded.to_bytes(), bytes);
+      }
+    }
+  });
+
+  quote! {
+    #[cfg(test)]
+    mod #module {
+      #(#tests)*
+    }
+  }
+}
+
+fn generate_builder(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
+  let name = &ast.ident;
+  let vis = &ast.vis;
+  let builder = syn::Ident::new(&format!("{}Builder", name), name.span());
+  let fields = get_fields(ast);
+
+  if fields.iter().any(|field| field.ident.is_none()) {
+    panic!("#[packet(builder)] requires a struct with named fields");
+  }
+
+  let idents = fields
+    .iter()
+    .map(|field| field.ident.clone().unwrap())
+    .collect::<Vec<_>>();
+  let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+  let values = fields.iter().map(|field| {
+    let ident = field.ident.clone().unwrap();
+    let reserved = is_skipped(field) || is_defaulted(field) || get_field_items(field)
+      .iter()
+      .any(|item| is_word("reserved", item));
+
+    if reserved {
+      quote!(self.#ident.unwrap_or_default())
+    } else {
+      let name = ident.to_string();
+      quote! {
+        self.#ident.ok_or_else(|| {
+          ::muonline_packet::ValidationError::new(#name, "value is required")
+        })?
+      }
+    }
+  });
+
+  let setters = idents.iter().zip(types.iter()).map(|(ident, ty)| {
+    quote! {
+      /// Sets the field's value.
+      #vis fn #ident(mut self, value: #ty) -> Self {
+        self.#ident = Some(value);
+        self
+      }
+    }
+  }).collect::<Vec<_>>();
+  let types = &types;
+
+  let idents1 = &idents;
+  let idents2 = &idents;
+  let doc = format!("A builder for `{}`.", name);
+
+  quote! {
+    #[doc = #doc]
+    #[derive(Default)]
+    #vis struct #builder {
+      #(#idents1: Option<#types>,)*
+    }
+
+    impl #name {
+      /// Returns a builder for the packet.
+      #vis fn builder() -> #builder { #builder::default() }
+    }
+
+    impl #builder {
+      #(#setters)*
+
+      /// Builds and validates the packet.
+      #vis fn build(self) -> Result<#name, ::muonline_packet::ValidationError> {
+        let packet = #name {
+          #(#idents2: #values,)*
+        };
+
+        ::muonline_packet::PacketType::validate(&packet)?;
+        Ok(packet)
+      }
+    }
+  }
+}
+
+fn get_fields(ast: &syn::DeriveInput) -> Vec<&syn::Field> {
+  match ast.data {
+    Data::Struct(ref data) => match data.fields {
+      Fields::Named(ref fields) => fields.named.iter().collect(),
+      Fields::Unnamed(ref fields) => fields.unnamed.iter().collect(),
+      Fields::Unit => Vec::new(),
+    },
+    _ => Vec::new(),
+  }
+}
+
+fn get_validations(ast: &syn::DeriveInput) -> Vec<proc_macro2::TokenStream> {
+  let fields = get_fields(ast);
+  let mut validations = Vec::new();
+  for (index, field) in fields.into_iter().enumerate() {
+    let (member, name) = match field.ident {
+      Some(ref ident) => (quote!(#ident), ident.to_string()),
+      None => {
+        let index = syn::Index::from(index);
+        (quote!(#index), index.index.to_string())
+      },
+    };
+
+    for item in get_field_items(field) {
+      if let Some(range) = get_key_value("range", &item) {
+        let expr = syn::parse_str::<syn::Expr>(&range)
+          .expect("#[derive(Packet)] field attribute 'range' must be a range expression");
+        let reason = format!("value is outside of {}", range);
+        validations.push(quote! {
+          if !(#expr).contains(&self.#member) {
+            return Err(::muonline_packet::ValidationError::new(#name, #reason));
+          }
+        });
+      } else if let Some(max_len) = get_key_int("max_len", &item) {
+        let max_len = max_len as usize;
+        let reason = format!("length exceeds {}", max_len);
+        validations.push(quote! {
+          if self.#member.len() > #max_len {
+            return Err(::muonline_packet::ValidationError::new(#name, #reason));
+          }
+        });
+      }
+    }
+  }
+
+  validations
+}
+
+fn get_field_items(field: &syn::Field) -> Vec<NestedMeta> {
+  field
+    .attrs
+    .iter()
+    .filter_map(|attr| match attr.parse_meta() {
+      Ok(Meta::List(list)) if list.ident == "mu" => Some(list.nested.into_iter()),
+      _ => None,
+    }).flat_map(|items| items)
+    .collect()
+}
+
+fn is_skipped(field: &syn::Field) -> bool {
+  get_field_items(field).iter().any(|item| is_word("skip", item))
+}
+
+fn is_defaulted(field: &syn::Field) -> bool {
+  get_field_items(field).iter().any(|item| is_word("default", item))
+}
+
+fn is_serde_trailing(field: &syn::Field) -> bool {
+  field.attrs.iter().any(|attr| match attr.parse_meta() {
+    Ok(Meta::List(list)) if list.ident == "serde" => list.nested.iter().any(|item| {
+      get_key_value("with", item).map_or(false, |path| path.ends_with("serialize::trailing"))
+    }),
+    _ => false,
+  })
+}
+
+fn is_serde_skipped(field: &syn::Field) -> bool {
+  field.attrs.iter().any(|attr| match attr.parse_meta() {
+    Ok(Meta::List(list)) if list.ident == "serde" => {
+      list.nested.iter().any(|item| is_word("skip", item))
+    },
+    _ => false,
+  })
+}
+
+fn is_word(key: &str, item: &NestedMeta) -> bool {
+  match item {
+    &NestedMeta::Meta(Meta::Word(ref ident)) => ident == key,
+    _ => false,
+  }
+}
+
+fn get_key_int(key: &str, item: &NestedMeta) -> Option<u64> {
+  match item {
+    &NestedMeta::Meta(Meta::NameValue(ref name_value)) if name_value.ident == key => {
+      match &name_value.lit {
+        &Lit::Int(ref lit_int) => Some(lit_int.value()),
+        _ => None,
+      }
+    },
+    _ => None,
+  }
+}
+
 fn get_key_value(key: &str, item: &NestedMeta) -> Option<String> {
   match item {
     &NestedMeta::Meta(ref meta) => match meta {
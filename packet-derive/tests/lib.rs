@@ -13,5 +13,5 @@ fn it_works() {
   assert_eq!(Example::kind(), PacketKind::C1);
   assert_eq!(Example::CODE, 0x00);
   assert_eq!(Example::subcodes(), &[0x06, 0x07]);
-  assert_eq!(&Example::identifier(), &[0x00, 0x06, 0x07]);
+  assert_eq!(Example::identifier().as_bytes(), &[0x00, 0x06, 0x07]);
 }
@@ -0,0 +1,138 @@
+use byteorder::{BigEndian, ByteOrder};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::convert::TryFrom;
+use std::{fmt, io};
+
+/// Size of the length prefix preceding each sealed frame.
+const LENGTH_SIZE: usize = 2;
+
+/// Label of the key for frames sent by the client.
+const CLIENT_LABEL: &[u8] = b"muonline-packet aead client to server";
+
+/// Label of the key for frames sent by the server.
+const SERVER_LABEL: &[u8] = b"muonline-packet aead server to client";
+
+/// A ChaCha20-Poly1305 transport for sealing entire frames.
+///
+/// This is only intended for custom clients & servers, since stock clients
+/// are oblivious to it. Each sealed frame is prefixed by its length (in big
+/// endian) and uses a sequential nonce. Since the nonce sequence is
+/// restarted for every instance, each direction's key is derived separately
+/// from the secret of the session's key exchange.
+pub struct FrameAead {
+  cipher: ChaCha20Poly1305,
+  sequence: u64,
+}
+
+impl FrameAead {
+  /// Creates the transport of frames sent by the client, from the secret of
+  /// the key exchange (i.e for the client's encryption, and the server's
+  /// decryption).
+  pub fn client_to_server(secret: &[u8]) -> Self { Self::derive(secret, CLIENT_LABEL) }
+
+  /// Creates the transport of frames sent by the server, from the secret of
+  /// the key exchange (i.e for the server's encryption, and the client's
+  /// decryption).
+  pub fn server_to_client(secret: &[u8]) -> Self { Self::derive(secret, SERVER_LABEL) }
+
+  /// Creates a transport using a key derived (with HKDF-SHA256) from a secret.
+  fn derive(secret: &[u8], label: &[u8]) -> Self {
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(None, secret)
+      .expand(label, &mut key)
+      .expect("key size is valid for HKDF-SHA256");
+
+    FrameAead {
+      cipher: ChaCha20Poly1305::new(&key),
+      sequence: 0,
+    }
+  }
+
+  /// Returns a copy of the transport for a new connection.
+  pub(crate) fn fresh(&self) -> Self {
+    FrameAead {
+      cipher: self.cipher.clone(),
+      sequence: 0,
+    }
+  }
+
+  /// Seals a frame, returning it with its length prefix.
+  pub(crate) fn seal(&mut self, frame: &[u8]) -> io::Result<Vec<u8>> {
+    let nonce = self.next_nonce();
+    let sealed = self
+      .cipher
+      .encrypt(&nonce, frame)
+      .map_err(|_| io::Error::other("failed to seal packet"))?;
+    let size = u16::try_from(sealed.len())
+      .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "sealed packet too large"))?;
+
+    let mut bytes = vec![0; LENGTH_SIZE];
+    BigEndian::write_u16(&mut bytes, size);
+    bytes.extend_from_slice(&sealed);
+    Ok(bytes)
+  }
+
+  /// Opens a sealed frame, returning it and the number of bytes it spans.
+  pub(crate) fn open(&mut self, input: &[u8]) -> io::Result<Option<(Vec<u8>, usize)>> {
+    if input.len() < LENGTH_SIZE {
+      return Ok(None);
+    }
+
+    let size = LENGTH_SIZE + BigEndian::read_u16(input) as usize;
+    if input.len() < size {
+      return Ok(None);
+    }
+
+    let nonce = self.next_nonce();
+    let frame = self
+      .cipher
+      .decrypt(&nonce, &input[LENGTH_SIZE..size])
+      .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "packet authentication failed"))?;
+    Ok(Some((frame, size)))
+  }
+
+  /// Returns the nonce for the next frame.
+  fn next_nonce(&mut self) -> Nonce {
+    let mut nonce = Nonce::default();
+    BigEndian::write_u64(&mut nonce[4..], self.sequence);
+    self.sequence += 1;
+    nonce
+  }
+}
+
+impl fmt::Debug for FrameAead {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    fmt
+      .debug_struct("FrameAead")
+      .field("sequence", &self.sequence)
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn seal_open() {
+    let frame = [0xC1, 0x04, 0x00, 0x01];
+    let secret = [7; 32];
+    let mut sender = FrameAead::client_to_server(&secret);
+    let mut receiver = FrameAead::client_to_server(&secret);
+
+    let sealed = sender.seal(&frame).unwrap();
+    assert_eq!(sealed.len(), LENGTH_SIZE + frame.len() + 16);
+    assert_eq!(receiver.open(&sealed[..10]).unwrap(), None);
+    assert_eq!(receiver.open(&sealed).unwrap(), Some((frame.to_vec(), sealed.len())));
+
+    // Replaying a frame must fail, due to the nonce sequence
+    assert!(receiver.open(&sealed).is_err());
+
+    // Each direction uses its own key, despite sharing the secret
+    let mut receiver = FrameAead::server_to_client(&secret);
+    assert!(receiver.open(&sealed).is_err());
+  }
+}
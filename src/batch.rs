@@ -0,0 +1,161 @@
+//! Batching of outbound packets per server tick.
+//!
+//! A tick-based server (e.g running 25 ticks a second) may send dozens of
+//! packets to each client per tick. Writing each of them as sent costs a
+//! syscall per packet, so they are instead encoded into a buffer as queued,
+//! and written once the tick ends. Frames are stored back-to-back, so an
+//! entire tick (e.g any number of small C1 frames) is usually written in a
+//! single syscall.
+
+use crate::{Packet, PacketCodec};
+use bytes::BytesMut;
+use futures::{try_ready, Async, AsyncSink, Poll, Sink, StartSend};
+use std::io;
+use tokio_io::codec::Encoder;
+use tokio_io::AsyncWrite;
+
+/// Default number of bytes queued before applying back pressure.
+const DEFAULT_LIMIT: usize = 64 * 1024;
+
+/// A sink of packets, written once per tick.
+///
+/// Packets are either queued using `queue`, and written by `poll_tick` once
+/// the tick ends, or sent as a regular sink, in which case they are written
+/// whenever the sink is flushed.
+#[derive(Debug)]
+pub struct TickBatcher<T> {
+  io: T,
+  codec: PacketCodec,
+  buffer: BytesMut,
+  limit: usize,
+  packets: u64,
+  writes: u64,
+}
+
+impl<T: AsyncWrite> TickBatcher<T> {
+  /// Creates a batcher, encoding packets using a codec.
+  pub fn new(io: T, codec: PacketCodec) -> Self { Self::with_limit(io, codec, DEFAULT_LIMIT) }
+
+  /// Creates a batcher, with a number of bytes queued before the sink
+  /// applies back pressure.
+  pub fn with_limit(io: T, codec: PacketCodec, limit: usize) -> Self {
+    TickBatcher {
+      io,
+      codec,
+      buffer: BytesMut::new(),
+      limit,
+      packets: 0,
+      writes: 0,
+    }
+  }
+
+  /// Queues a packet for the current tick, without writing anything.
+  ///
+  /// The packet is encoded (and encrypted) immediately, so packets are sent
+  /// in the order queued.
+  pub fn queue(&mut self, packet: Packet) -> io::Result<()> {
+    self.codec.encode(packet, &mut self.buffer)?;
+    self.packets += 1;
+    Ok(())
+  }
+
+  /// Writes all packets queued during the tick, and flushes the writer.
+  pub fn poll_tick(&mut self) -> Poll<(), io::Error> {
+    while !self.buffer.is_empty() {
+      let written = try_ready!(self.io.poll_write(&self.buffer));
+      if written == 0 {
+        let error = io::Error::new(io::ErrorKind::WriteZero, "failed to write queued packets");
+        return Err(error);
+      }
+
+      self.buffer.split_to(written);
+      self.writes += 1;
+    }
+
+    try_ready!(self.io.poll_flush());
+    Ok(Async::Ready(()))
+  }
+
+  /// Returns the number of bytes queued, but not yet written.
+  pub fn pending(&self) -> usize { self.buffer.len() }
+
+  /// Returns the number of packets queued in total.
+  pub fn packets(&self) -> u64 { self.packets }
+
+  /// Returns the number of writes performed in total.
+  pub fn writes(&self) -> u64 { self.writes }
+
+  /// Returns a reference to the codec.
+  pub fn codec(&self) -> &PacketCodec { &self.codec }
+
+  /// Returns a mutable reference to the codec.
+  pub fn codec_mut(&mut self) -> &mut PacketCodec { &mut self.codec }
+
+  /// Returns a reference to the underlying writer.
+  pub fn get_ref(&self) -> &T { &self.io }
+
+  /// Returns a mutable reference to the underlying writer.
+  pub fn get_mut(&mut self) -> &mut T { &mut self.io }
+
+  /// Consumes the batcher, returning the underlying writer (dropping any
+  /// queued packets).
+  pub fn into_inner(self) -> T { self.io }
+}
+
+impl<T: AsyncWrite> Sink for TickBatcher<T> {
+  type SinkItem = Packet;
+  type SinkError = io::Error;
+
+  fn start_send(&mut self, packet: Packet) -> StartSend<Packet, io::Error> {
+    if self.buffer.len() >= self.limit {
+      self.poll_tick()?;
+      if self.buffer.len() >= self.limit {
+        return Ok(AsyncSink::NotReady(packet));
+      }
+    }
+
+    self.queue(packet)?;
+    Ok(AsyncSink::Ready)
+  }
+
+  fn poll_complete(&mut self) -> Poll<(), io::Error> { self.poll_tick() }
+
+  fn close(&mut self) -> Poll<(), io::Error> {
+    try_ready!(self.poll_tick());
+    self.io.shutdown()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{PacketCodecState, PacketKind};
+  use std::io::Cursor;
+
+  #[test]
+  fn tick() {
+    let codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let mut batcher = TickBatcher::new(Cursor::new(Vec::new()), codec);
+
+    for code in 0..3 {
+      batcher.queue(Packet::new(PacketKind::C1, code)).unwrap();
+    }
+    assert_eq!((batcher.pending(), batcher.writes()), (9, 0));
+
+    assert!(batcher.poll_tick().unwrap().is_ready());
+    assert_eq!((batcher.packets(), batcher.writes()), (3, 1));
+    let expected = [0xC1, 0x03, 0x00, 0xC1, 0x03, 0x01, 0xC1, 0x03, 0x02];
+    assert_eq!(batcher.into_inner().into_inner(), expected);
+  }
+
+  #[test]
+  fn back_pressure() {
+    let codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let mut batcher = TickBatcher::with_limit(Cursor::new(Vec::new()), codec, 3);
+
+    // Reaching the limit writes the queued packets, making room for more
+    assert!(batcher.start_send(Packet::new(PacketKind::C1, 0x00)).unwrap().is_ready());
+    assert!(batcher.start_send(Packet::new(PacketKind::C1, 0x01)).unwrap().is_ready());
+    assert_eq!((batcher.pending(), batcher.writes()), (3, 1));
+  }
+}
@@ -0,0 +1,170 @@
+use crate::Packet;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use std::{error, fmt};
+
+/// The usage of a packet code within the current window.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+  packets: u32,
+  bytes: usize,
+}
+
+impl Usage {
+  /// Returns the number of packets received.
+  pub fn packets(&self) -> u32 { self.packets }
+
+  /// Returns the number of bytes received.
+  pub fn bytes(&self) -> usize { self.bytes }
+}
+
+/// A limit of a packet code per window.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct Limit {
+  packets: Option<u32>,
+  bytes: Option<usize>,
+}
+
+/// A packet code which exceeded its budget.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BudgetExceeded {
+  /// The packet's code.
+  pub code: u8,
+  /// The code's usage within the window, including the packet.
+  pub usage: Usage,
+}
+
+impl fmt::Display for BudgetExceeded {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      fmt,
+      "packet budget exceeded for code {:02X} ({} packets, {} bytes)",
+      self.code, self.usage.packets, self.usage.bytes
+    )
+  }
+}
+
+impl error::Error for BudgetExceeded {}
+
+/// An accounting of packets & bytes received per code, over a time window.
+///
+/// This allows expensive requests (e.g opening the warehouse, or creating a
+/// character) to be throttled per client. Each code's window starts with its
+/// first packet, and usage is reset once it has elapsed. Usage is always
+/// accounted, so it can be queried for codes without a limit.
+///
+/// Cloning a budget retains its limits, but resets its usage.
+#[derive(Debug)]
+pub struct Budget {
+  window: Duration,
+  limits: HashMap<u8, Limit>,
+  usage: HashMap<u8, (Instant, Usage)>,
+}
+
+impl Budget {
+  /// Creates a budget without limits, accounted over a window.
+  pub fn new(window: Duration) -> Self {
+    Budget {
+      window,
+      limits: HashMap::new(),
+      usage: HashMap::new(),
+    }
+  }
+
+  /// Limits the number of packets with a code per window.
+  pub fn limit_packets(mut self, code: u8, packets: u32) -> Self {
+    self.limits.entry(code).or_default().packets = Some(packets);
+    self
+  }
+
+  /// Limits the number of bytes of packets with a code per window.
+  pub fn limit_bytes(mut self, code: u8, bytes: usize) -> Self {
+    self.limits.entry(code).or_default().bytes = Some(bytes);
+    self
+  }
+
+  /// Returns the usage of a code within its current window.
+  pub fn usage(&self, code: u8) -> Usage { self.usage_at(code, Instant::now()) }
+
+  /// Returns the usage of a code within its window at a point in time.
+  pub fn usage_at(&self, code: u8, now: Instant) -> Usage {
+    match self.usage.get(&code) {
+      Some(&(start, usage)) if now.duration_since(start) < self.window => usage,
+      _ => Usage::default(),
+    }
+  }
+
+  /// Accounts for a received packet, spanning a number of bytes.
+  ///
+  /// An error is returned if its code exceeded the budget, in which case the
+  /// packet is accounted regardless.
+  pub fn record(&mut self, packet: &Packet, size: usize) -> Result<(), BudgetExceeded> {
+    self.record_at(packet, size, Instant::now())
+  }
+
+  /// Accounts for a received packet, at a point in time.
+  pub fn record_at(
+    &mut self,
+    packet: &Packet,
+    size: usize,
+    now: Instant,
+  ) -> Result<(), BudgetExceeded> {
+    let code = packet.code();
+    let window = self.window;
+    let (start, usage) = self.usage.entry(code).or_insert((now, Usage::default()));
+
+    if now.duration_since(*start) >= window {
+      *start = now;
+      *usage = Usage::default();
+    }
+
+    usage.packets = usage.packets.saturating_add(1);
+    usage.bytes = usage.bytes.saturating_add(size);
+
+    let limit = self.limits.get(&code).cloned().unwrap_or_default();
+    if limit.packets.is_some_and(|packets| usage.packets > packets)
+      || limit.bytes.is_some_and(|bytes| usage.bytes > bytes)
+    {
+      return Err(BudgetExceeded { code, usage: *usage });
+    }
+
+    Ok(())
+  }
+}
+
+impl Clone for Budget {
+  fn clone(&self) -> Self {
+    Budget {
+      window: self.window,
+      limits: self.limits.clone(),
+      usage: HashMap::new(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PacketKind;
+
+  #[test]
+  fn windows() {
+    let window = Duration::from_secs(10);
+    let mut budget = Budget::new(window).limit_packets(0x81, 2).limit_bytes(0xF3, 8);
+    let warehouse = Packet::new(PacketKind::C1, 0x81);
+    let character = Packet::new(PacketKind::C1, 0xF3);
+    let start = Instant::now();
+
+    assert!(budget.record_at(&warehouse, 3, start).is_ok());
+    assert!(budget.record_at(&warehouse, 3, start).is_ok());
+    let error = budget.record_at(&warehouse, 3, start).unwrap_err();
+    assert_eq!(error.usage.packets(), 3);
+    assert!(budget.record_at(&warehouse, 3, start + window).is_ok());
+    assert_eq!(budget.usage_at(0x81, start + window).packets(), 1);
+
+    assert!(budget.record_at(&character, 5, start).is_ok());
+    assert!(budget.record_at(&character, 5, start).is_err());
+    assert_eq!(budget.usage_at(0xF3, start).bytes(), 10);
+    assert_eq!(budget.clone().usage_at(0xF3, start), Usage::default());
+  }
+}
@@ -0,0 +1,142 @@
+//! Fuzzing corpora, exported from captured traffic.
+//!
+//! Seeding a fuzzer with real frames lets it exercise realistic inputs,
+//! instead of spending its time discovering the framing. Each packet is
+//! added as its plaintext frame and, if keys are set for its direction, as
+//! an encrypted frame using consecutive counters (as on the wire).
+
+use crate::{Direction, Packet, PacketCrypto, PacketKind};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::{fmt, io};
+
+/// A deduplicated corpus of raw frames.
+#[derive(Clone, Default)]
+pub struct Corpus {
+  frames: Vec<Vec<u8>>,
+  seen: HashSet<Vec<u8>>,
+  encryption: [Option<(PacketCrypto, u8)>; 2],
+}
+
+impl Corpus {
+  /// Creates an empty corpus, without encrypted frames.
+  pub fn new() -> Self { Self::default() }
+
+  /// Adds encrypted variants of packets in a direction, using keys.
+  pub fn encrypt(mut self, direction: Direction, crypto: PacketCrypto) -> Self {
+    self.encryption[Self::index(direction)] = Some((crypto, 0));
+    self
+  }
+
+  /// Adds a packet, returning the number of new frames.
+  pub fn add(&mut self, direction: Direction, packet: &Packet) -> usize {
+    let mut added = self.add_frame(&packet.to_bytes()) as usize;
+
+    if let Some((crypto, counter)) = &mut self.encryption[Self::index(direction)] {
+      // Packets too large to encrypt are only added as plaintext
+      let size = PacketCrypto::encrypt_len(packet.len() + 1) + PacketKind::C4.offset();
+      if size <= PacketKind::C4.max_size() {
+        let frame = packet.to_bytes_ex(None, Some((crypto, *counter)));
+        *counter = counter.wrapping_add(1);
+        added += self.add_frame(&frame) as usize;
+      }
+    }
+
+    added
+  }
+
+  /// Adds a sequence of packets, e.g a captured session.
+  pub fn add_all<'a, I>(&mut self, packets: I) -> usize
+  where
+    I: IntoIterator<Item = (Direction, &'a Packet)>,
+  {
+    packets
+      .into_iter()
+      .map(|(direction, packet)| self.add(direction, packet))
+      .sum()
+  }
+
+  /// Adds a raw frame, returning whether it was new.
+  pub fn add_frame(&mut self, frame: &[u8]) -> bool {
+    if !self.seen.insert(frame.to_vec()) {
+      return false;
+    }
+
+    self.frames.push(frame.to_vec());
+    true
+  }
+
+  /// Returns the frames, in the order they were added.
+  pub fn frames(&self) -> impl Iterator<Item = &[u8]> { self.frames.iter().map(Vec::as_slice) }
+
+  /// Returns the number of frames.
+  pub fn len(&self) -> usize { self.frames.len() }
+
+  /// Returns whether there are any frames.
+  pub fn is_empty(&self) -> bool { self.frames.is_empty() }
+
+  /// Writes each frame to a file in a directory (e.g a cargo-fuzz corpus).
+  ///
+  /// Files are named by a hash of their content, so exporting to an existing
+  /// corpus does not duplicate frames. Returns the number of files written.
+  pub fn write_to<P: AsRef<Path>>(&self, directory: P) -> io::Result<usize> {
+    let directory = directory.as_ref();
+    fs::create_dir_all(directory)?;
+
+    let mut written = 0;
+    for frame in &self.frames {
+      let path = directory.join(format!("{:016x}", fnv1a(frame)));
+      if !path.exists() {
+        fs::write(path, frame)?;
+        written += 1;
+      }
+    }
+    Ok(written)
+  }
+
+  fn index(direction: Direction) -> usize {
+    match direction {
+      Direction::Inbound => 0,
+      Direction::Outbound => 1,
+    }
+  }
+}
+
+impl fmt::Debug for Corpus {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    fmt.debug_struct("Corpus").field("frames", &self.frames.len()).finish()
+  }
+}
+
+/// Returns the 64-bit FNV-1a hash of bytes.
+fn fnv1a(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+    (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01B3)
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::crypto;
+
+  #[test]
+  fn deduplicated() {
+    let mut corpus = Corpus::new().encrypt(Direction::Outbound, crypto::CLIENT.clone());
+    let ping = Packet::new(PacketKind::C1, 0x0E);
+
+    assert_eq!(corpus.add(Direction::Inbound, &ping), 1);
+    assert_eq!(corpus.add(Direction::Inbound, &ping), 0);
+    // Encrypted frames differ by their counter
+    assert_eq!(corpus.add(Direction::Outbound, &ping), 1);
+    assert_eq!(corpus.add_all(vec![(Direction::Outbound, &ping)]), 1);
+    assert_eq!(corpus.len(), 3);
+    assert_eq!(corpus.frames().filter(|frame| frame[0] == 0xC3).count(), 2);
+
+    let directory = std::env::temp_dir().join(format!("corpus-{}", std::process::id()));
+    assert_eq!(corpus.write_to(&directory).unwrap(), 3);
+    assert_eq!(corpus.write_to(&directory).unwrap(), 0);
+    fs::remove_dir_all(directory).unwrap();
+  }
+}
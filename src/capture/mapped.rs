@@ -0,0 +1,157 @@
+//! Memory-mapped reading of raw captures.
+//!
+//! A raw capture is one direction of a connection, stored as its frames
+//! back-to-back (e.g a dump of a TCP stream). Mapping it lets the operating
+//! system page it in on demand, so even multi-gigabyte captures from soak
+//! tests are analyzed without loading them into memory. Frames are only
+//! parsed & decrypted as they are iterated.
+
+use crate::header::PacketHeader;
+use crate::{Packet, PacketCodecState};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// A memory-mapped raw capture.
+#[derive(Debug)]
+pub struct MappedCapture {
+  map: Mmap,
+}
+
+impl MappedCapture {
+  /// Maps a raw capture file.
+  ///
+  /// The file must not be truncated while it is mapped, since reading the
+  /// missing pages would then crash the process.
+  pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    let file = File::open(path)?;
+    // SAFETY: The mapping is read-only, and the file is required to remain
+    // unchanged for its lifetime.
+    let map = unsafe { Mmap::map(&file)? };
+    Ok(MappedCapture { map })
+  }
+
+  /// Returns the bytes of the capture.
+  pub fn bytes(&self) -> &[u8] { &self.map }
+
+  /// Returns the size of the capture.
+  pub fn len(&self) -> usize { self.map.len() }
+
+  /// Returns whether the capture is empty.
+  pub fn is_empty(&self) -> bool { self.map.is_empty() }
+
+  /// Returns an iterator of the capture's raw frames, with their offsets.
+  pub fn frames(&self) -> Frames<'_> {
+    Frames {
+      bytes: &self.map,
+      offset: 0,
+      failed: false,
+    }
+  }
+
+  /// Returns an iterator of the capture's packets, with their offsets.
+  ///
+  /// Frames are decrypted using a state's cipher & keys. Only these are
+  /// applied, so captures using obfuscation or trailers are not supported.
+  pub fn packets(&self, state: PacketCodecState) -> Packets<'_> {
+    Packets {
+      frames: self.frames(),
+      state,
+    }
+  }
+}
+
+/// An iterator of raw frames, yielding each frame with its offset.
+///
+/// Iteration stops after the first error, e.g an invalid or truncated frame.
+#[derive(Debug)]
+pub struct Frames<'a> {
+  bytes: &'a [u8],
+  offset: usize,
+  failed: bool,
+}
+
+impl<'a> Iterator for Frames<'a> {
+  type Item = io::Result<(usize, &'a [u8])>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let input = &self.bytes[self.offset..];
+    if input.is_empty() || self.failed {
+      return None;
+    }
+
+    let offset = self.offset;
+    let size = match PacketHeader::peek(input) {
+      Ok(Some(header)) if header.size() <= input.len() => Ok(header.size()),
+      Ok(_) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated frame")),
+      Err(error) => Err(error),
+    }
+    .map_err(|error| {
+      self.failed = true;
+      io::Error::new(error.kind(), format!("at byte {}: {}", offset, error))
+    });
+
+    Some(size.map(|size| {
+      self.offset += size;
+      (offset, &input[..size])
+    }))
+  }
+}
+
+/// An iterator of decoded packets, yielding each packet with its offset.
+#[derive(Debug)]
+pub struct Packets<'a> {
+  frames: Frames<'a>,
+  state: PacketCodecState,
+}
+
+impl Iterator for Packets<'_> {
+  type Item = io::Result<(usize, Packet)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let (offset, frame) = match self.frames.next()? {
+      Ok(frame) => frame,
+      Err(error) => return Some(Err(error)),
+    };
+
+    Some(self.state.parse(frame).map(|(packet, _, _)| (offset, packet)).map_err(|error| {
+      self.frames.failed = true;
+      io::Error::new(error.kind(), format!("at byte {}: {}", offset, error))
+    }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::crypto;
+  use std::fs;
+
+  #[test]
+  fn mapped() {
+    let ping = Packet::from_bytes(&[0xC1, 0x04, 0x0E, 0x01]).unwrap();
+    let mut bytes = ping.to_bytes();
+    bytes.extend(ping.to_bytes_ex(None, Some((&crypto::CLIENT, 0))));
+    bytes.extend(&[0xC1, 0x05, 0x0E]);
+
+    let path = std::env::temp_dir().join(format!("mapped-{}", std::process::id()));
+    fs::write(&path, &bytes).unwrap();
+    let capture = MappedCapture::open(&path).unwrap();
+    assert_eq!(capture.len(), bytes.len());
+
+    let frames = capture.frames().collect::<Vec<_>>();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0].as_ref().unwrap(), &(0, &bytes[..4]));
+    assert!(frames[2].as_ref().unwrap_err().to_string().ends_with("truncated frame"));
+
+    let state = PacketCodecState::builder().crypto(crypto::CLIENT.clone()).build();
+    let packets = capture.packets(state).collect::<Vec<_>>();
+    let (offset, packet) = packets[1].as_ref().unwrap();
+    assert_eq!((*offset, packet.data()), (4, ping.data()));
+    assert!(packets[2].is_err());
+
+    drop(capture);
+    fs::remove_file(path).unwrap();
+  }
+}
@@ -0,0 +1,8 @@
+//! Storage & export of captured packet sessions.
+
+pub mod corpus;
+#[cfg(feature = "mmap")]
+pub mod mapped;
+pub mod recorder;
+#[cfg(feature = "capture_store")]
+pub mod store;
@@ -0,0 +1,229 @@
+//! Recording of sessions off the hot path.
+//!
+//! Packets are passed over a bounded channel to a background thread, which
+//! writes them in batches. Recording a packet never performs any I/O itself,
+//! so full-session recording can be enabled on a production server. Once the
+//! channel is full, packets are either dropped (and counted) or the caller
+//! waits for room, depending on the method used.
+
+use crate::{Direction, Packet};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+
+/// The maximum number of packets written at once.
+const BATCH_SIZE: usize = 256;
+
+/// A recorded packet.
+#[derive(Clone, Debug)]
+pub struct Recorded {
+  /// The packet's direction, relative to the recording endpoint.
+  pub direction: Direction,
+  /// The decrypted packet.
+  pub packet: Packet,
+  /// When the packet was recorded.
+  pub timestamp: SystemTime,
+}
+
+/// A destination of recorded packets, written in batches.
+///
+/// This is implemented for closures, e.g to record into a capture store
+/// using `CaptureStore::record_all`.
+pub trait Recording: Send + 'static {
+  /// Writes a batch of packets, in the order they were recorded.
+  fn write(&mut self, batch: &[Recorded]) -> io::Result<()>;
+}
+
+impl<F> Recording for F
+where
+  F: FnMut(&[Recorded]) -> io::Result<()> + Send + 'static,
+{
+  fn write(&mut self, batch: &[Recorded]) -> io::Result<()> { self(batch) }
+}
+
+/// A recording of raw frames, with a writer per direction.
+///
+/// Each writer receives its direction's frames back-to-back, i.e as a raw
+/// capture, and is flushed after each batch.
+#[derive(Debug)]
+pub struct RawRecording<W> {
+  inbound: W,
+  outbound: W,
+}
+
+impl<W: Write + Send + 'static> RawRecording<W> {
+  /// Creates a raw recording, using a writer for each direction.
+  pub fn new(inbound: W, outbound: W) -> Self { RawRecording { inbound, outbound } }
+
+  /// Returns the writers, as `(inbound, outbound)`.
+  pub fn into_inner(self) -> (W, W) { (self.inbound, self.outbound) }
+}
+
+impl<W: Write + Send + 'static> Recording for RawRecording<W> {
+  fn write(&mut self, batch: &[Recorded]) -> io::Result<()> {
+    for recorded in batch {
+      let writer = match recorded.direction {
+        Direction::Inbound => &mut self.inbound,
+        Direction::Outbound => &mut self.outbound,
+      };
+      writer.write_all(&recorded.packet.to_bytes())?;
+    }
+
+    self.inbound.flush()?;
+    self.outbound.flush()
+  }
+}
+
+/// A recorder, writing packets to a recording on a background thread.
+#[derive(Debug)]
+pub struct Recorder {
+  handle: RecorderHandle,
+  worker: JoinHandle<io::Result<()>>,
+}
+
+impl Recorder {
+  /// Spawns a recorder, buffering up to a number of packets.
+  pub fn spawn<R: Recording>(recording: R, capacity: usize) -> Self {
+    let (sender, receiver) = mpsc::sync_channel(capacity);
+    let worker = thread::spawn(move || run(recording, receiver));
+
+    Recorder {
+      handle: RecorderHandle {
+        sender,
+        dropped: Arc::default(),
+      },
+      worker,
+    }
+  }
+
+  /// Returns a handle for recording packets, e.g for each connection.
+  pub fn handle(&self) -> RecorderHandle { self.handle.clone() }
+
+  /// Stops recording once all handles are dropped, waiting for the recording
+  /// to be written.
+  ///
+  /// Returns the first error of the recording, after which any further
+  /// packets were dropped.
+  pub fn finish(self) -> io::Result<()> {
+    drop(self.handle);
+    self
+      .worker
+      .join()
+      .unwrap_or_else(|_| Err(io::Error::other("recorder thread panicked")))
+  }
+}
+
+/// A handle for recording packets, shared by cloning.
+///
+/// As an observer of a codec, each packet it sends & receives is recorded,
+/// dropping packets if the recorder is behind.
+#[derive(Clone, Debug)]
+pub struct RecorderHandle {
+  sender: SyncSender<Recorded>,
+  dropped: Arc<AtomicU64>,
+}
+
+impl RecorderHandle {
+  /// Records a packet without waiting, returning whether it was recorded.
+  ///
+  /// Packets are dropped if the recorder is behind, or has failed.
+  pub fn record(&self, direction: Direction, packet: &Packet) -> bool {
+    match self.sender.try_send(recorded(direction, packet)) {
+      Ok(()) => true,
+      Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        false
+      },
+    }
+  }
+
+  /// Records a packet, waiting for room if the recorder is behind.
+  ///
+  /// An error is returned if the recorder has failed.
+  pub fn record_blocking(&self, direction: Direction, packet: &Packet) -> io::Result<()> {
+    self.sender.send(recorded(direction, packet)).map_err(|_| {
+      self.dropped.fetch_add(1, Ordering::Relaxed);
+      io::Error::new(io::ErrorKind::BrokenPipe, "recorder has stopped")
+    })
+  }
+
+  /// Returns the number of packets dropped, by all handles of the recorder.
+  pub fn dropped(&self) -> u64 { self.dropped.load(Ordering::Relaxed) }
+}
+
+#[cfg(feature = "codec")]
+impl crate::Observer for RecorderHandle {
+  fn frame_received(&self, packet: &Packet, _size: usize) {
+    self.record(Direction::Inbound, packet);
+  }
+
+  fn frame_sent(&self, packet: &Packet, _size: usize) { self.record(Direction::Outbound, packet); }
+}
+
+fn recorded(direction: Direction, packet: &Packet) -> Recorded {
+  Recorded {
+    direction,
+    packet: packet.clone(),
+    timestamp: SystemTime::now(),
+  }
+}
+
+/// Writes batches of packets, until every sender is dropped.
+fn run<R: Recording>(mut recording: R, receiver: Receiver<Recorded>) -> io::Result<()> {
+  let mut batch = Vec::with_capacity(BATCH_SIZE);
+  while let Ok(first) = receiver.recv() {
+    batch.push(first);
+    batch.extend(receiver.try_iter().take(BATCH_SIZE - 1));
+    recording.write(&batch)?;
+    batch.clear();
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PacketKind;
+  use std::sync::Mutex;
+
+  #[test]
+  fn recorder() {
+    let frames = Arc::new(Mutex::new(Vec::new()));
+    let output = Arc::clone(&frames);
+    let recorder = Recorder::spawn(
+      move |batch: &[Recorded]| {
+        let mut output = output.lock().unwrap();
+        output.extend(batch.iter().map(|recorded| recorded.packet.to_bytes()));
+        Ok(())
+      },
+      16,
+    );
+
+    let handle = recorder.handle();
+    let ping = Packet::new(PacketKind::C1, 0x0E);
+    assert!(handle.record(Direction::Inbound, &ping));
+    handle.record_blocking(Direction::Outbound, &ping).unwrap();
+    drop(handle);
+
+    recorder.finish().unwrap();
+    assert_eq!(*frames.lock().unwrap(), vec![ping.to_bytes(), ping.to_bytes()]);
+  }
+
+  #[test]
+  fn failed() {
+    let recorder = Recorder::spawn(|_: &[Recorded]| Err(io::Error::other("disk full")), 1);
+    let handle = recorder.handle();
+    let ping = Packet::new(PacketKind::C1, 0x0E);
+
+    // Once the recording fails, packets are dropped
+    while handle.record_blocking(Direction::Inbound, &ping).is_ok() {}
+    assert!(!handle.record(Direction::Inbound, &ping));
+    assert_eq!(handle.dropped(), 2);
+
+    drop(handle);
+    assert_eq!(recorder.finish().unwrap_err().to_string(), "disk full");
+  }
+}
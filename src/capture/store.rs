@@ -0,0 +1,298 @@
+//! Capture sessions stored in SQLite.
+//!
+//! Each packet is a row of the `packets` table, so long captures can be
+//! analyzed with SQL:
+//!
+//! ```sql
+//! SELECT printf('%02X', code), COUNT(*) FROM packets
+//! WHERE session = 1 AND direction = 'inbound' GROUP BY code;
+//! ```
+//!
+//! Packets are stored in their decrypted form, as hexadecimal text, along
+//! with an optional decoded representation as JSON.
+
+use crate::{Direction, Packet};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &str = "
+  CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    started INTEGER NOT NULL
+  );
+  CREATE TABLE IF NOT EXISTS packets (
+    id INTEGER PRIMARY KEY,
+    session INTEGER NOT NULL REFERENCES sessions (id),
+    direction TEXT NOT NULL,
+    kind INTEGER NOT NULL,
+    code INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    hex TEXT NOT NULL,
+    decoded TEXT
+  );
+  CREATE INDEX IF NOT EXISTS packets_session_code ON packets (session, code);
+";
+
+/// A packet to record, as `(direction, packet, timestamp, decoded)`.
+pub type Record<'a> = (Direction, &'a Packet, SystemTime, Option<&'a str>);
+
+/// A captured packet, as stored.
+#[derive(Clone, Debug)]
+pub struct CapturedPacket {
+  /// The packet's direction, relative to the capturing endpoint.
+  pub direction: Direction,
+  /// The decrypted packet.
+  pub packet: Packet,
+  /// When the packet was captured.
+  pub timestamp: SystemTime,
+  /// The packet's decoded representation, as JSON.
+  pub decoded: Option<String>,
+}
+
+/// A SQLite database of capture sessions.
+#[derive(Debug)]
+pub struct CaptureStore {
+  connection: Connection,
+}
+
+impl CaptureStore {
+  /// Opens (or creates) a capture database.
+  pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    Self::with_connection(Connection::open(path).map_err(sql_error)?)
+  }
+
+  /// Opens a temporary in-memory capture database.
+  pub fn open_in_memory() -> io::Result<Self> {
+    Self::with_connection(Connection::open_in_memory().map_err(sql_error)?)
+  }
+
+  fn with_connection(connection: Connection) -> io::Result<Self> {
+    connection.execute_batch(SCHEMA).map_err(sql_error)?;
+    Ok(CaptureStore { connection })
+  }
+
+  /// Begins a new session, returning its identifier.
+  pub fn begin_session(&self, name: &str) -> io::Result<i64> {
+    self
+      .connection
+      .execute(
+        "INSERT INTO sessions (name, started) VALUES (?1, ?2)",
+        params![name, to_micros(SystemTime::now())],
+      )
+      .map_err(sql_error)?;
+    Ok(self.connection.last_insert_rowid())
+  }
+
+  /// Records a packet of a session.
+  pub fn record(
+    &self,
+    session: i64,
+    direction: Direction,
+    packet: &Packet,
+    timestamp: SystemTime,
+    decoded: Option<&str>,
+  ) -> io::Result<()> {
+    insert(&self.connection, session, (direction, packet, timestamp, decoded))
+  }
+
+  /// Records many packets of a session, within a single transaction.
+  ///
+  /// This is considerably faster than recording packets one by one.
+  pub fn record_all<'a, I>(&mut self, session: i64, packets: I) -> io::Result<()>
+  where
+    I: IntoIterator<Item = Record<'a>>,
+  {
+    let transaction = self.connection.transaction().map_err(sql_error)?;
+    for record in packets {
+      insert(&transaction, session, record)?;
+    }
+    transaction.commit().map_err(sql_error)
+  }
+
+  /// Returns all sessions, as `(id, name)`, in the order they began.
+  pub fn sessions(&self) -> io::Result<Vec<(i64, String)>> {
+    let mut statement = self
+      .connection
+      .prepare("SELECT id, name FROM sessions ORDER BY id")
+      .map_err(sql_error)?;
+    let rows = statement
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+      .map_err(sql_error)?;
+    rows.collect::<Result<_, _>>().map_err(sql_error)
+  }
+
+  /// Returns the identifier of the latest session with a name.
+  pub fn find_session(&self, name: &str) -> io::Result<Option<i64>> {
+    self
+      .connection
+      .query_row(
+        "SELECT id FROM sessions WHERE name = ?1 ORDER BY id DESC LIMIT 1",
+        params![name],
+        |row| row.get(0),
+      )
+      .optional()
+      .map_err(sql_error)
+  }
+
+  /// Returns the packets of a session, in the order they were recorded.
+  pub fn packets(&self, session: i64) -> io::Result<Vec<CapturedPacket>> {
+    let mut statement = self
+      .connection
+      .prepare(
+        "SELECT direction, timestamp, hex, decoded FROM packets
+         WHERE session = ?1 ORDER BY id",
+      )
+      .map_err(sql_error)?;
+    let rows = statement
+      .query_map(params![session], |row| {
+        Ok((
+          row.get::<_, String>(0)?,
+          row.get::<_, i64>(1)?,
+          row.get::<_, String>(2)?,
+          row.get::<_, Option<String>>(3)?,
+        ))
+      })
+      .map_err(sql_error)?;
+
+    rows
+      .map(|row| {
+        let (direction, timestamp, hex, decoded) = row.map_err(sql_error)?;
+        Ok(CapturedPacket {
+          direction: parse_direction(&direction)?,
+          packet: Packet::from_bytes(&parse_hex(&hex)?)?,
+          timestamp: UNIX_EPOCH + Duration::from_micros(timestamp as u64),
+          decoded,
+        })
+      })
+      .collect()
+  }
+
+  /// Returns the number of packets per code of a session, as
+  /// `(direction, code, count)`, ordered by direction and code.
+  pub fn code_counts(&self, session: i64) -> io::Result<Vec<(Direction, u8, u64)>> {
+    let mut statement = self
+      .connection
+      .prepare(
+        "SELECT direction, code, COUNT(*) FROM packets WHERE session = ?1
+         GROUP BY direction, code ORDER BY direction, code",
+      )
+      .map_err(sql_error)?;
+    let rows = statement
+      .query_map(params![session], |row| {
+        Ok((row.get::<_, String>(0)?, row.get(1)?, row.get::<_, i64>(2)?))
+      })
+      .map_err(sql_error)?;
+
+    rows
+      .map(|row| {
+        let (direction, code, count) = row.map_err(sql_error)?;
+        Ok((parse_direction(&direction)?, code, count as u64))
+      })
+      .collect()
+  }
+
+  /// Returns the underlying connection, for custom queries.
+  pub fn connection(&self) -> &Connection { &self.connection }
+}
+
+/// Inserts a packet into a session.
+fn insert(connection: &Connection, session: i64, record: Record) -> io::Result<()> {
+  let (direction, packet, timestamp, decoded) = record;
+  let hex = packet
+    .to_bytes()
+    .iter()
+    .map(|byte| format!("{:02X}", byte))
+    .collect::<String>();
+
+  connection
+    .prepare_cached(
+      "INSERT INTO packets (session, direction, kind, code, timestamp, hex, decoded)
+       VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )
+    .and_then(|mut statement| {
+      statement.execute(params![
+        session,
+        direction.as_str(),
+        packet.kind() as u8,
+        packet.code(),
+        to_micros(timestamp),
+        hex,
+        decoded,
+      ])
+    })
+    .map(|_| ())
+    .map_err(sql_error)
+}
+
+/// Converts a time to microseconds since the Unix epoch.
+fn to_micros(time: SystemTime) -> i64 {
+  time
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |duration| duration.as_micros() as i64)
+}
+
+fn parse_direction(text: &str) -> io::Result<Direction> {
+  match text {
+    "inbound" => Ok(Direction::Inbound),
+    "outbound" => Ok(Direction::Outbound),
+    _ => Err(invalid_data(format!("invalid direction '{}'", text))),
+  }
+}
+
+fn parse_hex(text: &str) -> io::Result<Vec<u8>> {
+  (0..text.len())
+    .step_by(2)
+    .map(|index| {
+      text
+        .get(index..index + 2)
+        .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        .ok_or_else(|| invalid_data(format!("invalid packet hex '{}'", text)))
+    })
+    .collect()
+}
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn sql_error(error: rusqlite::Error) -> io::Error { io::Error::other(error) }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PacketKind;
+
+  #[test]
+  fn record_and_query() {
+    let mut store = CaptureStore::open_in_memory().unwrap();
+    let session = store.begin_session("soak").unwrap();
+    assert_eq!(store.find_session("soak").unwrap(), Some(session));
+
+    let mut login = Packet::new(PacketKind::C3, 0xF1);
+    login.append(&[0x01, 0x02]);
+    let ping = Packet::new(PacketKind::C1, 0x0E);
+    let time = UNIX_EPOCH + Duration::from_micros(1_500_000);
+
+    store
+      .record(session, Direction::Outbound, &login, time, Some(r#"{"user":"test"}"#))
+      .unwrap();
+    store
+      .record_all(session, vec![
+        (Direction::Inbound, &ping, time, None),
+        (Direction::Inbound, &ping, time, None),
+      ])
+      .unwrap();
+
+    let packets = store.packets(session).unwrap();
+    assert_eq!(packets.len(), 3);
+    assert_eq!(packets[0].packet.data(), login.data());
+    assert_eq!(packets[0].timestamp, time);
+    assert_eq!(packets[0].decoded.as_deref(), Some(r#"{"user":"test"}"#));
+
+    let counts = store.code_counts(session).unwrap();
+    assert_eq!(counts, [(Direction::Inbound, 0x0E, 2), (Direction::Outbound, 0xF1, 1)]);
+  }
+}
@@ -0,0 +1,346 @@
+//! A minimal headless game client, for bots and test harnesses.
+//!
+//! The client performs the handshake upon connecting, and provides the login
+//! and character selection flows. Keep-alives are sent in the background of
+//! any wait for packets, using a blocking socket:
+//!
+//! ```no_run
+//! # use muonline_packet::{client::Client, PacketCodec, PacketCodecState};
+//! # fn main() -> std::io::Result<()> {
+//! let codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+//! let mut client = Client::builder().connect("127.0.0.1:55901", codec)?;
+//! client.login("account", "password")?;
+//! client.select_character("Character")?;
+//! let packet = client.recv()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use bytes::BytesMut;
+use crate::crypto::scramble_credential;
+#[cfg(feature = "serialize")]
+use crate::packets::auth::LoginRequest;
+use crate::{Packet, PacketCodec, PacketKind};
+#[cfg(feature = "serialize")]
+use crate::{PacketDecodable, PacketEncodable};
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use tokio_io::codec::{Decoder, Encoder};
+
+/// Size of the account field of the login request.
+const ACCOUNT_SIZE: usize = 10;
+
+/// Default size of the password field of the login request.
+const PASSWORD_SIZE: usize = 10;
+
+/// Size of a character name.
+const NAME_SIZE: usize = 10;
+
+/// The state of a client's session.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClientState {
+  /// Greeted by the server, but not authenticated.
+  Connected,
+  /// Authenticated, but without a character selected.
+  Authenticated,
+  /// Playing a character.
+  InGame,
+}
+
+/// A client builder.
+#[derive(Debug)]
+pub struct ClientBuilder {
+  timeout: Duration,
+  keep_alive: Option<Duration>,
+  version: [u8; 5],
+  serial: [u8; 16],
+  password_size: usize,
+}
+
+impl ClientBuilder {
+  /// Connects to a server, waiting for its greeting.
+  pub fn connect<A: ToSocketAddrs>(self, address: A, codec: PacketCodec) -> io::Result<Client> {
+    let mut client = Client {
+      stream: TcpStream::connect(address)?,
+      codec,
+      input: BytesMut::new(),
+      state: ClientState::Connected,
+      started: Instant::now(),
+      last_sent: Instant::now(),
+      options: self,
+    };
+
+    client.recv_code(&[0xF1, 0x00])?;
+    Ok(client)
+  }
+
+  /// Sets how long a response may be waited for (defaults to five seconds).
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Sets the interval of keep-alives, sent when otherwise idle.
+  ///
+  /// By default, keep-alives are not sent.
+  pub fn keep_alive(mut self, interval: Duration) -> Self {
+    self.keep_alive = Some(interval);
+    self
+  }
+
+  /// Sets the client version sent when logging in (e.g `10405`).
+  pub fn version(mut self, version: &[u8; 5]) -> Self {
+    self.version = *version;
+    self
+  }
+
+  /// Sets the client serial sent when logging in.
+  pub fn serial(mut self, serial: &[u8; 16]) -> Self {
+    self.serial = *serial;
+    self
+  }
+
+  /// Sets the size of the password field (defaults to 10, later versions
+  /// use 20).
+  pub fn password_size(mut self, size: usize) -> Self {
+    self.password_size = size;
+    self
+  }
+}
+
+/// A headless client connected to a game server.
+#[derive(Debug)]
+pub struct Client {
+  stream: TcpStream,
+  codec: PacketCodec,
+  input: BytesMut,
+  state: ClientState,
+  started: Instant,
+  last_sent: Instant,
+  options: ClientBuilder,
+}
+
+impl Client {
+  /// Returns a builder for a client.
+  pub fn builder() -> ClientBuilder {
+    ClientBuilder {
+      timeout: Duration::from_secs(5),
+      keep_alive: None,
+      version: *b"10405",
+      serial: [0; 16],
+      password_size: PASSWORD_SIZE,
+    }
+  }
+
+  /// Returns the state of the session.
+  pub fn state(&self) -> ClientState { self.state }
+
+  /// Authenticates with an account.
+  ///
+  /// An error is returned if the server rejects the credentials.
+  pub fn login(&mut self, account: &str, password: &str) -> io::Result<()> {
+    let packet = self.login_request(account, password)?;
+    self.send(packet)?;
+
+    let result = self.recv_code(&[0xF1, 0x01])?;
+    match result.data().get(1) {
+      Some(0x01) => {
+        self.state = ClientState::Authenticated;
+        Ok(())
+      },
+      result => {
+        let message = format!("login rejected with result {:02X?}", result);
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, message))
+      },
+    }
+  }
+
+  /// Requests the account's characters, returning the list as received.
+  pub fn characters(&mut self) -> io::Result<Packet> {
+    let mut packet = Packet::new(PacketKind::C1, 0xF3);
+    packet.append(&[0x00]);
+    self.send(packet)?;
+    self.recv_code(&[0xF3, 0x00])
+  }
+
+  /// Selects a character, returning its information as received.
+  pub fn select_character(&mut self, name: &str) -> io::Result<Packet> {
+    let mut packet = Packet::new(PacketKind::C1, 0xF3);
+    packet.append(&[0x03]);
+    packet.append(&padded(name.as_bytes(), NAME_SIZE)?);
+    self.send(packet)?;
+
+    let information = self.recv_code(&[0xF3, 0x03])?;
+    self.state = ClientState::InGame;
+    Ok(information)
+  }
+
+  /// Sends a packet.
+  pub fn send(&mut self, packet: Packet) -> io::Result<()> {
+    let mut output = BytesMut::new();
+    self.codec.encode(packet, &mut output)?;
+    self.stream.write_all(&output)?;
+    self.last_sent = Instant::now();
+    Ok(())
+  }
+
+  /// Sends a typed packet.
+  #[cfg(feature = "serialize")]
+  pub fn send_typed<T: PacketEncodable>(&mut self, packet: &T) -> io::Result<()> {
+    self.send(packet.to_packet()?)
+  }
+
+  /// Receives the next packet, waiting up to the timeout.
+  pub fn recv(&mut self) -> io::Result<Packet> {
+    let deadline = Instant::now() + self.options.timeout;
+    let mut buffer = [0; 4096];
+
+    loop {
+      if let Some(packet) = self.codec.decode(&mut self.input)? {
+        return Ok(packet);
+      }
+
+      let now = Instant::now();
+      if now >= deadline {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out waiting for packet"));
+      }
+
+      let mut wait = deadline - now;
+      if let Some(interval) = self.options.keep_alive {
+        let due = self.last_sent + interval;
+        if now >= due {
+          let mut keep_alive = Packet::new(PacketKind::C1, 0x0E);
+          keep_alive.append(&self.tick().to_le_bytes());
+          self.send(keep_alive)?;
+          continue;
+        }
+        wait = wait.min(due - now);
+      }
+
+      self.stream.set_read_timeout(Some(wait))?;
+      match self.stream.read(&mut buffer) {
+        Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed")),
+        Ok(size) => self.input.extend_from_slice(&buffer[..size]),
+        Err(ref error)
+          if error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut =>
+        {
+          continue
+        },
+        Err(error) => return Err(error),
+      }
+    }
+  }
+
+  /// Receives the next packet starting with a code, followed by any
+  /// subcodes, skipping any others.
+  pub fn recv_code(&mut self, codes: &[u8]) -> io::Result<Packet> {
+    loop {
+      let packet = self.recv()?;
+      let identifier = std::iter::once(packet.code()).chain(packet.data().iter().cloned());
+      if codes.iter().cloned().eq(identifier.take(codes.len())) {
+        return Ok(packet);
+      }
+    }
+  }
+
+  /// Receives the next packet of a type, skipping any others.
+  #[cfg(feature = "serialize")]
+  pub fn recv_typed<T: PacketDecodable>(&mut self) -> io::Result<T> {
+    let packet = self.recv_code(T::identifier().as_bytes())?;
+    T::from_packet(&packet)
+  }
+
+  /// Returns the codec used by the client.
+  pub fn codec(&mut self) -> &mut PacketCodec { &mut self.codec }
+
+  /// Returns the number of milliseconds since connecting.
+  fn tick(&self) -> u32 { self.started.elapsed().as_millis() as u32 }
+
+  /// Returns a login request, typed unless the password's size differs.
+  fn login_request(&self, account: &str, password: &str) -> io::Result<Packet> {
+    let (version, serial) = (self.options.version, self.options.serial);
+
+    #[cfg(feature = "serialize")]
+    {
+      if self.options.password_size == PASSWORD_SIZE {
+        return LoginRequest::new(account, password, self.tick(), version, serial)?.to_packet();
+      }
+    }
+
+    let mut packet = Packet::new(PacketKind::C1, 0xF1);
+    packet.append(&[0x01]);
+    packet.append(&credential(account, ACCOUNT_SIZE)?);
+    packet.append(&credential(password, self.options.password_size)?);
+    packet.append(&self.tick().to_le_bytes());
+    packet.append(&version);
+    packet.append(&serial);
+    Ok(packet)
+  }
+}
+
+/// Returns a credential, padded to its size and encoded.
+fn credential(value: &str, size: usize) -> io::Result<Vec<u8>> {
+  let mut bytes = padded(value.as_bytes(), size)?;
+  scramble_credential(&mut bytes);
+  Ok(bytes)
+}
+
+/// Returns bytes, padded with NUL to a size.
+fn padded(bytes: &[u8], size: usize) -> io::Result<Vec<u8>> {
+  if bytes.len() > size {
+    let message = format!("value exceeds {} bytes", size);
+    return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+  }
+
+  let mut padded = bytes.to_vec();
+  padded.resize(size, 0);
+  Ok(padded)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PacketCodecState;
+  use std::net::TcpListener;
+  use std::thread;
+
+  #[test]
+  fn session() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // A server which greets, accepts any login and character, and then
+    // replies to a keep-alive
+    thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      stream.write_all(&[0xC1, 0x05, 0xF1, 0x00, 0x01]).unwrap();
+
+      let mut login = [0; 3 + 1 + 10 + 10 + 4 + 5 + 16];
+      stream.read_exact(&mut login).unwrap();
+      assert_eq!(login[4..7], [b'a' ^ 0xFC, b'c' ^ 0xCF, 0xAB]);
+      stream.write_all(&[0xC1, 0x05, 0xF1, 0x01, 0x01]).unwrap();
+
+      let mut select = [0; 3 + 1 + 10];
+      stream.read_exact(&mut select).unwrap();
+      stream.write_all(&[0xC1, 0x05, 0xF3, 0x03, 0x2A]).unwrap();
+
+      let mut keep_alive = [0; 3 + 4];
+      stream.read_exact(&mut keep_alive).unwrap();
+      assert_eq!(keep_alive[2], 0x0E);
+      stream.write_all(&[0xC1, 0x03, 0x0F]).unwrap();
+    });
+
+    let codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let mut client = Client::builder()
+      .keep_alive(Duration::from_millis(50))
+      .connect(address, codec)
+      .unwrap();
+
+    client.login("ac", "secret").unwrap();
+    assert_eq!(client.state(), ClientState::Authenticated);
+    assert_eq!(client.select_character("Hero").unwrap().data(), [0x03, 0x2A]);
+    assert_eq!(client.state(), ClientState::InGame);
+    assert_eq!(client.recv().unwrap().code(), 0x0F);
+  }
+}
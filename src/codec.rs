@@ -1,13 +1,37 @@
+use byteorder::{BigEndian, ByteOrder};
 use bytes::BytesMut;
-use crate::{Packet, PacketCrypto};
+#[cfg(feature = "aead")]
+use crate::aead::FrameAead;
+#[cfg(feature = "prometheus")]
+use crate::CodecMetrics;
+#[cfg(any(feature = "lz4", feature = "zlib"))]
+use crate::Compression;
+#[cfg(feature = "serialize")]
+use crate::PacketEncodable;
+use crate::{crypto, CryptoError, Direction, Obfuscator, Observer, Packet, PacketCrypto, PacketKind};
+use crate::header::PacketHeader;
+use crate::Diagnostic;
+use crate::{Budget, EmptyFrames, FramePolicy, PacketBytes, PacketError, RotatingCrypto, Trailer};
 use log::trace;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+#[cfg(feature = "serialize")]
+use std::marker::PhantomData;
 use std::{fmt, io};
 use tokio_io::codec::{Decoder, Encoder};
 
 /// A packet codec encryption state builder.
 pub struct PacketCodecStateBuilder {
   cipher: Option<&'static [u8]>,
-  crypto: Option<PacketCrypto>,
+  crypto: Option<Arc<PacketCrypto>>,
+  rotating: Option<Arc<RotatingCrypto>>,
+  detect: Option<Arc<PacketCrypto>>,
+  obfuscator: Option<Arc<dyn Obfuscator>>,
+  #[cfg(feature = "aead")]
+  aead: Option<FrameAead>,
+  trailer: Option<Box<dyn Trailer>>,
+  infer_counter: bool,
 }
 
 impl PacketCodecStateBuilder {
@@ -16,7 +40,17 @@ impl PacketCodecStateBuilder {
     PacketCodecState {
       cipher: self.cipher,
       crypto: self.crypto,
+      rotating: self.rotating,
+      detect: self.detect,
+      detected: None,
+      obfuscator: self.obfuscator,
+      #[cfg(feature = "aead")]
+      aead: self.aead,
+      trailer: self.trailer,
       counter: 0,
+      infer_counter: self.infer_counter,
+      inferred: false,
+      scratch: Vec::new(),
     }
   }
 
@@ -27,18 +61,82 @@ impl PacketCodecStateBuilder {
   }
 
   /// Sets the packet codec encryption.
-  pub fn crypto(mut self, crypto: PacketCrypto) -> Self {
-    self.crypto = Some(crypto);
+  ///
+  /// The keys may be shared between codecs, by passing an `Arc`.
+  pub fn crypto<C: Into<Arc<PacketCrypto>>>(mut self, crypto: C) -> Self {
+    self.crypto = Some(crypto.into());
+    self
+  }
+
+  /// Sets the packet codec encryption, selected from a key rotation.
+  ///
+  /// This is ignored if the encryption is set explicitly.
+  pub fn rotating_crypto<R: Into<Arc<RotatingCrypto>>>(mut self, rotating: R) -> Self {
+    self.rotating = Some(rotating.into());
+    self
+  }
+
+  /// Sets the packet codec encryption, if the peer's first packet is
+  /// encrypted (i.e C3/C4).
+  ///
+  /// Otherwise, encryption is disabled for the rest of the connection. This
+  /// allows accepting both stock and patched (plaintext) clients on the same
+  /// port. It only applies to decryption states.
+  pub fn detect_crypto<C: Into<Arc<PacketCrypto>>>(mut self, crypto: C) -> Self {
+    self.detect = Some(crypto.into());
+    self
+  }
+
+  /// Sets the packet codec obfuscation.
+  pub fn obfuscator<O: Obfuscator + 'static>(mut self, obfuscator: O) -> Self {
+    self.obfuscator = Some(Arc::new(obfuscator));
+    self
+  }
+
+  /// Sets the packet codec authenticated encryption.
+  #[cfg(feature = "aead")]
+  pub fn aead(mut self, aead: FrameAead) -> Self {
+    self.aead = Some(aead);
+    self
+  }
+
+  /// Sets the packet codec frame trailer.
+  pub fn trailer<T: Trailer + 'static>(mut self, trailer: T) -> Self {
+    self.trailer = Some(Box::new(trailer));
+    self
+  }
+
+  /// Infers the encryption counter from the first encrypted packet.
+  ///
+  /// This allows attaching to a session in progress (e.g when monitoring),
+  /// where the counter cannot be tracked from the connection's start. Any
+  /// later packets are verified against it as usual. It only applies to
+  /// decryption states.
+  pub fn infer_counter(mut self) -> Self {
+    self.infer_counter = true;
     self
   }
 }
 
 /// A packet codec encryption state.
+///
+/// Cloning a state shares its keys, but restarts its counters and sequences,
+/// as required for a new connection.
 #[derive(Debug, Default)]
 pub struct PacketCodecState {
   cipher: Option<&'static [u8]>,
-  crypto: Option<PacketCrypto>,
+  crypto: Option<Arc<PacketCrypto>>,
+  rotating: Option<Arc<RotatingCrypto>>,
+  detect: Option<Arc<PacketCrypto>>,
+  detected: Option<bool>,
+  obfuscator: Option<Arc<dyn Obfuscator>>,
+  #[cfg(feature = "aead")]
+  aead: Option<FrameAead>,
+  trailer: Option<Box<dyn Trailer>>,
   counter: u8,
+  infer_counter: bool,
+  inferred: bool,
+  scratch: Vec<u8>,
 }
 
 impl PacketCodecState {
@@ -47,21 +145,208 @@ impl PacketCodecState {
     Self::builder().build()
   }
 
+  /// Returns the default states of a game client, as `(encrypt, decrypt)`.
+  ///
+  /// Outbound packets use the XOR cipher, and inbound C3/C4 packets are
+  /// decrypted with the default server keys. Outbound encryption is left
+  /// out, since it applies to every packet sent.
+  pub fn client_default() -> (Self, Self) {
+    let encrypt = Self::builder().cipher(&crate::XOR_CIPHER).build();
+    let decrypt = Self::builder().crypto(crypto::SERVER.clone()).build();
+    (encrypt, decrypt)
+  }
+
+  /// Returns the default states of a game server, as `(encrypt, decrypt)`.
+  ///
+  /// Inbound packets use the XOR cipher, and inbound C3/C4 packets are
+  /// decrypted with the default client keys. Outbound encryption is left
+  /// out, since it applies to every packet sent.
+  pub fn server_default() -> (Self, Self) {
+    let encrypt = Self::new();
+    let decrypt = Self::builder()
+      .cipher(&crate::XOR_CIPHER)
+      .crypto(crypto::CLIENT.clone())
+      .build();
+    (encrypt, decrypt)
+  }
+
+  /// Returns the states of an internal server link (e.g between a game
+  /// server and a join or data server), as `(encrypt, decrypt)`.
+  ///
+  /// Internal links use the same framing, without any cipher or encryption.
+  pub fn internal_default() -> (Self, Self) { (Self::new(), Self::new()) }
+
   /// Returns a packet codec state builder.
   pub fn builder() -> PacketCodecStateBuilder {
     PacketCodecStateBuilder {
       cipher: None,
       crypto: None,
+      rotating: None,
+      detect: None,
+      obfuscator: None,
+      #[cfg(feature = "aead")]
+      aead: None,
+      trailer: None,
+      infer_counter: false,
+    }
+  }
+
+  /// Returns the state's encryption, selecting the active keys of a rotation
+  /// if none has been selected.
+  fn encryption(&mut self) -> Option<&PacketCrypto> {
+    if self.crypto.is_none() {
+      let now = SystemTime::now();
+      let active = self.rotating.as_ref().and_then(|rotating| rotating.active_at(now));
+      self.crypto = active.cloned();
+    }
+    self.crypto.as_deref()
+  }
+
+  /// Parses a frame, selecting the keys of a rotation which decrypt the first
+  /// encrypted frame, if none has been selected.
+  pub(crate) fn parse(&mut self, bytes: &[u8]) -> io::Result<(Packet, usize, Option<u8>)> {
+    if let (Some(detect), None) = (&self.detect, self.detected) {
+      let kind = bytes.first().and_then(|&byte| PacketKind::from_byte(byte));
+      if let Some(kind) = kind {
+        self.crypto = Some(detect.clone()).filter(|_| kind.is_encrypted());
+        self.detected = Some(kind.is_encrypted());
+      }
+    }
+
+    let rotating = match &self.rotating {
+      Some(rotating) if self.crypto.is_none() => rotating,
+      _ => {
+        let crypto = self.crypto.as_deref();
+        return Packet::from_bytes_buffered(bytes, self.cipher, crypto, &mut self.scratch);
+      },
+    };
+
+    let mut result = Err(PacketError::NoValidKeys.into());
+    for crypto in rotating.valid_at(SystemTime::now()) {
+      result = Packet::from_bytes_buffered(bytes, self.cipher, Some(crypto), &mut self.scratch);
+      match &result {
+        // Only encrypted packets identify the keys used
+        Ok((_, _, Some(_))) => {
+          self.crypto = Some(crypto.clone());
+          break;
+        },
+        Err(error) if is_crypto_error(error) => continue,
+        _ => break,
+      }
+    }
+    result
+  }
+}
+
+/// The version of the resumption token format.
+const RESUME_VERSION: u8 = 1;
+
+/// The size of a resumption token.
+const RESUME_SIZE: usize = 12;
+
+impl Clone for PacketCodecState {
+  fn clone(&self) -> Self {
+    PacketCodecState {
+      cipher: self.cipher,
+      // Keys selected from a rotation, or detected, are selected anew
+      crypto: self
+        .crypto
+        .clone()
+        .filter(|_| self.rotating.is_none() && self.detect.is_none()),
+      rotating: self.rotating.clone(),
+      detect: self.detect.clone(),
+      detected: None,
+      obfuscator: self.obfuscator.clone(),
+      #[cfg(feature = "aead")]
+      aead: self.aead.as_ref().map(FrameAead::fresh),
+      trailer: self.trailer.as_ref().map(|trailer| trailer.fresh()),
+      counter: 0,
+      infer_counter: self.infer_counter,
+      inferred: false,
+      scratch: Vec::new(),
+    }
+  }
+}
+
+/// Counts of decryption failures on a connection.
+///
+/// A sudden increase usually indicates a broken client, or tampering.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CryptoStats {
+  checksum_failures: u64,
+  misaligned_blocks: u64,
+  counter_mismatches: u64,
+}
+
+impl CryptoStats {
+  /// Returns the number of blocks with an invalid checksum or size.
+  pub fn checksum_failures(&self) -> u64 { self.checksum_failures }
+
+  /// Returns the number of packets not aligned to the block size.
+  pub fn misaligned_blocks(&self) -> u64 { self.misaligned_blocks }
+
+  /// Returns the number of packets with an unexpected encryption counter.
+  pub fn counter_mismatches(&self) -> u64 { self.counter_mismatches }
+
+  /// Returns the total number of failures.
+  pub fn total(&self) -> u64 {
+    self.checksum_failures + self.misaligned_blocks + self.counter_mismatches
+  }
+
+  /// Records a failure.
+  fn record(&mut self, error: &CryptoError) {
+    match error {
+      CryptoError::Checksum | CryptoError::InvalidBlockSize => self.checksum_failures += 1,
+      CryptoError::MisalignedBlock => self.misaligned_blocks += 1,
+      CryptoError::CounterMismatch { .. } => self.counter_mismatches += 1,
     }
   }
 }
 
+/// A callback invoked for each decryption failure.
+#[derive(Clone)]
+struct FailureCallback(Arc<dyn Fn(&CryptoError) + Send + Sync>);
+
+impl fmt::Debug for FailureCallback {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result { fmt.write_str("FailureCallback") }
+}
+
+/// A function handling a received probe.
+type ProbeFn = dyn Fn(&[u8]) + Send + Sync;
+
+/// A callback invoked for each probe received.
+#[derive(Clone)]
+struct ProbeCallback(Arc<ProbeFn>);
+
+impl fmt::Debug for ProbeCallback {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result { fmt.write_str("ProbeCallback") }
+}
+
 /// A Mu Online packet codec.
+///
+/// Cloning a codec creates one for a new connection, with fresh counters and
+/// statistics, sharing the keys and any callback or metrics. Compression is
+/// negotiated per connection, so it is not retained.
 #[derive(Debug)]
 pub struct PacketCodec {
   encrypt: PacketCodecState,
   decrypt: PacketCodecState,
   max_size: Option<usize>,
+  code_limits: HashMap<u8, usize>,
+  crypto_stats: CryptoStats,
+  crypto_callback: Option<FailureCallback>,
+  probes: Vec<Vec<u8>>,
+  probe_callback: Option<ProbeCallback>,
+  plain: Vec<Vec<u8>>,
+  observers: Vec<Arc<dyn Observer>>,
+  budget: Option<Budget>,
+  frame_policy: FramePolicy,
+  #[cfg(any(feature = "lz4", feature = "zlib"))]
+  compression: Option<Compression>,
+  #[cfg(feature = "prometheus")]
+  metrics: Option<CodecMetrics>,
+  #[cfg(feature = "tokio_util")]
+  pub(crate) pending: BytesMut,
 }
 
 impl PacketCodec {
@@ -71,6 +356,21 @@ impl PacketCodec {
       encrypt,
       decrypt,
       max_size: None,
+      code_limits: HashMap::new(),
+      crypto_stats: CryptoStats::default(),
+      crypto_callback: None,
+      probes: Vec::new(),
+      probe_callback: None,
+      plain: Vec::new(),
+      observers: Vec::new(),
+      budget: None,
+      frame_policy: FramePolicy::default(),
+      #[cfg(any(feature = "lz4", feature = "zlib"))]
+      compression: None,
+      #[cfg(feature = "prometheus")]
+      metrics: None,
+      #[cfg(feature = "tokio_util")]
+      pending: BytesMut::new(),
     }
   }
 
@@ -84,6 +384,182 @@ impl PacketCodec {
       encrypt,
       decrypt,
       max_size: Some(max_size),
+      code_limits: HashMap::new(),
+      crypto_stats: CryptoStats::default(),
+      crypto_callback: None,
+      probes: Vec::new(),
+      probe_callback: None,
+      plain: Vec::new(),
+      observers: Vec::new(),
+      budget: None,
+      frame_policy: FramePolicy::default(),
+      #[cfg(any(feature = "lz4", feature = "zlib"))]
+      compression: None,
+      #[cfg(feature = "prometheus")]
+      metrics: None,
+      #[cfg(feature = "tokio_util")]
+      pending: BytesMut::new(),
+    }
+  }
+
+  /// Creates a packet codec continuing a session from a resumption token.
+  ///
+  /// The states must use the same encryption as the codec which created the
+  /// token, since only its counters are restored.
+  pub fn resume(
+    token: &[u8],
+    mut encrypt: PacketCodecState,
+    mut decrypt: PacketCodecState,
+  ) -> io::Result<Self> {
+    let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+    if token.len() != RESUME_SIZE || token[0] != RESUME_VERSION {
+      return Err(invalid("invalid resumption token"));
+    }
+
+    if BigEndian::read_u32(&token[4..8]) != fingerprint(&encrypt)
+      || BigEndian::read_u32(&token[8..12]) != fingerprint(&decrypt)
+    {
+      return Err(invalid("resumption token encryption differs"));
+    }
+
+    encrypt.counter = token[1];
+    decrypt.counter = token[2];
+    decrypt.inferred = true;
+    Ok(Self::new(encrypt, decrypt))
+  }
+
+  /// Returns a token for resuming the session in another codec.
+  ///
+  /// The token contains the encryption counters and identifiers of the keys
+  /// used, but not the keys themselves.
+  pub fn resume_token(&self) -> Vec<u8> {
+    let mut token = vec![RESUME_VERSION, self.encrypt.counter, self.decrypt.counter, 0];
+    token.extend_from_slice(&fingerprint(&self.encrypt).to_be_bytes());
+    token.extend_from_slice(&fingerprint(&self.decrypt).to_be_bytes());
+    token
+  }
+
+  /// Sets the maximum size of received packets with a specific code.
+  ///
+  /// This is verified after decryption, so it applies to the packet's
+  /// original size.
+  pub fn set_code_limit(&mut self, code: u8, max_size: usize) {
+    self.code_limits.insert(code, max_size);
+  }
+
+  /// Returns the decryption failures seen by the codec.
+  pub fn crypto_stats(&self) -> CryptoStats { self.crypto_stats }
+
+  /// Sets a callback invoked for each decryption failure.
+  ///
+  /// The callback is shared with any clones of the codec.
+  pub fn set_crypto_callback<F>(&mut self, callback: F)
+  where
+    F: Fn(&CryptoError) + Send + Sync + 'static,
+  {
+    self.crypto_callback = Some(FailureCallback(Arc::new(callback)));
+  }
+
+  /// Adds a probe, i.e a fixed frame which is not a packet.
+  ///
+  /// Some launchers & status tools send tiny pings on the game port (e.g a
+  /// single byte). Instead of failing with "not a packet", received probes
+  /// are consumed and passed to the probe callback. A probe must not start
+  /// with a packet kind (i.e C1-C4), since it would be ambiguous.
+  pub fn add_probe(&mut self, probe: &[u8]) {
+    assert!(!probe.is_empty(), "probe must not be empty");
+    assert!(PacketKind::from_byte(probe[0]).is_none(), "probe must not start with a packet kind");
+    self.probes.push(probe.to_vec());
+  }
+
+  /// Sets a callback invoked for each probe received (e.g to answer it).
+  ///
+  /// The callback is shared with any clones of the codec.
+  pub fn set_probe_callback<F>(&mut self, callback: F)
+  where
+    F: Fn(&[u8]) + Send + Sync + 'static,
+  {
+    self.probe_callback = Some(ProbeCallback(Arc::new(callback)));
+  }
+
+  /// Adds codes of packets which are always sent unencrypted.
+  ///
+  /// A packet is sent as-is if it starts with the code, followed by any
+  /// subcodes (e.g the handshake & server list, which precede the encrypted
+  /// channel). Unencrypted packets do not advance the encryption counter.
+  pub fn add_plain(&mut self, codes: &[u8]) {
+    assert!(!codes.is_empty(), "plain codes must not be empty");
+    self.plain.push(codes.to_vec());
+  }
+
+  /// Encodes a packet without encryption, regardless of the codec's keys.
+  ///
+  /// Any cipher, obfuscation and trailer of the encrypting state still apply.
+  pub fn encode_plain(&mut self, packet: Packet, output: &mut BytesMut) -> io::Result<()> {
+    self.encode_packet(packet, output, false)
+  }
+
+  /// Adds an observer, notified of the codec's events.
+  ///
+  /// Observers are shared with any clones of the codec.
+  pub fn add_observer(&mut self, observer: Arc<dyn Observer>) { self.observers.push(observer); }
+
+  /// Sets the budget of received packets, enforced by the codec.
+  ///
+  /// Packets exceeding their code's budget fail with an error. The limits
+  /// are retained by clones of the codec, but not the usage.
+  pub fn set_budget(&mut self, budget: Option<Budget>) { self.budget = budget; }
+
+  /// Returns the budget of received packets, e.g to query its usage.
+  pub fn budget(&self) -> Option<&Budget> { self.budget.as_ref() }
+
+  /// Sets the handling of empty frames and padding, when decoding.
+  pub fn set_frame_policy(&mut self, policy: FramePolicy) { self.frame_policy = policy; }
+
+  /// Returns whether the peer's packets are encrypted, if detected.
+  ///
+  /// This is only determined once a packet is received, by a decryption
+  /// state with `detect_crypto`.
+  pub fn peer_encrypted(&self) -> Option<bool> { self.decrypt.detected }
+
+  /// Sets the compression of packet data, in both directions.
+  ///
+  /// This must be enabled at the same point in the stream as the peer (e.g
+  /// directly after the negotiating packets).
+  #[cfg(any(feature = "lz4", feature = "zlib"))]
+  pub fn set_compression(&mut self, compression: Option<Compression>) {
+    self.compression = compression;
+  }
+
+  /// Returns the encryption counters, for sending and receiving.
+  pub(crate) fn counters(&self) -> (u8, u8) { (self.encrypt.counter, self.decrypt.counter) }
+
+  /// Sets the metrics updated by the codec.
+  #[cfg(feature = "prometheus")]
+  pub fn set_metrics(&mut self, metrics: CodecMetrics) { self.metrics = Some(metrics); }
+}
+
+impl Clone for PacketCodec {
+  fn clone(&self) -> Self {
+    PacketCodec {
+      encrypt: self.encrypt.clone(),
+      decrypt: self.decrypt.clone(),
+      max_size: self.max_size,
+      code_limits: self.code_limits.clone(),
+      crypto_stats: CryptoStats::default(),
+      crypto_callback: self.crypto_callback.clone(),
+      probes: self.probes.clone(),
+      probe_callback: self.probe_callback.clone(),
+      plain: self.plain.clone(),
+      observers: self.observers.clone(),
+      budget: self.budget.clone(),
+      frame_policy: self.frame_policy,
+      #[cfg(any(feature = "lz4", feature = "zlib"))]
+      compression: None,
+      #[cfg(feature = "prometheus")]
+      metrics: self.metrics.clone(),
+      #[cfg(feature = "tokio_util")]
+      pending: BytesMut::new(),
     }
   }
 }
@@ -94,20 +570,11 @@ impl Encoder for PacketCodec {
 
   /// Encodes a packet into a byte buffer.
   fn encode(&mut self, packet: Packet, output: &mut BytesMut) -> io::Result<()> {
-    let bytes = packet.to_bytes_ex(
-      self.encrypt.cipher,
-      self
-        .encrypt
-        .crypto
-        .as_ref()
-        .map(|c| (c, self.encrypt.counter)),
-    );
-
-    trace!("<codec> sent: {:x}", ByteHex(&packet.to_bytes()));
-    output.extend_from_slice(&bytes);
-
-    self.encrypt.counter = self.encrypt.counter.wrapping_add(1);
-    Ok(())
+    let identifier = std::iter::once(packet.code()).chain(packet.data().iter().cloned());
+    let plain = self.plain.iter().any(|codes| {
+      codes.iter().cloned().eq(identifier.clone().take(codes.len()))
+    });
+    self.encode_packet(packet, output, !plain)
   }
 }
 
@@ -117,54 +584,503 @@ impl Decoder for PacketCodec {
 
   /// Decodes a packet from an input of bytes.
   fn decode(&mut self, input: &mut BytesMut) -> io::Result<Option<Self::Item>> {
+    loop {
+      let padding = self.frame_policy.skipped_padding(input);
+      input.split_to(padding);
+
+      match self.decode_packet(input)? {
+        Some(packet) if packet.data().is_empty() => {
+          if self.surface_empty(packet.kind(), packet.code())? {
+            return Ok(Some(packet));
+          }
+        },
+        packet => return Ok(packet),
+      }
+    }
+  }
+}
+
+impl PacketCodec {
+  /// Decodes a packet from an input of bytes, referencing the input where
+  /// possible.
+  ///
+  /// Unencrypted frames are referenced instead of copied, unless received
+  /// packets are transformed (i.e by a cipher, obfuscation, trailers, sealing
+  /// or compression), in which case packets are decoded as usual. Packets are
+  /// still copied for any observers, budget or metrics of the codec.
+  pub fn decode_bytes(&mut self, input: &mut BytesMut) -> io::Result<Option<PacketBytes>> {
+    loop {
+      let padding = self.frame_policy.skipped_padding(input);
+      input.split_to(padding);
+
+      let encrypted = input.first().and_then(|&byte| PacketKind::from_byte(byte));
+      if !self.references_frames() || encrypted.is_none_or(PacketKind::is_encrypted) {
+        return self.decode(input).map(|packet| packet.map(PacketBytes::from));
+      }
+
+      if let Some(max_size) = self.max_size.filter(|&max_size| input.len() > max_size) {
+        for observer in &self.observers {
+          observer.oversize_rejected(input.len(), max_size);
+        }
+        return Err(PacketError::SizeLimit {
+          size: input.len(),
+          limit: max_size,
+        }
+        .into());
+      }
+
+      let size = match PacketHeader::peek(input)? {
+        Some(header) if input.len() >= header.size() => header.size(),
+        _ => return Ok(None),
+      };
+
+      let packet = PacketBytes::from_frame(input.split_to(size).freeze())?;
+      trace!("<codec> {}: {:x}", Direction::Inbound, ByteHex(packet.frame()));
+
+      if let Some(&limit) = self.code_limits.get(&packet.code()) {
+        if packet.len() > limit {
+          for observer in &self.observers {
+            observer.oversize_rejected(packet.len(), limit);
+          }
+          return Err(PacketError::CodeLimit {
+            code: packet.code(),
+            size: packet.len(),
+            limit,
+          }
+          .into());
+        }
+      }
+
+      if self.has_receive_hooks() {
+        self.received(packet.to_packet(), size)?;
+      }
+
+      if !packet.data().is_empty() || self.surface_empty(packet.kind(), packet.code())? {
+        return Ok(Some(packet));
+      }
+    }
+  }
+
+  /// Encodes a packet, referencing a frame, into a byte buffer.
+  ///
+  /// The frame is copied as-is, unless sent packets are transformed, in which
+  /// case the packet is encoded as usual.
+  pub fn encode_bytes(&mut self, packet: PacketBytes, output: &mut BytesMut) -> io::Result<()> {
+    let state = &self.encrypt;
+    let transformed = state.cipher.is_some()
+      || state.crypto.is_some()
+      || state.rotating.is_some()
+      || state.obfuscator.is_some()
+      || state.trailer.is_some()
+      || self.transforms_data();
+
+    if transformed || !self.observers.is_empty() || self.has_metrics() {
+      return self.encode(packet.to_packet(), output);
+    }
+
+    output.extend_from_slice(packet.frame());
+    self.encrypt.counter = self.encrypt.counter.wrapping_add(1);
+    Ok(())
+  }
+
+  /// Returns whether received frames may be referenced as-is.
+  fn references_frames(&self) -> bool {
+    let state = &self.decrypt;
+    state.cipher.is_none()
+      && state.obfuscator.is_none()
+      && state.trailer.is_none()
+      && self.probes.is_empty()
+      && !self.transforms_data()
+  }
+
+  /// Returns whether frames are sealed, or their data is compressed.
+  fn transforms_data(&self) -> bool {
+    #[allow(unused_mut)]
+    let mut transforms = false;
+    #[cfg(feature = "aead")]
+    {
+      transforms |= self.encrypt.aead.is_some() || self.decrypt.aead.is_some();
+    }
+    #[cfg(any(feature = "lz4", feature = "zlib"))]
+    {
+      transforms |= self.compression.is_some();
+    }
+    transforms
+  }
+
+  /// Returns whether received packets are passed to any observers, budget or
+  /// metrics.
+  fn has_receive_hooks(&self) -> bool {
+    !self.observers.is_empty() || self.budget.is_some() || self.has_metrics()
+  }
+
+  fn has_metrics(&self) -> bool {
+    #[cfg(feature = "prometheus")]
+    {
+      if self.metrics.is_some() {
+        return true;
+      }
+    }
+    false
+  }
+
+  /// Returns whether an empty packet is surfaced, according to the frame
+  /// policy, failing if it is rejected.
+  fn surface_empty(&self, kind: PacketKind, code: u8) -> io::Result<bool> {
+    match self.frame_policy.empty_frames() {
+      EmptyFrames::Surface => Ok(true),
+      EmptyFrames::Skip => Ok(false),
+      EmptyFrames::Reject => {
+        let diagnostic = Diagnostic::from_error(0, PacketError::Empty).in_packet(kind, &[code]);
+        Err(io::Error::new(io::ErrorKind::InvalidData, diagnostic))
+      },
+    }
+  }
+
+  /// Encodes a packet into a byte buffer, optionally encrypted.
+  fn encode_packet(
+    &mut self,
+    packet: Packet,
+    output: &mut BytesMut,
+    encrypt: bool,
+  ) -> io::Result<()> {
+    let counter = self.encrypt.counter;
+    let cipher = self.encrypt.cipher;
+    let compressed = self.compress(&packet)?;
+    let crypto = if encrypt { self.encrypt.encryption() } else { None };
+    let mut bytes = compressed
+      .as_ref()
+      .unwrap_or(&packet)
+      .to_bytes_ex(cipher, crypto.map(|c| (c, counter)));
+
+    if let Some(obfuscator) = &self.encrypt.obfuscator {
+      let offset = PacketHeader::peek(&bytes)?.map_or(bytes.len(), |header| header.len());
+      obfuscator.obfuscate(&mut bytes[offset..]);
+    }
+
+    if let Some(trailer) = &mut self.encrypt.trailer {
+      if trailer.included() {
+        let header = PacketHeader::peek(&bytes)?.expect("encoded frame has a header");
+        header.resize(&mut bytes, header.size() + trailer.size())?;
+      }
+
+      let trailer = trailer.sign(&bytes)?;
+      bytes.extend_from_slice(&trailer);
+    }
+
+    #[cfg(feature = "aead")]
+    {
+      if let Some(aead) = &mut self.encrypt.aead {
+        bytes = aead.seal(&bytes)?;
+      }
+    }
+
+    trace!("<codec> {}: {:x}", Direction::Outbound, ByteHex(&packet.to_bytes()));
+    output.extend_from_slice(&bytes);
+
+    for observer in &self.observers {
+      observer.frame_sent(&packet, bytes.len());
+    }
+
+    #[cfg(feature = "prometheus")]
+    {
+      if let Some(metrics) = &self.metrics {
+        metrics.record(Direction::Outbound, &packet, bytes.len());
+      }
+    }
+
+    if encrypt {
+      self.encrypt.counter = self.encrypt.counter.wrapping_add(1);
+    }
+    Ok(())
+  }
+
+  /// Decodes a packet from an input of bytes, as-is.
+  fn decode_packet(&mut self, input: &mut BytesMut) -> io::Result<Option<Packet>> {
     if input.is_empty() {
       return Ok(None);
     }
 
-    if self
-      .max_size
-      .map_or(false, |max_size| input.len() > max_size)
+    // Probes are only recognized where a frame is expected
+    if !self.probes.is_empty() && PacketKind::from_byte(input[0]).is_none() {
+      match self.probes.iter().find(|probe| input.starts_with(probe)).cloned() {
+        Some(probe) => {
+          input.split_to(probe.len());
+          if let Some(ProbeCallback(callback)) = &self.probe_callback {
+            callback(&probe);
+          }
+          return self.decode(input);
+        },
+        None if self.probes.iter().any(|probe| probe.starts_with(input)) => return Ok(None),
+        None => (),
+      }
+    }
+
+    if let Some(max_size) = self.max_size.filter(|&max_size| input.len() > max_size) {
+      for observer in &self.observers {
+        observer.oversize_rejected(input.len(), max_size);
+      }
+      return Err(PacketError::SizeLimit {
+        size: input.len(),
+        limit: max_size,
+      }
+      .into());
+    }
+
+    #[cfg(feature = "aead")]
     {
-      return Err(io::Error::new(
-        io::ErrorKind::Other,
-        "max packet size exceeded",
-      ));
+      if let Some(aead) = &mut self.decrypt.aead {
+        let (frame, size) = match aead.open(input)? {
+          Some(sealed) => sealed,
+          None => return Ok(None),
+        };
+
+        // Consume the sealed frame, regardless of its content
+        input.split_to(size);
+        let (packet, _) = self
+          .decode_frame(&frame)?
+          .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "incomplete sealed packet"))?;
+        return self.received(packet, size).map(Some);
+      }
+    }
+
+    match self.decode_frame(input)? {
+      Some((packet, size)) => {
+        // Consume the used bytes from the input
+        input.split_to(size);
+        self.received(packet, size).map(Some)
+      },
+      None => Ok(None),
+    }
+  }
+
+  /// Records a received packet and the number of bytes it spanned.
+  fn received(&mut self, packet: Packet, size: usize) -> io::Result<Packet> {
+    #[cfg(feature = "prometheus")]
+    {
+      if let Some(metrics) = &self.metrics {
+        metrics.record(Direction::Inbound, &packet, size);
+      }
+    }
+
+    for observer in &self.observers {
+      observer.frame_received(&packet, size);
+    }
+
+    if let Some(budget) = &mut self.budget {
+      budget.record(&packet, size).map_err(io::Error::other)?;
     }
+    Ok(packet)
+  }
+
+  /// Decodes a single frame, returning the packet and the bytes it spans.
+  fn decode_frame(&mut self, input: &[u8]) -> io::Result<Option<(Packet, usize)>> {
+    // Wait for the entire frame before attempting to decrypt or copy it
+    let (offset, size) = match PacketHeader::peek(input)? {
+      Some(header) if input.len() >= header.size() => (header.len(), header.size()),
+      _ => return Ok(None),
+    };
+
+    let mut frame = None;
+    if self.decrypt.obfuscator.is_some() || self.decrypt.trailer.is_some() {
+      let (mut size, mut total, mut included) = (size, size, false);
+      if let Some(trailer) = &mut self.decrypt.trailer {
+        if trailer.included() {
+          included = true;
+          size = size
+            .checked_sub(trailer.size())
+            .filter(|&size| size > offset)
+            .ok_or(PacketError::InvalidSize(total))?;
+        } else {
+          total += trailer.size();
+          if input.len() < total {
+            return Ok(None);
+          }
+        }
 
-    Packet::from_bytes_ex(&input, self.decrypt.cipher, self.decrypt.crypto.as_ref())
+        trailer.verify(&input[..size], &input[size..size + trailer.size()])?;
+      }
+
+      let mut bytes = input[..size].to_vec();
+      if included {
+        // The packet itself lacks the trailer, so its size is adjusted
+        let header = PacketHeader::peek(&bytes)?.expect("decoded frame has a header");
+        header.resize(&mut bytes, size)?;
+      }
+
+      if let Some(obfuscator) = &self.decrypt.obfuscator {
+        obfuscator.deobfuscate(&mut bytes[offset..]);
+      }
+      frame = Some((bytes, total));
+    }
+
+    let bytes = frame.as_ref().map_or(&input[..size], |(bytes, _)| bytes);
+    self
+      .decrypt
+      .parse(bytes)
       .and_then(|(packet, bytes_read, decrypt_counter)| {
-        trace!("<codec> received: {:x}", ByteHex(&packet.to_bytes()));
+        trace!("<codec> {}: {:x}", Direction::Inbound, ByteHex(&packet.to_bytes()));
 
-        // Consume the used bytes from the input
-        input.split_to(bytes_read);
+        if let Some(&limit) = self.code_limits.get(&packet.code()) {
+          if packet.len() > limit {
+            for observer in &self.observers {
+              observer.oversize_rejected(packet.len(), limit);
+            }
+
+            return Err(PacketError::CodeLimit {
+              code: packet.code(),
+              size: packet.len(),
+              limit,
+            }
+            .into());
+          }
+        }
 
         // Encrypted packets contain an encryption counter
         if let Some(counter) = decrypt_counter {
+          if self.decrypt.infer_counter && !self.decrypt.inferred {
+            self.decrypt.counter = counter;
+            self.decrypt.inferred = true;
+          }
+
           // Some tampering has been done if they do not match
           if self.decrypt.counter != counter {
-            let message = format!(
-              "invalid decryption counter {}, expected {}",
-              counter, self.decrypt.counter
-            );
-            return Err(io::Error::new(io::ErrorKind::Other, message));
+            return Err(PacketError::Crypto(CryptoError::CounterMismatch {
+              expected: self.decrypt.counter,
+              received: counter,
+            })
+            .into());
           }
 
           self.decrypt.counter = self.decrypt.counter.wrapping_add(1);
         }
 
-        Ok(Some(packet))
-      }).or_else(|error| {
-        // TODO: Do the bytes received so far need to be consumed?
-        // In case data is missing, wait for more
+        let packet = self.decompress(packet)?;
+        Ok(Some((packet, frame.as_ref().map_or(bytes_read, |&(_, total)| total))))
+      }).map_err(|error| {
+        // The entire frame is buffered, so any missing data is malformed
         if error.kind() == io::ErrorKind::UnexpectedEof {
-          Ok(None)
-        } else {
-          Err(error)
+          return PacketError::InvalidSize(size).into();
         }
+
+        self.crypto_failed(&error);
+        error
       })
   }
+
+  /// Returns a packet's compressed form, if compression is enabled.
+  #[allow(unused_variables)]
+  fn compress(&self, packet: &Packet) -> io::Result<Option<Packet>> {
+    #[cfg(any(feature = "lz4", feature = "zlib"))]
+    {
+      if let Some(compression) = &self.compression {
+        return compression.compress(packet).map(Some);
+      }
+    }
+    Ok(None)
+  }
+
+  /// Returns a packet's decompressed form, if compression is enabled.
+  fn decompress(&self, packet: Packet) -> io::Result<Packet> {
+    #[cfg(any(feature = "lz4", feature = "zlib"))]
+    {
+      if let Some(compression) = &self.compression {
+        return compression.decompress(packet);
+      }
+    }
+    Ok(packet)
+  }
+
+  /// Records an error if it is caused by a decryption failure.
+  fn crypto_failed(&mut self, error: &io::Error) {
+    if let Some(PacketError::Crypto(failure)) = PacketError::of(error) {
+      self.crypto_stats.record(&failure);
+      if let Some(FailureCallback(callback)) = &self.crypto_callback {
+        callback(&failure);
+      }
+
+      for observer in &self.observers {
+        match failure {
+          CryptoError::CounterMismatch { expected, received } => {
+            observer.counter_mismatch(expected, received)
+          },
+          _ => observer.decrypt_failed(&failure),
+        }
+      }
+    }
+  }
+}
+
+/// A packet codec encoding typed packets.
+///
+/// Received packets are decoded as-is, since their type is not known.
+#[cfg(feature = "serialize")]
+#[derive(Debug)]
+pub struct TypedPacketCodec<T> {
+  codec: PacketCodec,
+  marker: PhantomData<fn(T)>,
+}
+
+#[cfg(feature = "serialize")]
+impl<T> TypedPacketCodec<T> {
+  /// Creates a new typed codec from a packet codec.
+  pub fn new(codec: PacketCodec) -> Self {
+    TypedPacketCodec {
+      codec,
+      marker: PhantomData,
+    }
+  }
+
+  /// Returns a reference to the underlying codec.
+  pub fn get_ref(&self) -> &PacketCodec { &self.codec }
+
+  /// Returns a mutable reference to the underlying codec.
+  pub fn get_mut(&mut self) -> &mut PacketCodec { &mut self.codec }
+
+  /// Consumes the typed codec, returning the underlying codec.
+  pub fn into_inner(self) -> PacketCodec { self.codec }
+}
+
+#[cfg(feature = "serialize")]
+impl<T: PacketEncodable> Encoder for TypedPacketCodec<T> {
+  type Item = T;
+  type Error = io::Error;
+
+  /// Encodes a typed packet into a byte buffer.
+  fn encode(&mut self, item: T, output: &mut BytesMut) -> io::Result<()> {
+    self.codec.encode(item.to_packet()?, output)
+  }
+}
+
+#[cfg(feature = "serialize")]
+impl<T> Decoder for TypedPacketCodec<T> {
+  type Item = Packet;
+  type Error = io::Error;
+
+  /// Decodes a packet from an input of bytes.
+  fn decode(&mut self, input: &mut BytesMut) -> io::Result<Option<Self::Item>> {
+    self.codec.decode(input)
+  }
 }
 
+/// Returns whether an error is caused by a decryption failure.
+fn is_crypto_error(error: &io::Error) -> bool {
+  matches!(PacketError::of(error), Some(PacketError::Crypto(_)))
+}
+
+/// Returns an identifier of a state's encryption (zero if there is none).
+fn fingerprint(state: &PacketCodecState) -> u32 {
+  state.crypto.as_ref().map_or(0, |crypto| crypto.fingerprint())
+}
+
+// Codecs are created per connection, possibly on any thread
+const _: fn() = || {
+  fn assert_send_sync<T: Send + Sync>() {}
+  assert_send_sync::<PacketCodec>();
+  assert_send_sync::<PacketCodecState>();
+  assert_send_sync::<PacketCrypto>();
+};
+
 struct ByteHex<'a>(&'a [u8]);
 
 impl<'a> fmt::LowerHex for ByteHex<'a> {
@@ -175,3 +1091,467 @@ impl<'a> fmt::LowerHex for ByteHex<'a> {
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{crypto, XOR_CIPHER};
+
+  #[derive(Debug)]
+  struct Reverse;
+
+  impl Obfuscator for Reverse {
+    fn obfuscate(&self, data: &mut [u8]) { data.reverse(); }
+
+    fn deobfuscate(&self, data: &mut [u8]) { data.reverse(); }
+  }
+
+  fn state() -> PacketCodecState {
+    PacketCodecState::builder()
+      .cipher(&XOR_CIPHER)
+      .crypto(crypto::CLIENT.clone())
+      .obfuscator(Reverse)
+      .build()
+  }
+
+  #[test]
+  fn obfuscated_round_trip() {
+    let mut codec = PacketCodec::new(state(), state());
+    let packet = Packet::from_bytes(&[0xC1, 0x06, 0xF4, 0x03, 0x00, 0x00]).unwrap();
+
+    let mut buffer = BytesMut::new();
+    codec.encode(packet.clone(), &mut buffer).unwrap();
+    assert_eq!(&buffer[..2], &[0xC3, 0x0D]);
+    assert_eq!(buffer[2], 0x0F);
+
+    let mut partial = BytesMut::from(&buffer[..5]);
+    assert!(codec.decode(&mut partial).unwrap().is_none());
+
+    let decoded = codec.decode(&mut buffer).unwrap().unwrap();
+    assert_eq!(decoded.to_bytes(), packet.to_bytes());
+    assert!(buffer.is_empty());
+  }
+
+  #[test]
+  fn header_only_frames() {
+    // Frames lacking a code (or counter) are rejected, instead of awaited
+    for frame in [&[0xC1, 0x02][..], &[0xC3, 0x02, 0xC1]] {
+      let mut codec = PacketCodec::new(state(), state());
+      let error = codec.decode(&mut BytesMut::from(frame)).unwrap_err();
+      assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+      assert_eq!(PacketError::of(&error), Some(PacketError::InvalidSize(2)));
+    }
+  }
+
+  #[test]
+  #[cfg(feature = "zlib")]
+  fn compression() {
+    use crate::{Compression, CompressionAlgorithm};
+
+    let mut codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    codec.set_compression(Some(Compression::new(CompressionAlgorithm::Zlib)));
+
+    let mut packet = Packet::new(PacketKind::C2, 0xF3);
+    packet.append(&[0x20; 300]);
+
+    let mut buffer = BytesMut::new();
+    codec.encode(packet.clone(), &mut buffer).unwrap();
+    assert_eq!(buffer[0], 0xC1);
+    assert!(buffer.len() < packet.len() / 2);
+
+    let decoded = codec.decode(&mut buffer).unwrap().unwrap();
+    assert_eq!(decoded.to_bytes(), packet.to_bytes());
+    assert!(codec.clone().compression.is_none());
+  }
+
+  #[test]
+  #[cfg(feature = "hmac")]
+  fn hmac_trailer() {
+    use crate::FrameHmac;
+
+    let state = || PacketCodecState::builder().trailer(FrameHmac::new(b"secret")).build();
+    let mut codec = PacketCodec::new(state(), state());
+    let packet = Packet::from_bytes(&[0xC1, 0x04, 0x00, 0x01]).unwrap();
+
+    let mut buffer = BytesMut::new();
+    codec.encode(packet.clone(), &mut buffer).unwrap();
+    codec.encode(packet.clone(), &mut buffer).unwrap();
+    assert_eq!(buffer.len(), 2 * (packet.len() + 32));
+
+    let decoded = codec.decode(&mut buffer).unwrap().unwrap();
+    assert_eq!(decoded.to_bytes(), packet.to_bytes());
+
+    buffer[3] ^= 0x01;
+    assert!(codec.decode(&mut buffer).is_err());
+  }
+
+  #[test]
+  fn crc_trailer() {
+    use crate::{CrcAlgorithm, FrameCrc};
+
+    let crc = FrameCrc::new(CrcAlgorithm::Crc16).included();
+    let state = || PacketCodecState::builder().trailer(crc).build();
+    let mut codec = PacketCodec::new(state(), state());
+    let packet = Packet::from_bytes(&[0xC1, 0x04, 0x00, 0x01]).unwrap();
+
+    let mut buffer = BytesMut::new();
+    codec.encode(packet.clone(), &mut buffer).unwrap();
+    assert_eq!(&buffer[..2], [0xC1, 0x06]);
+
+    let decoded = codec.decode(&mut buffer).unwrap().unwrap();
+    assert_eq!(decoded.to_bytes(), packet.to_bytes());
+    assert!(buffer.is_empty());
+
+    let mut corrupt = BytesMut::from(&[0xC1, 0x06, 0x00, 0x01, 0x00, 0x00][..]);
+    assert!(codec.decode(&mut corrupt).is_err());
+
+    // Frames too small for their trailer are malformed
+    let error = codec.decode(&mut BytesMut::from(&[0xC1, 0x03, 0x00][..])).unwrap_err();
+    assert_eq!(PacketError::of(&error), Some(PacketError::InvalidSize(3)));
+  }
+
+  #[test]
+  fn crypto_stats() {
+    use std::sync::{Arc, Mutex};
+
+    let encrypt = || PacketCodecState::builder().crypto(crypto::CLIENT.clone()).build();
+    let mut sender = PacketCodec::new(encrypt(), PacketCodecState::new());
+    let mut receiver = PacketCodec::new(PacketCodecState::new(), encrypt());
+
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let seen = failures.clone();
+    receiver.set_crypto_callback(move |error| seen.lock().unwrap().push(*error));
+
+    let packet = Packet::from_bytes(&[0xC1, 0x05, 0x00, 0x01, 0x02]).unwrap();
+    let mut buffer = BytesMut::new();
+    sender.encode(packet.clone(), &mut buffer).unwrap();
+    sender.encode(packet, &mut buffer).unwrap();
+
+    // Skip the first packet, causing a counter mismatch
+    let size = buffer.len() / 2;
+    buffer.split_to(size);
+    assert!(receiver.decode(&mut buffer).is_err());
+
+    let mut corrupt = BytesMut::from(&[0xC3, 0x0D, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0][..]);
+    assert!(receiver.decode(&mut corrupt).is_err());
+
+    let stats = receiver.crypto_stats();
+    assert_eq!(stats.counter_mismatches(), 1);
+    assert_eq!(stats.checksum_failures(), 1);
+    assert_eq!(stats.total(), 2);
+    assert_eq!(
+      failures.lock().unwrap()[0],
+      CryptoError::CounterMismatch { expected: 0, received: 1 }
+    );
+  }
+
+  #[test]
+  fn probes() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let mut codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let received = Arc::new(AtomicUsize::new(0));
+    let counter = received.clone();
+    codec.add_probe(&[0x00, 0x00, 0x00, 0x01]);
+    codec.set_probe_callback(move |probe| {
+      assert_eq!(probe.len(), 4);
+      counter.fetch_add(1, Ordering::SeqCst);
+    });
+
+    let mut buffer = BytesMut::from(&[0x00, 0x00][..]);
+    assert!(codec.decode(&mut buffer).unwrap().is_none());
+
+    buffer.extend_from_slice(&[0x00, 0x01, 0xC1, 0x03, 0x0E]);
+    assert_eq!(codec.decode(&mut buffer).unwrap().unwrap().code(), 0x0E);
+    assert_eq!(received.load(Ordering::SeqCst), 1);
+
+    let mut buffer = BytesMut::from(&[0x00, 0x02][..]);
+    assert!(codec.decode(&mut buffer).is_err());
+  }
+
+  #[test]
+  fn resume() {
+    let encrypt = || PacketCodecState::builder().crypto(crypto::CLIENT.clone()).build();
+    let mut sender = PacketCodec::new(encrypt(), PacketCodecState::new());
+    let packet = Packet::from_bytes(&[0xC1, 0x04, 0x00, 0x01]).unwrap();
+
+    let mut buffer = BytesMut::new();
+    sender.encode(packet.clone(), &mut buffer).unwrap();
+    buffer.clear();
+
+    let token = sender.resume_token();
+    assert_eq!(token.len(), RESUME_SIZE);
+    assert!(PacketCodec::resume(&token, PacketCodecState::new(), PacketCodecState::new()).is_err());
+
+    let mut sender = PacketCodec::resume(&token, encrypt(), PacketCodecState::new()).unwrap();
+    let mut receiver = PacketCodec::new(PacketCodecState::new(), encrypt());
+    receiver.decrypt.counter = 1;
+
+    sender.encode(packet.clone(), &mut buffer).unwrap();
+    assert_eq!(receiver.decode(&mut buffer).unwrap().unwrap().to_bytes(), packet.to_bytes());
+  }
+
+  #[test]
+  fn observers() {
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct Events(Mutex<Vec<String>>);
+
+    impl Observer for Events {
+      fn frame_received(&self, packet: &Packet, size: usize) {
+        self.0.lock().unwrap().push(format!("received {:02X} {}", packet.code(), size));
+      }
+
+      fn frame_sent(&self, packet: &Packet, size: usize) {
+        self.0.lock().unwrap().push(format!("sent {:02X} {}", packet.code(), size));
+      }
+
+      fn oversize_rejected(&self, size: usize, limit: usize) {
+        self.0.lock().unwrap().push(format!("oversize {} {}", size, limit));
+      }
+    }
+
+    let events = Arc::new(Events::default());
+    let mut codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    codec.add_observer(events.clone());
+    codec.set_code_limit(0x01, 3);
+
+    let mut buffer = BytesMut::new();
+    codec.encode(Packet::new(PacketKind::C1, 0x00), &mut buffer).unwrap();
+    assert!(codec.decode(&mut buffer).unwrap().is_some());
+
+    let mut buffer = BytesMut::from(&[0xC1, 0x04, 0x01, 0x00][..]);
+    assert!(codec.decode(&mut buffer).is_err());
+    assert_eq!(*events.0.lock().unwrap(), ["sent 00 3", "received 00 3", "oversize 4 3"]);
+  }
+
+  #[test]
+  fn budget() {
+    use std::time::Duration;
+
+    let mut codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    codec.set_budget(Some(Budget::new(Duration::from_secs(60)).limit_packets(0x81, 1)));
+
+    let mut buffer = BytesMut::from(&[0xC1, 0x03, 0x81, 0xC1, 0x03, 0x81][..]);
+    assert!(codec.decode(&mut buffer).unwrap().is_some());
+    assert!(codec.decode(&mut buffer).is_err());
+    assert_eq!(codec.budget().unwrap().usage(0x81).bytes(), 6);
+    assert_eq!(codec.clone().budget().unwrap().usage(0x81).packets(), 0);
+  }
+
+  #[test]
+  fn infer_counter() {
+    let encrypt = || PacketCodecState::builder().crypto(crypto::CLIENT.clone()).build();
+    let mut sender = PacketCodec::new(encrypt(), PacketCodecState::new());
+    let packet = Packet::from_bytes(&[0xC1, 0x04, 0x00, 0x01]).unwrap();
+
+    // Attach after a few packets have already been sent
+    let mut buffer = BytesMut::new();
+    for _ in 0..3 {
+      sender.encode(packet.clone(), &mut buffer).unwrap();
+    }
+    buffer.clear();
+
+    let decrypt = PacketCodecState::builder()
+      .crypto(crypto::CLIENT.clone())
+      .infer_counter()
+      .build();
+    let mut receiver = PacketCodec::new(PacketCodecState::new(), decrypt);
+
+    for _ in 0..2 {
+      sender.encode(packet.clone(), &mut buffer).unwrap();
+      assert!(receiver.decode(&mut buffer).unwrap().is_some());
+    }
+    assert_eq!(receiver.counters(), (0, 5));
+
+    // Once inferred, the counter is verified as usual
+    receiver.decrypt.counter = 0;
+    sender.encode(packet, &mut buffer).unwrap();
+    assert!(receiver.decode(&mut buffer).is_err());
+  }
+
+  #[test]
+  fn decode_bytes() {
+    let mut codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let mut input = vec![0xC1, 0x40, 0x0E];
+    input.resize(0x40, 0x01);
+    input.extend_from_slice(&[0xC2, 0x00, 0x05, 0xF3, 0x2A]);
+    let mut input = BytesMut::from(input);
+    let start = input.as_ptr();
+
+    // Unencrypted frames reference the input
+    let packet = codec.decode_bytes(&mut input).unwrap().unwrap();
+    assert_eq!((packet.code(), packet.len()), (0x0E, 0x40));
+    assert_eq!(packet.frame().as_ptr(), start);
+    let packet = codec.decode_bytes(&mut input).unwrap().unwrap();
+    assert_eq!((packet.kind(), packet.data()), (PacketKind::C2, &[0x2A][..]));
+    assert!(codec.decode_bytes(&mut input).unwrap().is_none());
+
+    let mut output = BytesMut::new();
+    codec.encode_bytes(packet, &mut output).unwrap();
+    assert_eq!(output[..], [0xC2, 0x00, 0x05, 0xF3, 0x2A]);
+
+    // Encrypted frames are decoded as usual
+    let decrypt = PacketCodecState::builder().crypto(crypto::CLIENT.clone()).build();
+    let mut codec = PacketCodec::new(PacketCodecState::new(), decrypt);
+    let ping = Packet::from_bytes(&[0xC1, 0x04, 0x0E, 0x01]).unwrap();
+    let mut input = BytesMut::from(ping.to_bytes_ex(None, Some((&crypto::CLIENT, 0))));
+    let packet = codec.decode_bytes(&mut input).unwrap().unwrap();
+    assert_eq!(packet.frame()[..], ping.to_bytes()[..]);
+  }
+
+  #[test]
+  fn plain_packets() {
+    let encrypt = PacketCodecState::builder().crypto(crypto::CLIENT.clone()).build();
+    let decrypt = PacketCodecState::builder().crypto(crypto::CLIENT.clone()).build();
+    let mut sender = PacketCodec::new(encrypt, PacketCodecState::new());
+    let mut receiver = PacketCodec::new(PacketCodecState::new(), decrypt);
+    sender.add_plain(&[0xF4, 0x06]);
+
+    let mut buffer = BytesMut::new();
+    let servers = Packet::from_bytes(&[0xC1, 0x04, 0xF4, 0x06]).unwrap();
+    sender.encode(servers, &mut buffer).unwrap();
+    assert_eq!(buffer[0], 0xC1);
+    assert!(receiver.decode(&mut buffer).unwrap().is_some());
+
+    let hello = Packet::from_bytes(&[0xC1, 0x04, 0xF1, 0x00]).unwrap();
+    sender.encode_plain(hello.clone(), &mut buffer).unwrap();
+    assert_eq!(buffer[0], 0xC1);
+    assert!(receiver.decode(&mut buffer).unwrap().is_some());
+
+    // Only encrypted packets advance the counter
+    sender.encode(hello, &mut buffer).unwrap();
+    assert_eq!(buffer[0], 0xC3);
+    assert!(receiver.decode(&mut buffer).unwrap().is_some());
+    assert_eq!((sender.counters(), receiver.counters()), ((1, 0), (0, 1)));
+  }
+
+  #[test]
+  fn clone_fresh() {
+    let crypto = Arc::new(crypto::CLIENT.clone());
+    let state = PacketCodecState::builder().crypto(crypto.clone()).build();
+    let mut codec = PacketCodec::new(state.clone(), state);
+    assert_eq!(Arc::strong_count(&crypto), 3);
+
+    let mut buffer = BytesMut::new();
+    codec.encode(Packet::new(PacketKind::C1, 0x00), &mut buffer).unwrap();
+    assert_eq!(codec.counters(), (1, 0));
+
+    let clone = codec.clone();
+    assert_eq!(clone.counters(), (0, 0));
+    assert_eq!(Arc::strong_count(&crypto), 5);
+  }
+
+  #[test]
+  fn default_states() {
+    let (encrypt, decrypt) = PacketCodecState::client_default();
+    let mut client = PacketCodec::new(encrypt, decrypt);
+    let (encrypt, decrypt) = PacketCodecState::server_default();
+    let mut server = PacketCodec::new(encrypt, decrypt);
+
+    let packet = Packet::from_bytes(&[0xC1, 0x05, 0x18, 0x03, 0x07]).unwrap();
+    let mut buffer = BytesMut::new();
+    client.encode(packet.clone(), &mut buffer).unwrap();
+    assert_ne!(&buffer[..], &packet.to_bytes()[..]);
+    assert_eq!(server.decode(&mut buffer).unwrap().unwrap().data(), packet.data());
+
+    let bytes = packet.to_bytes_ex(None, Some((&crypto::SERVER, 0)));
+    let mut buffer = BytesMut::from(&bytes[..]);
+    assert_eq!(client.decode(&mut buffer).unwrap().unwrap().data(), packet.data());
+
+    let (encrypt, decrypt) = PacketCodecState::internal_default();
+    let mut internal = PacketCodec::new(encrypt, decrypt);
+    let mut buffer = BytesMut::new();
+    internal.encode(packet.clone(), &mut buffer).unwrap();
+    assert_eq!(&buffer[..], &packet.to_bytes()[..]);
+  }
+
+  #[test]
+  fn rotating_keys() {
+    use std::time::Duration;
+
+    // The receiver has rotated its keys since the sender's session started
+    let now = SystemTime::now();
+    let start = now - Duration::from_secs(60);
+    let old = RotatingCrypto::new().add(crypto::SERVER.clone(), start, None);
+    let rotating = Arc::new(old.clone().add(crypto::CLIENT.clone(), now, None));
+
+    let state = |rotating: Arc<RotatingCrypto>| {
+      PacketCodecState::builder().rotating_crypto(rotating).build()
+    };
+    let mut sender = PacketCodec::new(state(Arc::new(old)), PacketCodecState::new());
+    let mut receiver = PacketCodec::new(PacketCodecState::new(), state(rotating));
+
+    let packet = Packet::from_bytes(&[0xC1, 0x04, 0x00, 0x01]).unwrap();
+    let mut buffer = BytesMut::new();
+    sender.encode(packet.clone(), &mut buffer).unwrap();
+    sender.encode(packet.clone(), &mut buffer).unwrap();
+
+    for _ in 0..2 {
+      assert_eq!(receiver.decode(&mut buffer).unwrap().unwrap().data(), packet.data());
+    }
+    assert_eq!(fingerprint(&receiver.decrypt), crypto::SERVER.fingerprint());
+
+    // Encrypted packets are rejected once all keys have expired
+    let expired = RotatingCrypto::new().add(crypto::SERVER.clone(), start, Some(start));
+    let mut receiver = PacketCodec::new(PacketCodecState::new(), state(Arc::new(expired)));
+    sender.encode(packet, &mut buffer).unwrap();
+    let error = receiver.decode(&mut buffer).unwrap_err();
+    assert_eq!(PacketError::of(&error), Some(PacketError::NoValidKeys));
+  }
+
+  #[test]
+  fn detect_crypto() {
+    let state = || PacketCodecState::builder().detect_crypto(crypto::CLIENT.clone()).build();
+    let packet = Packet::from_bytes(&[0xC1, 0x04, 0x00, 0x01]).unwrap();
+
+    let mut plain = PacketCodec::new(PacketCodecState::new(), state());
+    let mut buffer = BytesMut::from(&packet.to_bytes()[..]);
+    assert!(plain.decode(&mut buffer).unwrap().is_some());
+    assert_eq!(plain.peer_encrypted(), Some(false));
+
+    let mut encrypted = plain.clone();
+    assert_eq!(encrypted.peer_encrypted(), None);
+
+    let bytes = packet.to_bytes_ex(None, Some((&crypto::CLIENT, 0)));
+    let mut buffer = BytesMut::from(&bytes[..]);
+    assert_eq!(encrypted.decode(&mut buffer).unwrap().unwrap().data(), packet.data());
+    assert_eq!(encrypted.peer_encrypted(), Some(true));
+
+    // Once detected, the mode is kept for the connection
+    let mut buffer = BytesMut::from(&bytes[..]);
+    assert!(plain.decode(&mut buffer).is_err());
+  }
+
+  #[test]
+  fn frame_policy() {
+    use crate::Padding;
+
+    let mut codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let bytes = [0xC1, 0x03, 0x0E, 0x00, 0x00, 0xC1, 0x04, 0x0E, 0x01];
+
+    let mut buffer = BytesMut::from(&bytes[..]);
+    assert!(codec.decode(&mut buffer).unwrap().unwrap().data().is_empty());
+    assert!(codec.decode(&mut buffer).is_err());
+
+    codec.set_frame_policy(FramePolicy::new().empty(EmptyFrames::Skip).padding(Padding::Skip));
+    let mut buffer = BytesMut::from(&bytes[..]);
+    assert_eq!(codec.decode(&mut buffer).unwrap().unwrap().data(), [0x01]);
+    assert!(buffer.is_empty());
+
+    codec.set_frame_policy(FramePolicy::new().empty(EmptyFrames::Reject));
+    let error = codec.decode(&mut BytesMut::from(&bytes[..3])).unwrap_err();
+    assert_eq!(PacketError::of(&error), Some(PacketError::Empty));
+  }
+
+  #[test]
+  fn code_limit() {
+    let mut codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    codec.set_code_limit(0x00, 4);
+
+    let mut buffer = BytesMut::from(&[0xC1, 0x04, 0x00, 0x01, 0xC1, 0x05, 0x00, 0x01, 0x02][..]);
+    assert!(codec.decode(&mut buffer).unwrap().is_some());
+    assert!(codec.decode(&mut buffer).is_err());
+  }
+}
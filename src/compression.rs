@@ -0,0 +1,174 @@
+use crate::{Packet, PacketKind};
+use std::io;
+
+/// Default size of data, from which it is compressed.
+const DEFAULT_THRESHOLD: usize = 128;
+
+/// Marks compressed data as originally belonging to a C2 packet.
+const WIDE_FLAG: u8 = 0x80;
+
+/// Flag of data which is not compressed.
+const RAW: u8 = 0x00;
+
+/// A compression algorithm.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompressionAlgorithm {
+  /// LZ4 block compression, favoring speed.
+  #[cfg(feature = "lz4")]
+  Lz4 = 0x01,
+  /// Zlib compression, favoring size.
+  #[cfg(feature = "zlib")]
+  Zlib = 0x02,
+}
+
+/// A compression of packet data, for custom clients & servers.
+///
+/// Once enabled, every packet's data is preceded by a flag, describing
+/// whether (and how) it is compressed. The code is left as-is, so packets
+/// can still be identified. Data below the threshold, or which does not
+/// shrink, is sent uncompressed. Since stock clients are oblivious to this,
+/// it must only be enabled once both sides have agreed upon it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Compression {
+  algorithm: CompressionAlgorithm,
+  threshold: usize,
+}
+
+impl Compression {
+  /// Creates a compression using an algorithm.
+  pub fn new(algorithm: CompressionAlgorithm) -> Self {
+    Compression {
+      algorithm,
+      threshold: DEFAULT_THRESHOLD,
+    }
+  }
+
+  /// Sets the size of data from which it is compressed (defaults to 128).
+  pub fn threshold(mut self, threshold: usize) -> Self {
+    self.threshold = threshold;
+    self
+  }
+
+  /// Returns the compression's algorithm.
+  pub fn algorithm(&self) -> CompressionAlgorithm { self.algorithm }
+
+  /// Compresses a packet's data, preceded by its flag.
+  pub(crate) fn compress(&self, packet: &Packet) -> io::Result<Packet> {
+    let mut flag = RAW;
+    let mut data = None;
+
+    if packet.data().len() >= self.threshold {
+      let compressed = match self.algorithm {
+        #[cfg(feature = "lz4")]
+        CompressionAlgorithm::Lz4 => lz4_flex::block::compress_prepend_size(packet.data()),
+        #[cfg(feature = "zlib")]
+        CompressionAlgorithm::Zlib => miniz_oxide::deflate::compress_to_vec_zlib(packet.data(), 6),
+      };
+
+      if compressed.len() < packet.data().len() {
+        flag = self.algorithm as u8;
+        data = Some(compressed);
+      }
+    }
+
+    if packet.kind() == PacketKind::C2 {
+      flag |= WIDE_FLAG;
+    }
+
+    let data = data.as_deref().unwrap_or_else(|| packet.data());
+    let kind = PacketKind::from_size(data.len() + 1, None)
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "packet too large"))?;
+
+    let mut output = Packet::new(kind, packet.code());
+    output.append(&[flag]);
+    output.append(data);
+    Ok(output)
+  }
+
+  /// Decompresses a packet's data, according to its flag.
+  pub(crate) fn decompress(&self, packet: Packet) -> io::Result<Packet> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let (&flag, data) = packet
+      .data()
+      .split_first()
+      .ok_or_else(|| invalid("missing compression flag"))?;
+
+    let kind = if flag & WIDE_FLAG != 0 { PacketKind::C2 } else { PacketKind::C1 };
+    let limit = kind.max_size() - kind.offset();
+
+    let decompressed = match flag & !WIDE_FLAG {
+      RAW => None,
+      #[cfg(feature = "lz4")]
+      algorithm if algorithm == CompressionAlgorithm::Lz4 as u8 => {
+        if data.len() < 4 {
+          return Err(invalid("missing decompressed size"));
+        }
+
+        // The size is verified beforehand, to avoid allocating excessively
+        let size = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        if size > limit {
+          return Err(invalid("decompressed packet too large"));
+        }
+
+        let data = lz4_flex::block::decompress(&data[4..], size)
+          .map_err(|error| invalid(&error.to_string()))?;
+        Some(data)
+      },
+      #[cfg(feature = "zlib")]
+      algorithm if algorithm == CompressionAlgorithm::Zlib as u8 => {
+        let data = miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(data, limit)
+          .map_err(|_| invalid("invalid or oversized compressed packet"))?;
+        Some(data)
+      },
+      _ => return Err(invalid("unsupported packet compression")),
+    };
+
+    let data = decompressed.as_deref().unwrap_or(data);
+    if data.len() > limit {
+      return Err(invalid("decompressed packet too large"));
+    }
+
+    let mut output = Packet::new(kind, packet.code());
+    output.append(data);
+    Ok(output)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn round_trip(compression: Compression) {
+    let mut packet = Packet::new(PacketKind::C2, 0xF3);
+    packet.append(&[0x10; 400]);
+
+    let compressed = compression.compress(&packet).unwrap();
+    assert_eq!(compressed.kind(), PacketKind::C1);
+    assert_eq!(compressed.data()[0], compression.algorithm() as u8 | WIDE_FLAG);
+
+    let decompressed = compression.decompress(compressed).unwrap();
+    assert_eq!(decompressed.kind(), PacketKind::C2);
+    assert_eq!(decompressed.data(), packet.data());
+
+    let small = Packet::new(PacketKind::C1, 0x0E);
+    let compressed = compression.compress(&small).unwrap();
+    assert_eq!(compressed.data(), [RAW]);
+    assert_eq!(compression.decompress(compressed).unwrap().data(), small.data());
+  }
+
+  #[test]
+  #[cfg(feature = "lz4")]
+  fn lz4() {
+    let compression = Compression::new(CompressionAlgorithm::Lz4);
+    round_trip(compression);
+
+    // A forged size must not be trusted
+    let mut packet = Packet::new(PacketKind::C1, 0xF3);
+    packet.append(&[0x01, 0xFF, 0xFF, 0xFF, 0x7F, 0x00]);
+    assert!(compression.decompress(packet).is_err());
+  }
+
+  #[test]
+  #[cfg(feature = "zlib")]
+  fn zlib() { round_trip(Compression::new(CompressionAlgorithm::Zlib).threshold(16)); }
+}
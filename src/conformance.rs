@@ -0,0 +1,317 @@
+//! Conformance testing of servers against this crate's protocol.
+//!
+//! A runner connects to a server once per scenario, and performs its actions
+//! in order through a codec. A scenario passes if every expectation is met.
+//! The default scenarios cover the handshake, malformed frames and counter
+//! tampering; login and game specific flows can be added as custom scenarios.
+
+use bytes::BytesMut;
+use crate::{crypto, Packet, PacketCodec, PacketKind};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use tokio_io::codec::{Decoder, Encoder};
+
+/// An action performed by a scenario.
+#[derive(Clone, Debug)]
+pub enum Action {
+  /// Sends a packet through the codec.
+  Send(Packet),
+  /// Sends raw bytes, bypassing the codec (e.g malformed frames).
+  SendRaw(Vec<u8>),
+  /// Expects a packet starting with a code, followed by any subcodes.
+  ///
+  /// Packets with other codes are skipped while waiting.
+  Expect(Vec<u8>),
+  /// Expects the server to close the connection.
+  ExpectClosed,
+}
+
+/// A named sequence of actions.
+#[derive(Clone, Debug)]
+pub struct Scenario {
+  name: String,
+  actions: Vec<Action>,
+}
+
+impl Scenario {
+  /// Creates an empty scenario.
+  pub fn new<S: Into<String>>(name: S) -> Self {
+    Scenario {
+      name: name.into(),
+      actions: Vec::new(),
+    }
+  }
+
+  /// Appends an action to the scenario.
+  pub fn then(mut self, action: Action) -> Self {
+    self.actions.push(action);
+    self
+  }
+
+  /// Returns the scenario's name.
+  pub fn name(&self) -> &str { &self.name }
+
+  /// Expects the server's greeting (`C1 F1 00`), sent upon connecting.
+  pub fn handshake() -> Self { Scenario::new("handshake").then(Action::Expect(vec![0xF1, 0x00])) }
+
+  /// Expects the server to disconnect after a frame with an invalid size.
+  pub fn malformed_frame() -> Self {
+    Scenario::new("malformed frame")
+      .then(Action::Expect(vec![0xF1, 0x00]))
+      .then(Action::SendRaw(vec![0xC1, 0x01, 0x0E]))
+      .then(Action::ExpectClosed)
+  }
+
+  /// Expects the server to disconnect after an encrypted packet with an
+  /// unexpected counter, using the default client keys.
+  pub fn counter_tampering() -> Self {
+    let mut packet = Packet::new(PacketKind::C1, 0x0E);
+    packet.append(&[0x00, 0x00, 0x00, 0x00, 0x00]);
+
+    Scenario::new("counter tampering")
+      .then(Action::Expect(vec![0xF1, 0x00]))
+      .then(Action::SendRaw(packet.to_bytes_ex(None, Some((&crypto::CLIENT, 0x7F)))))
+      .then(Action::ExpectClosed)
+  }
+}
+
+/// A runner of conformance scenarios against a server.
+#[derive(Debug)]
+pub struct Runner {
+  address: SocketAddr,
+  codec: PacketCodec,
+  timeout: Duration,
+  scenarios: Vec<Scenario>,
+}
+
+impl Runner {
+  /// Creates a runner, with the default scenarios, for a server.
+  ///
+  /// Each scenario uses a clone of the codec, i.e a fresh connection state.
+  pub fn new<A: ToSocketAddrs>(address: A, codec: PacketCodec) -> io::Result<Self> {
+    let address = address
+      .to_socket_addrs()?
+      .next()
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to"))?;
+
+    Ok(Runner {
+      address,
+      codec,
+      timeout: Duration::from_secs(2),
+      scenarios: vec![
+        Scenario::handshake(),
+        Scenario::malformed_frame(),
+        Scenario::counter_tampering(),
+      ],
+    })
+  }
+
+  /// Sets how long each expectation may wait (defaults to two seconds).
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Adds a custom scenario.
+  pub fn scenario(mut self, scenario: Scenario) -> Self {
+    self.scenarios.push(scenario);
+    self
+  }
+
+  /// Removes all scenarios, including the default ones.
+  pub fn clear(mut self) -> Self {
+    self.scenarios.clear();
+    self
+  }
+
+  /// Runs all scenarios in order, returning their outcomes.
+  pub fn run(&self) -> Report {
+    let outcomes = self
+      .scenarios
+      .iter()
+      .map(|scenario| Outcome {
+        name: scenario.name.clone(),
+        failure: self.run_scenario(scenario).err().map(|error| error.to_string()),
+      })
+      .collect();
+    Report { outcomes }
+  }
+
+  fn run_scenario(&self, scenario: &Scenario) -> io::Result<()> {
+    let mut connection = Connection {
+      stream: TcpStream::connect_timeout(&self.address, self.timeout)?,
+      codec: self.codec.clone(),
+      input: BytesMut::new(),
+      timeout: self.timeout,
+    };
+
+    for (index, action) in scenario.actions.iter().enumerate() {
+      connection.perform(action).map_err(|error| {
+        let message = format!("action #{} ({:?}) failed: {}", index + 1, action, error);
+        io::Error::new(error.kind(), message)
+      })?;
+    }
+    Ok(())
+  }
+}
+
+/// A connection performing a scenario.
+struct Connection {
+  stream: TcpStream,
+  codec: PacketCodec,
+  input: BytesMut,
+  timeout: Duration,
+}
+
+impl Connection {
+  fn perform(&mut self, action: &Action) -> io::Result<()> {
+    match action {
+      Action::Send(packet) => {
+        let mut output = BytesMut::new();
+        self.codec.encode(packet.clone(), &mut output)?;
+        self.stream.write_all(&output)
+      },
+      Action::SendRaw(bytes) => self.stream.write_all(bytes),
+      Action::Expect(codes) => loop {
+        let packet = self
+          .next_packet()?
+          .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"))?;
+
+        let identifier = std::iter::once(packet.code()).chain(packet.data().iter().cloned());
+        if codes.iter().cloned().eq(identifier.take(codes.len())) {
+          return Ok(());
+        }
+      },
+      Action::ExpectClosed => {
+        while self.next_packet()?.is_some() {}
+        Ok(())
+      },
+    }
+  }
+
+  /// Returns the next packet received, or none if the connection closed.
+  fn next_packet(&mut self) -> io::Result<Option<Packet>> {
+    let deadline = Instant::now() + self.timeout;
+    let mut buffer = [0; 4096];
+
+    loop {
+      if let Some(packet) = self.codec.decode(&mut self.input)? {
+        return Ok(Some(packet));
+      }
+
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      if remaining == Duration::from_secs(0) {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+      }
+
+      self.stream.set_read_timeout(Some(remaining))?;
+      match self.stream.read(&mut buffer) {
+        Ok(0) => return Ok(None),
+        Ok(size) => self.input.extend_from_slice(&buffer[..size]),
+        // A reset is as good as a close, when expecting one
+        Err(ref error) if error.kind() == io::ErrorKind::ConnectionReset => return Ok(None),
+        Err(ref error)
+          if error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut =>
+        {
+          return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"))
+        },
+        Err(error) => return Err(error),
+      }
+    }
+  }
+}
+
+/// The outcome of a single scenario.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+  name: String,
+  failure: Option<String>,
+}
+
+impl Outcome {
+  /// Returns the scenario's name.
+  pub fn name(&self) -> &str { &self.name }
+
+  /// Returns whether the scenario passed.
+  pub fn passed(&self) -> bool { self.failure.is_none() }
+
+  /// Returns the reason the scenario failed.
+  pub fn failure(&self) -> Option<&str> { self.failure.as_deref() }
+}
+
+/// The outcomes of a conformance run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+  outcomes: Vec<Outcome>,
+}
+
+impl Report {
+  /// Returns the outcome of each scenario, in the order they ran.
+  pub fn outcomes(&self) -> &[Outcome] { &self.outcomes }
+
+  /// Returns whether all scenarios passed.
+  pub fn passed(&self) -> bool { self.outcomes.iter().all(Outcome::passed) }
+
+  /// Returns the scenarios that failed.
+  pub fn failures(&self) -> impl Iterator<Item = &Outcome> {
+    self.outcomes.iter().filter(|outcome| !outcome.passed())
+  }
+}
+
+impl fmt::Display for Report {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    for outcome in &self.outcomes {
+      match outcome.failure() {
+        None => writeln!(fmt, "PASS {}", outcome.name)?,
+        Some(failure) => writeln!(fmt, "FAIL {}: {}", outcome.name, failure)?,
+      }
+    }
+
+    let passed = self.outcomes.iter().filter(|outcome| outcome.passed()).count();
+    write!(fmt, "{} of {} scenarios passed", passed, self.outcomes.len())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PacketCodecState;
+  use std::net::TcpListener;
+  use std::thread;
+
+  #[test]
+  fn default_scenarios() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // A server which greets, and disconnects upon any packet but a ping
+    thread::spawn(move || {
+      for stream in listener.incoming() {
+        let mut stream = stream.unwrap();
+        stream.write_all(&[0xC1, 0x05, 0xF1, 0x00, 0x01]).unwrap();
+
+        let mut buffer = [0; 3];
+        while stream.read_exact(&mut buffer).is_ok() && buffer == [0xC1, 0x03, 0x0E] {}
+      }
+    });
+
+    let codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let ping = Scenario::new("ping")
+      .then(Action::Send(Packet::new(PacketKind::C1, 0x0E)))
+      .then(Action::ExpectClosed);
+
+    let report = Runner::new(address, codec)
+      .unwrap()
+      .timeout(Duration::from_millis(200))
+      .scenario(ping)
+      .run();
+
+    let outcomes = report.outcomes();
+    assert!(outcomes[..3].iter().all(Outcome::passed), "{}", report);
+    assert_eq!(outcomes[3].name(), "ping");
+    assert!(outcomes[3].failure().unwrap().contains("timed out"));
+    assert!(report.to_string().ends_with("3 of 4 scenarios passed"));
+  }
+}
@@ -0,0 +1,117 @@
+use crate::Trailer;
+use std::io;
+
+/// A CRC algorithm of a frame trailer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CrcAlgorithm {
+  /// CRC-16/ARC (i.e the reflected polynomial `0xA001`), two bytes.
+  Crc16,
+  /// CRC-32/ISO-HDLC (i.e as used by zlib), four bytes.
+  Crc32,
+}
+
+impl CrcAlgorithm {
+  /// Returns the size of the checksum.
+  pub fn size(self) -> usize {
+    match self {
+      CrcAlgorithm::Crc16 => 2,
+      CrcAlgorithm::Crc32 => 4,
+    }
+  }
+
+  /// Computes the checksum of bytes.
+  pub fn checksum(self, bytes: &[u8]) -> u32 {
+    let (polynomial, initial) = match self {
+      CrcAlgorithm::Crc16 => (0xA001, 0),
+      CrcAlgorithm::Crc32 => (0xEDB8_8320, 0xFFFF_FFFF),
+    };
+
+    let mut crc = initial;
+    for &byte in bytes {
+      crc ^= u32::from(byte);
+      for _ in 0..8 {
+        crc = if crc & 1 != 0 { (crc >> 1) ^ polynomial } else { crc >> 1 };
+      }
+    }
+    crc ^ initial
+  }
+}
+
+/// A CRC trailer, as appended to frames by some custom servers.
+///
+/// Unlike other trailers, this only detects corruption, not tampering. The
+/// checksum is little-endian by default, and follows the frame without being
+/// included in its size.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FrameCrc {
+  algorithm: CrcAlgorithm,
+  big_endian: bool,
+  included: bool,
+}
+
+impl FrameCrc {
+  /// Creates a CRC trailer using an algorithm.
+  pub fn new(algorithm: CrcAlgorithm) -> Self {
+    FrameCrc {
+      algorithm,
+      big_endian: false,
+      included: false,
+    }
+  }
+
+  /// Stores the checksum as big-endian.
+  pub fn big_endian(mut self) -> Self {
+    self.big_endian = true;
+    self
+  }
+
+  /// Includes the checksum in the frame's declared size.
+  pub fn included(mut self) -> Self {
+    self.included = true;
+    self
+  }
+
+  /// Returns the checksum of a frame, in its wire format.
+  fn encode(&self, frame: &[u8]) -> Vec<u8> {
+    let size = self.algorithm.size();
+    let checksum = self.algorithm.checksum(frame).to_le_bytes();
+    let mut bytes = checksum[..size].to_vec();
+    if self.big_endian {
+      bytes.reverse();
+    }
+    bytes
+  }
+}
+
+impl Trailer for FrameCrc {
+  fn size(&self) -> usize { self.algorithm.size() }
+
+  fn included(&self) -> bool { self.included }
+
+  fn sign(&mut self, frame: &[u8]) -> io::Result<Vec<u8>> { Ok(self.encode(frame)) }
+
+  fn verify(&mut self, frame: &[u8], trailer: &[u8]) -> io::Result<()> {
+    if self.encode(frame) != trailer {
+      return Err(io::Error::new(io::ErrorKind::InvalidData, "packet checksum mismatch"));
+    }
+    Ok(())
+  }
+
+  fn fresh(&self) -> Box<dyn Trailer> { Box::new(*self) }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn checksums() {
+    assert_eq!(CrcAlgorithm::Crc16.checksum(b"123456789"), 0xBB3D);
+    assert_eq!(CrcAlgorithm::Crc32.checksum(b"123456789"), 0xCBF4_3926);
+
+    let mut crc = FrameCrc::new(CrcAlgorithm::Crc16).big_endian();
+    assert_eq!(crc.sign(b"123456789").unwrap(), [0xBB, 0x3D]);
+    assert!(crc.verify(b"123456789", &[0xBB, 0x3D]).is_ok());
+    assert!(crc.verify(b"123456780", &[0xBB, 0x3D]).is_err());
+  }
+}
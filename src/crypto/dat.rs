@@ -0,0 +1,143 @@
+//! Parsing of `.dat` key files.
+//!
+//! A key file consists of a 6-byte header (a magic value followed by the
+//! file's size, as a little endian `u32`) and 48 bytes of keys. Headerless
+//! files, containing only the keys, are accepted as well.
+
+use super::ENCRYPTION_SIZE;
+use byteorder::{ByteOrder, LittleEndian};
+use std::{error, fmt, io};
+
+/// Magic value at the start of a key file.
+const MAGIC: [u8; 2] = [0x12, 0x11];
+
+/// Size of a key file's header.
+const HEADER_SIZE: usize = 6;
+
+/// Size of the keys in a key file.
+const KEYS_SIZE: usize = ENCRYPTION_SIZE - HEADER_SIZE;
+
+/// A failure to parse a key file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DatError {
+  /// The file's size matches no known layout.
+  WrongSize(usize),
+  /// The file's header has an unknown magic value, or an invalid size.
+  BadHeader,
+  /// The file ends before all of its keys.
+  TruncatedKeys {
+    /// The number of key bytes expected.
+    expected: usize,
+    /// The number of key bytes found.
+    found: usize,
+  },
+  /// The file's keys are unusable (i.e a modulus is zero).
+  InvalidKeys,
+}
+
+impl fmt::Display for DatError {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      DatError::WrongSize(size) => write!(fmt, "unexpected key file size {}", size),
+      DatError::BadHeader => write!(fmt, "invalid key file header"),
+      DatError::TruncatedKeys { expected, found } => {
+        write!(fmt, "truncated key file, {} of {} key bytes", found, expected)
+      },
+      DatError::InvalidKeys => write!(fmt, "invalid key file keys"),
+    }
+  }
+}
+
+impl error::Error for DatError {}
+
+impl From<DatError> for io::Error {
+  fn from(error: DatError) -> Self { io::Error::new(io::ErrorKind::InvalidData, error) }
+}
+
+/// Parses a key file, returning it in its standard layout.
+pub fn parse(bytes: &[u8]) -> Result<[u8; ENCRYPTION_SIZE], DatError> {
+  if bytes.len() == KEYS_SIZE {
+    return Ok(with_header(bytes));
+  }
+
+  if bytes.len() < HEADER_SIZE {
+    return Err(DatError::WrongSize(bytes.len()));
+  }
+
+  let size = LittleEndian::read_u32(&bytes[2..HEADER_SIZE]) as usize;
+  if bytes[..2] != MAGIC || size != ENCRYPTION_SIZE {
+    return Err(DatError::BadHeader);
+  }
+
+  if bytes.len() < ENCRYPTION_SIZE {
+    return Err(DatError::TruncatedKeys {
+      expected: KEYS_SIZE,
+      found: bytes.len() - HEADER_SIZE,
+    });
+  } else if bytes.len() > ENCRYPTION_SIZE {
+    return Err(DatError::WrongSize(bytes.len()));
+  }
+
+  let mut output = [0; ENCRYPTION_SIZE];
+  output.copy_from_slice(bytes);
+  Ok(output)
+}
+
+/// Parses a key file, returning its keys deobfuscated using a XOR cipher.
+///
+/// The keys are three quadruples; the modulus, the key (for encryption or
+/// decryption) and the XOR key.
+pub fn decode(bytes: &[u8], xor: &[u32; 4]) -> Result<[[u32; 4]; 3], DatError> {
+  let file = parse(bytes)?;
+  let mut keys = [[0; 4]; 3];
+
+  let chunks = file[HEADER_SIZE..].chunks(4);
+  for ((key, chunk), xor) in keys.iter_mut().flatten().zip(chunks).zip(xor.iter().cycle()) {
+    *key = LittleEndian::read_u32(chunk) ^ xor;
+  }
+
+  if !super::valid_keys(&keys) {
+    return Err(DatError::InvalidKeys);
+  }
+  Ok(keys)
+}
+
+/// Returns keys in the standard layout, i.e preceded by a header.
+pub(super) fn with_header(keys: &[u8]) -> [u8; ENCRYPTION_SIZE] {
+  let mut output = [0; ENCRYPTION_SIZE];
+  output[..2].copy_from_slice(&MAGIC);
+  LittleEndian::write_u32(&mut output[2..HEADER_SIZE], ENCRYPTION_SIZE as u32);
+  output[HEADER_SIZE..].copy_from_slice(keys);
+  output
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn layouts() {
+    let file = include_bytes!("../../res/Enc1.dat");
+    assert_eq!(&parse(file).unwrap()[..], &file[..]);
+    assert_eq!(&parse(&file[HEADER_SIZE..]).unwrap()[..], &file[..]);
+
+    assert_eq!(parse(&file[..4]), Err(DatError::WrongSize(4)));
+    assert_eq!(parse(&[0; ENCRYPTION_SIZE]), Err(DatError::BadHeader));
+    assert_eq!(
+      parse(&file[..20]),
+      Err(DatError::TruncatedKeys { expected: 48, found: 14 })
+    );
+  }
+
+  #[test]
+  fn invalid_keys() {
+    let mut file = *include_bytes!("../../res/Enc1.dat");
+    let keys = decode(&file, &super::super::XOR_CIPHER).unwrap();
+    assert_eq!(keys[0][0], 0x0001_F44F);
+
+    // A zero modulus would otherwise fail when encrypting
+    LittleEndian::write_u32(&mut file[HEADER_SIZE..], super::super::XOR_CIPHER[0]);
+    assert_eq!(parse(&file), Ok(file));
+    assert_eq!(decode(&file, &super::super::XOR_CIPHER), Err(DatError::InvalidKeys));
+  }
+}
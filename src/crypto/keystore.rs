@@ -0,0 +1,128 @@
+//! Passphrase protected storage of encryption keys.
+//!
+//! Instead of keeping plaintext `.dat` files next to a server, the key files
+//! and their cipher can be sealed in a container. The passphrase is stretched
+//! using Argon2id, and the keys are sealed using ChaCha20-Poly1305.
+//!
+//! The container consists of a magic value, a version, a random salt and
+//! nonce, followed by the sealed keys.
+
+use super::{PacketCrypto, ENCRYPTION_SIZE};
+use argon2::Argon2;
+use byteorder::{ByteOrder, LittleEndian};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::convert::TryFrom;
+use std::path::Path;
+use std::{fs, io};
+
+/// Magic value at the start of a container.
+const MAGIC: &[u8; 4] = b"MUKS";
+
+/// The version of the container format.
+const VERSION: u8 = 1;
+
+/// Size of the salt used for stretching the passphrase.
+const SALT_SIZE: usize = 16;
+
+/// Size of the nonce used for sealing.
+const NONCE_SIZE: usize = 12;
+
+/// Size of the container's header (i.e everything before the sealed keys).
+const HEADER_SIZE: usize = MAGIC.len() + 1 + SALT_SIZE + NONCE_SIZE;
+
+/// Size of the sealed keys; the key files and their cipher.
+const KEYS_SIZE: usize = 2 * ENCRYPTION_SIZE + 16;
+
+/// Seals key files and their cipher in a container.
+pub fn seal(
+  passphrase: &[u8],
+  enc: &[u8; ENCRYPTION_SIZE],
+  dec: &[u8; ENCRYPTION_SIZE],
+  xor: &[u32; 4],
+) -> io::Result<Vec<u8>> {
+  let mut salt = [0; SALT_SIZE];
+  OsRng.fill_bytes(&mut salt);
+  let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+  let mut keys = Vec::with_capacity(KEYS_SIZE);
+  keys.extend_from_slice(enc);
+  keys.extend_from_slice(dec);
+  for &value in xor {
+    keys.extend_from_slice(&value.to_le_bytes());
+  }
+
+  let sealed = cipher(passphrase, &salt)?
+    .encrypt(&nonce, keys.as_slice())
+    .map_err(|_| io::Error::other("failed to seal keys"))?;
+
+  let mut container = Vec::with_capacity(HEADER_SIZE + sealed.len());
+  container.extend_from_slice(MAGIC);
+  container.push(VERSION);
+  container.extend_from_slice(&salt);
+  container.extend_from_slice(&nonce);
+  container.extend_from_slice(&sealed);
+  Ok(container)
+}
+
+/// Opens a container, returning its encryption scheme.
+pub fn open(passphrase: &[u8], container: &[u8]) -> io::Result<PacketCrypto> {
+  if container.len() < HEADER_SIZE || &container[..MAGIC.len()] != MAGIC {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "not a key container"));
+  }
+
+  if container[MAGIC.len()] != VERSION {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported key container version"));
+  }
+
+  let salt = &container[MAGIC.len() + 1..][..SALT_SIZE];
+  let nonce = Nonce::from_slice(&container[HEADER_SIZE - NONCE_SIZE..HEADER_SIZE]);
+  let keys = cipher(passphrase, salt)?
+    .decrypt(nonce, &container[HEADER_SIZE..])
+    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid passphrase or container"))?;
+
+  if keys.len() != KEYS_SIZE {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid key container size"));
+  }
+
+  let (enc, rest) = keys.split_at(ENCRYPTION_SIZE);
+  let (dec, xor) = rest.split_at(ENCRYPTION_SIZE);
+  let mut cipher = [0; 4];
+  LittleEndian::read_u32_into(xor, &mut cipher);
+
+  let enc = <&[u8; ENCRYPTION_SIZE]>::try_from(enc).unwrap();
+  let dec = <&[u8; ENCRYPTION_SIZE]>::try_from(dec).unwrap();
+  Ok(PacketCrypto::new(enc, dec, &cipher))
+}
+
+/// Opens a container from a file, returning its encryption scheme.
+pub fn load<P: AsRef<Path>>(path: P, passphrase: &[u8]) -> io::Result<PacketCrypto> {
+  open(passphrase, &fs::read(path)?)
+}
+
+/// Returns the cipher for a passphrase and salt.
+fn cipher(passphrase: &[u8], salt: &[u8]) -> io::Result<ChaCha20Poly1305> {
+  let mut key = [0; 32];
+  Argon2::default()
+    .hash_password_into(passphrase, salt, &mut key)
+    .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error.to_string()))?;
+  Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::crypto::{CLIENT, XOR_CIPHER};
+
+  #[test]
+  fn seal_and_open() {
+    let enc = include_bytes!("../../res/Enc1.dat");
+    let dec = include_bytes!("../../res/Dec1.dat");
+    let container = seal(b"hunter2", enc, dec, &XOR_CIPHER).unwrap();
+
+    let crypto = open(b"hunter2", &container).unwrap();
+    assert_eq!(crypto.fingerprint(), CLIENT.fingerprint());
+    assert!(open(b"hunter3", &container).is_err());
+  }
+}
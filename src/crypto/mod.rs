@@ -0,0 +1,588 @@
+use lazy_static::lazy_static;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::{error, fmt, io};
+
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use smallvec::SmallVec;
+use crate::PacketError;
+
+pub use self::dat::DatError;
+pub use self::rotating::RotatingCrypto;
+
+pub mod dat;
+#[cfg(feature = "keystore")]
+pub mod keystore;
+pub mod scan;
+
+mod rotating;
+
+/// Default size of an encryption scheme.
+const ENCRYPTION_SIZE: usize = 54;
+
+/// Chunk size when decrypting.
+const DECRYPT_MOD: usize = 8;
+
+/// Chunk size when encrypting.
+const ENCRYPT_MOD: usize = 11;
+
+/// The groups of keys stored in an encryption key file.
+const ENCRYPT_FLAGS: [bool; 4] = [true, true, false, true];
+
+/// The groups of keys stored in a decryption key file.
+const DECRYPT_FLAGS: [bool; 4] = [true, false, true, true];
+
+/// Cipher used for the default encryption keys.
+const XOR_CIPHER: [u32; 4] = [0x3F08_A79B, 0xE25C_C287, 0x93D2_7AB9, 0x20DE_A7BF];
+
+/// Key used for scrambling credentials.
+const CREDENTIAL_KEY: [u8; 3] = [0xFC, 0xCF, 0xAB];
+
+lazy_static! {
+    /// Default client encryption scheme.
+    pub static ref CLIENT: PacketCrypto = PacketCrypto::new(
+        include_bytes!("../../res/Enc1.dat"),
+        include_bytes!("../../res/Dec1.dat"),
+        &XOR_CIPHER);
+
+    /// Default server encryption scheme.
+    pub static ref SERVER: PacketCrypto = PacketCrypto::new(
+        include_bytes!("../../res/Enc2.dat"),
+        include_bytes!("../../res/Dec2.dat"),
+        &XOR_CIPHER);
+}
+
+/// Toggles the scrambling of a credential (e.g an account's name or
+/// password), using a repeating XOR key.
+pub fn scramble_credential(bytes: &mut [u8]) {
+  for (byte, key) in bytes.iter_mut().zip(CREDENTIAL_KEY.iter().cycle()) {
+    *byte ^= key;
+  }
+}
+
+/// A failure to decrypt, or to verify, an encrypted packet.
+///
+/// These are wrapped, as a `PacketError`, by the `io::Error` values returned
+/// when decrypting.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CryptoError {
+  /// A decrypted block's checksum did not match its content.
+  Checksum,
+  /// The encrypted data was not a multiple of the block size.
+  MisalignedBlock,
+  /// A decrypted block claimed more content than a block can hold.
+  InvalidBlockSize,
+  /// A packet's encryption counter did not match the expected value.
+  CounterMismatch {
+    /// The counter expected by the receiver.
+    expected: u8,
+    /// The counter contained in the packet.
+    received: u8,
+  },
+}
+
+impl fmt::Display for CryptoError {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      CryptoError::Checksum => write!(fmt, "incorrect data hash"),
+      CryptoError::MisalignedBlock => write!(fmt, "misaligned encrypted block"),
+      CryptoError::InvalidBlockSize => write!(fmt, "invalid block size"),
+      CryptoError::CounterMismatch { expected, received } => write!(
+        fmt,
+        "invalid decryption counter {}, expected {}",
+        received, expected
+      ),
+    }
+  }
+}
+
+impl error::Error for CryptoError {}
+
+/// Reads a key file, stopping once it is larger than any known layout.
+fn read_dat<R: Read>(reader: R) -> io::Result<Vec<u8>> {
+  let mut bytes = Vec::with_capacity(ENCRYPTION_SIZE);
+  reader.take(ENCRYPTION_SIZE as u64 + 1).read_to_end(&mut bytes)?;
+  Ok(bytes)
+}
+
+/// Returns whether keys, as used by a direction, are usable.
+fn valid_keys(keys: &[[u32; 4]; 3]) -> bool { keys[0].iter().all(|&modulus| modulus != 0) }
+
+/// Whether encrypted packets contain an encryption counter.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CounterMode {
+  /// No counter is included (i.e by very old clients, such as 0.97).
+  None,
+  /// A counter, incremented for each packet, precedes the code.
+  #[default]
+  Sequential,
+}
+
+/// An implementation of Mu Online's symmetric-key algorithm.
+#[derive(Debug, Clone)]
+pub struct PacketCrypto {
+  encrypt: Vec<u32>,
+  decrypt: Vec<u32>,
+  counter: CounterMode,
+}
+
+impl PacketCrypto {
+  /// Creates a new encryption scheme.
+  pub fn new(enc: &[u8; ENCRYPTION_SIZE], dec: &[u8; ENCRYPTION_SIZE], xor: &[u32; 4]) -> Self {
+    PacketCrypto {
+      encrypt: Self::load_keys(enc, xor, ENCRYPT_FLAGS),
+      decrypt: Self::load_keys(dec, xor, DECRYPT_FLAGS),
+      counter: CounterMode::default(),
+    }
+  }
+
+  /// Creates a new encryption scheme from plain (i.e not obfuscated) keys.
+  ///
+  /// The keys of each direction are three quadruples; the modulus, the key
+  /// (for encryption or decryption) and the XOR key. An error is returned if
+  /// any modulus is zero.
+  pub fn from_raw_keys(encrypt: &[[u32; 4]; 3], decrypt: &[[u32; 4]; 3]) -> io::Result<Self> {
+    if !valid_keys(encrypt) || !valid_keys(decrypt) {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "keys contain a zero modulus"));
+    }
+
+    Ok(PacketCrypto {
+      encrypt: Self::expand_keys(encrypt, ENCRYPT_FLAGS),
+      decrypt: Self::expand_keys(decrypt, DECRYPT_FLAGS),
+      counter: CounterMode::default(),
+    })
+  }
+
+  /// Creates a new encryption scheme from the contents of key files.
+  ///
+  /// The keys are validated, unlike those of `new`, since they are loaded at
+  /// runtime.
+  pub fn from_dat(enc: &[u8], dec: &[u8], xor: &[u32; 4]) -> Result<Self, DatError> {
+    Ok(PacketCrypto {
+      encrypt: Self::expand_keys(&dat::decode(enc, xor)?, ENCRYPT_FLAGS),
+      decrypt: Self::expand_keys(&dat::decode(dec, xor)?, DECRYPT_FLAGS),
+      counter: CounterMode::default(),
+    })
+  }
+
+  /// Creates a new encryption scheme by reading key files.
+  ///
+  /// As with `from_dat`, files with an invalid layout or keys are rejected.
+  pub fn from_readers<E: Read, D: Read>(enc: E, dec: D, xor: &[u32; 4]) -> io::Result<Self> {
+    Ok(Self::from_dat(&read_dat(enc)?, &read_dat(dec)?, xor)?)
+  }
+
+  /// Creates a new encryption scheme by loading key files (e.g `Enc1.dat` and
+  /// `Dec1.dat`), allowing the default keys to be replaced at runtime.
+  pub fn from_files<P: AsRef<Path>>(enc: P, dec: P, xor: &[u32; 4]) -> io::Result<Self> {
+    Self::from_readers(File::open(enc)?, File::open(dec)?, xor)
+  }
+
+  /// Writes the keys as key files, in the standard (i.e 54-byte) layout.
+  ///
+  /// The keys are obfuscated using a XOR cipher, which must match the one
+  /// of the client loading them.
+  pub fn write_keys<E, D>(&self, mut enc: E, mut dec: D, xor: &[u32; 4]) -> io::Result<()>
+  where
+    E: Write,
+    D: Write,
+  {
+    enc.write_all(&Self::store_keys(&self.encrypt, xor, ENCRYPT_FLAGS))?;
+    dec.write_all(&Self::store_keys(&self.decrypt, xor, DECRYPT_FLAGS))
+  }
+
+  /// Sets whether encrypted packets contain an encryption counter.
+  pub fn with_counter_mode(mut self, mode: CounterMode) -> Self {
+    self.counter = mode;
+    self
+  }
+
+  /// Returns whether encrypted packets contain an encryption counter.
+  pub fn counter_mode(&self) -> CounterMode { self.counter }
+
+  /// Returns whether a counter precedes the code of encrypted packets.
+  pub(crate) fn has_counter(&self) -> bool { self.counter == CounterMode::Sequential }
+
+  /// Returns an identifier of the keys, without revealing them.
+  ///
+  /// This is a 32-bit FNV-1a hash, and not suitable for authentication.
+  pub fn fingerprint(&self) -> u32 {
+    self
+      .encrypt
+      .iter()
+      .chain(self.decrypt.iter())
+      .flat_map(|key| key.to_le_bytes())
+      .fold(0x811C_9DC5, |hash, byte| (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193))
+  }
+
+  /// Returns the size of a buffer after encryption.
+  pub fn encrypt_len(len: usize) -> usize { ENCRYPT_MOD * Self::align(len, DECRYPT_MOD) }
+
+  /// Returns the maximum size of an encrypted buffer after decryption.
+  pub fn decrypt_max_len(len: usize) -> usize { DECRYPT_MOD * (len / ENCRYPT_MOD) }
+
+  /// Decrypts an encrypted byte buffer.
+  pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let mut output = Vec::new();
+    self.decrypt_into(data, &mut output)?;
+    Ok(output)
+  }
+
+  /// Decrypts an encrypted byte buffer, appending the result to an output.
+  ///
+  /// This allows reusing the output's allocation between packets.
+  pub fn decrypt_into(&self, data: &[u8], output: &mut Vec<u8>) -> Result<(), io::Error> {
+    output.reserve(Self::decrypt_max_len(data.len()));
+
+    for block in self.decrypt_blocks(data) {
+      let (block, size) = block?;
+      output.extend_from_slice(&block[..size]);
+    }
+
+    Ok(())
+  }
+
+  /// Lazily decrypts an encrypted byte buffer, one block at a time.
+  ///
+  /// Each block yields up to 8 bytes, along with the number of bytes used.
+  /// This allows consumers to stop early, e.g after decrypting a header.
+  pub fn decrypt_blocks<'a>(
+    &'a self,
+    data: &'a [u8],
+  ) -> impl Iterator<Item = Result<([u8; DECRYPT_MOD], usize), io::Error>> + 'a {
+    data.chunks(ENCRYPT_MOD).map(move |input| {
+      if input.len() != ENCRYPT_MOD {
+        return Err(io::Error::from(PacketError::Crypto(CryptoError::MisalignedBlock)));
+      }
+
+      let mut output = [0; DECRYPT_MOD];
+      let size = self.convert_11to8_bytes(&mut output, input)?;
+
+      if size > DECRYPT_MOD {
+        return Err(io::Error::from(PacketError::Crypto(CryptoError::InvalidBlockSize)));
+      }
+
+      Ok((output, size))
+    })
+  }
+
+  /// Encrypts a raw byte buffer.
+  pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0; Self::encrypt_len(data.len())];
+
+    for (input, output) in data.chunks(DECRYPT_MOD).zip(output.chunks_mut(ENCRYPT_MOD)) {
+      self.convert_8to11_bytes(output, input);
+    }
+
+    output
+  }
+
+  /// Encrypts a block of up to 8 bytes into an 11 byte output.
+  pub(crate) fn encrypt_block(&self, block: &[u8], output: &mut [u8]) {
+    output.iter_mut().for_each(|byte| *byte = 0);
+    self.convert_8to11_bytes(output, block);
+  }
+
+  /// Converts 8 bytes to 11, using the associated keys.
+  fn convert_8to11_bytes(&self, out: &mut [u8], slice: &[u8]) {
+    assert_eq!(out.len(), ENCRYPT_MOD);
+
+    // Pad the input with zeroes if not 8-bit aligned
+    let input = Self::slice_with_padding(slice);
+
+    let mut reader = io::Cursor::new(input);
+    let mut crypt = 0;
+
+    let mut enc = (0..4)
+      .map(|index| {
+        let mut data = u32::from(reader.read_u16::<LittleEndian>().unwrap());
+        data ^= self.encrypt[12 + index] ^ crypt;
+        data = data.wrapping_mul(self.encrypt[4 + index]);
+        data %= self.encrypt[index];
+
+        crypt = data & 0xFFFF;
+        data
+      }).collect::<SmallVec<[u32; 4]>>();
+
+    for index in 0..3 {
+      enc[index] ^= self.encrypt[12 + index] ^ (enc[index + 1] & 0xFFFF);
+    }
+
+    let pos = enc.iter().fold(0, |mut pos, &value| {
+      let mut value_as_bytes = [0u8; 4];
+      LittleEndian::write_u32(&mut value_as_bytes, value);
+
+      pos = Self::hash_buffer(out, pos, &value_as_bytes, 0, 16);
+      Self::hash_buffer(out, pos, &value_as_bytes, 22, 2)
+    });
+
+    let xor = input.iter().fold(0xF8, |xor, &value| xor ^ value);
+    let finale = [xor ^ (slice.len() as u8) ^ 0x3D, xor, 0, 0];
+
+    Self::hash_buffer(out, pos, &finale, 0x00, 0x10);
+  }
+
+  /// Converts 11 bytes to 8, using the associated keys.
+  fn convert_11to8_bytes(&self, out: &mut [u8], slice: &[u8]) -> Result<usize, io::Error> {
+    assert_eq!(out.len(), DECRYPT_MOD);
+    let mut offset = 0;
+    let mut dec = (0..4)
+      .map(|_| {
+        let mut data = [0; 4];
+        Self::hash_buffer(&mut data, 0, slice, offset, 16);
+        offset += 16;
+        Self::hash_buffer(&mut data, 22, slice, offset, 2);
+        offset += 2;
+        LittleEndian::read_u32(&data)
+      }).collect::<SmallVec<[u32; 4]>>();
+
+    for index in (0..3).rev() {
+      dec[index] ^= self.decrypt[12 + index] ^ (dec[index + 1] & 0xFFFF);
+    }
+
+    let mut writer = io::Cursor::new(out);
+    let mut crypt = 0;
+    for (index, dec) in dec.iter().enumerate().take(4) {
+      let mut original = self.decrypt[8 + index].wrapping_mul(*dec);
+      original %= self.decrypt[index];
+      original ^= self.decrypt[index + 12] ^ crypt;
+
+      crypt = dec & 0xFFFF;
+      writer.write_u16::<LittleEndian>(original as u16).unwrap();
+    }
+
+    // First byte contains the original length, and the 2nd the checksum
+    let mut finale = [0; 4];
+    Self::hash_buffer(&mut finale, 0, slice, offset, 16);
+    finale[0] ^= finale[1] ^ 0x3D;
+
+    let xor = writer
+      .into_inner()
+      .iter()
+      .fold(0xF8, |xor, &value| xor ^ value);
+    if finale[1] == xor {
+      Ok(finale[0] as usize)
+    } else {
+      Err(io::Error::from(PacketError::Crypto(CryptoError::Checksum)))
+    }
+  }
+
+  /// Decrypts and loads encryption keys from a byte buffer.
+  fn load_keys(keys: &[u8; ENCRYPTION_SIZE], xor: &[u32; 4], flags: [bool; 4]) -> Vec<u32> {
+    let mut result = Vec::new();
+    let mut reader = io::Cursor::new(&keys[..]);
+    reader.set_position(6);
+
+    for flag in &flags {
+      for xor in xor.iter() {
+        result.push(if *flag {
+          reader.read_u32::<LittleEndian>().unwrap() ^ xor
+        } else {
+          0
+        });
+      }
+    }
+
+    assert_eq!(result.len(), 16);
+    result
+  }
+
+  /// Expands the groups of keys used by a direction, zeroing the others.
+  fn expand_keys(keys: &[[u32; 4]; 3], flags: [bool; 4]) -> Vec<u32> {
+    let mut keys = keys.iter();
+    flags
+      .iter()
+      .flat_map(|&flag| if flag { *keys.next().unwrap() } else { [0; 4] })
+      .collect()
+  }
+
+  /// Stores keys in a key file's layout, reversing `load_keys`.
+  fn store_keys(keys: &[u32], xor: &[u32; 4], flags: [bool; 4]) -> [u8; ENCRYPTION_SIZE] {
+    let mut output = Vec::new();
+    let keys = flags.iter().zip(keys.chunks(xor.len())).filter(|(flag, _)| **flag);
+
+    for (_, keys) in keys {
+      for (key, xor) in keys.iter().zip(xor.iter()) {
+        output.write_u32::<LittleEndian>(key ^ xor).unwrap();
+      }
+    }
+
+    dat::with_header(&output)
+  }
+
+  /// Hashes a byte buffer.
+  fn hash_buffer(
+    out: &mut [u8],
+    offset_out: usize,
+    input: &[u8],
+    offset_in: usize,
+    delta: usize,
+  ) -> usize {
+    let size = ((offset_in + delta - 1) >> 3) - (offset_in >> 3) + 2;
+
+    let mut buffer = (0..size).map(|_| 0).collect::<SmallVec<[u8; 8]>>();
+    buffer[..size - 1].copy_from_slice(&input[(offset_in >> 3)..][..size - 1]);
+
+    let disp = (offset_in + delta) % 8;
+
+    if disp != 0 {
+      buffer[size - 2] &= 0xFF << (8 - disp);
+    }
+
+    let mod_in = offset_in % 8;
+    let mod_out = offset_out % 8;
+
+    Self::shift_bytes(&mut buffer, size - 1, -(mod_in as isize));
+    Self::shift_bytes(&mut buffer, size, mod_out as isize);
+
+    let mod_size = (size - 1) + (mod_out > mod_in) as usize;
+    for (index, value) in out[offset_out >> 3..][..mod_size].iter_mut().enumerate() {
+      *value |= buffer[index];
+    }
+
+    offset_out + delta
+  }
+
+  /// Shifts a byte buffer.
+  fn shift_bytes(out: &mut [u8], size: usize, delta: isize) {
+    match delta.cmp(&0) {
+      Ordering::Equal => (),
+      Ordering::Greater => {
+        if size > 1 {
+          for index in (1..size).rev() {
+            out[index] = (out[index - 1] << (8 - delta)) | (out[index] >> delta);
+          }
+        }
+        out[0] >>= delta;
+      }
+      Ordering::Less => {
+        let delta = delta.abs();
+        if size > 1 {
+          for index in 0..size {
+            out[index] = (out[index + 1] >> (8 - delta)) | (out[index] << delta);
+          }
+        }
+        out[size - 1] <<= delta;
+      }
+    }
+  }
+
+  /// Creates a slice with 8 elements, padding with zeroes.
+  fn slice_with_padding(slice: &[u8]) -> [u8; DECRYPT_MOD] {
+    let mut input = [0; DECRYPT_MOD];
+    input[..slice.len()].copy_from_slice(slice);
+    input
+  }
+
+  /// Rounds a value up to a specific alignment.
+  fn align(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn client2server() {
+    let raw = [0x00, 0xF4, 0x03, 0x00, 0x00];
+    let enc = CLIENT.encrypt(&raw);
+    assert_eq!(
+      enc,
+      [0xE3, 0xB3, 0x53, 0x9A, 0x4F, 0xC8, 0x32, 0x7D, 0x04, 0x37, 0x0F]
+    );
+
+    let dec = CLIENT.decrypt(&enc).unwrap();
+    assert_eq!(dec, raw);
+
+    assert_eq!(PacketCrypto::encrypt_len(raw.len()), enc.len());
+    assert_eq!(PacketCrypto::decrypt_max_len(enc.len()), 8);
+  }
+
+  #[test]
+  fn server2client() {
+    let raw = [0x00, 0xF4, 0x03, 0x00, 0x00];
+    let enc = SERVER.encrypt(&raw);
+    assert_eq!(
+      enc,
+      [0x47, 0x93, 0x15, 0x3B, 0x0B, 0x1C, 0x15, 0x7C, 0x16, 0x37, 0x0F]
+    );
+
+    let dec = SERVER.decrypt(&enc).unwrap();
+    assert_eq!(dec, raw);
+    assert_ne!(SERVER.fingerprint(), CLIENT.fingerprint());
+  }
+
+  #[test]
+  fn large_buffer() {
+    let raw = [
+      0x7C, 0xE7, 0xE6, 0xA2, 0x1E, 0xA8, 0xDA, 0xBC, 0xDB, 0x6D, 0x31, 0x62, 0xFE, 0xA7, 0xA0,
+      0xF3, 0xF4, 0x05, 0x1D, 0x64, 0x1A, 0x42, 0xC2,
+    ];
+
+    let dec = SERVER.decrypt(&SERVER.encrypt(&raw)).unwrap();
+    assert_eq!(dec, raw);
+
+    let enc = CLIENT.encrypt(&raw);
+    let (block, size) = CLIENT.decrypt_blocks(&enc).next().unwrap().unwrap();
+    assert_eq!(&block[..size], &raw[..8]);
+    assert!(CLIENT.decrypt_blocks(&enc[..12]).nth(1).unwrap().is_err());
+
+    let dec = CLIENT.decrypt(&CLIENT.encrypt(&raw)).unwrap();
+    assert_eq!(dec, raw);
+  }
+
+  #[test]
+  fn from_files() {
+    let path = |name| Path::new(env!("CARGO_MANIFEST_DIR")).join("res").join(name);
+    let crypto = PacketCrypto::from_files(path("Enc2.dat"), path("Dec2.dat"), &XOR_CIPHER).unwrap();
+    assert_eq!(crypto.fingerprint(), SERVER.fingerprint());
+
+    let error = PacketCrypto::from_readers(&[0; 4][..], &[0; 48][..], &XOR_CIPHER).unwrap_err();
+    assert_eq!(error.to_string(), "unexpected key file size 4");
+    assert!(PacketCrypto::from_files(path("Enc2.dat"), path("missing.dat"), &XOR_CIPHER).is_err());
+
+    // Keys are validated before they are used
+    let mut enc = *include_bytes!("../../res/Enc2.dat");
+    LittleEndian::write_u32(&mut enc[6..], XOR_CIPHER[0]);
+    let error = PacketCrypto::from_readers(&enc[..], &[0; 48][..], &XOR_CIPHER).unwrap_err();
+    assert_eq!(error.to_string(), "invalid key file keys");
+  }
+
+  #[test]
+  fn write_keys() {
+    let (mut enc, mut dec) = (Vec::new(), Vec::new());
+    CLIENT.write_keys(&mut enc, &mut dec, &XOR_CIPHER).unwrap();
+    assert_eq!(enc, &include_bytes!("../../res/Enc1.dat")[..]);
+    assert_eq!(dec, &include_bytes!("../../res/Dec1.dat")[..]);
+  }
+
+  #[test]
+  fn from_raw_keys() {
+    let group = |keys: &[u32], index: usize| {
+      let mut group = [0; 4];
+      group.copy_from_slice(&keys[index * 4..][..4]);
+      group
+    };
+
+    let encrypt = [group(&CLIENT.encrypt, 0), group(&CLIENT.encrypt, 1), group(&CLIENT.encrypt, 3)];
+    let decrypt = [group(&CLIENT.decrypt, 0), group(&CLIENT.decrypt, 2), group(&CLIENT.decrypt, 3)];
+    let crypto = PacketCrypto::from_raw_keys(&encrypt, &decrypt).unwrap();
+    assert_eq!(crypto.fingerprint(), CLIENT.fingerprint());
+    assert_eq!(CLIENT.decrypt(&crypto.encrypt(&[0xF4, 0x03])).unwrap(), [0xF4, 0x03]);
+
+    let mut invalid = encrypt;
+    invalid[0][2] = 0;
+    assert!(PacketCrypto::from_raw_keys(&invalid, &decrypt).is_err());
+
+    // Keys and data overflowing the arithmetic wrap, as in the client
+    invalid[0][2] = 0x0001_0000;
+    invalid[1][2] = u32::MAX;
+    let crypto = PacketCrypto::from_raw_keys(&invalid, &decrypt).unwrap();
+    assert_eq!(crypto.encrypt(&[0xFF; 8]).len(), 11);
+    assert!(CLIENT.decrypt(&[0xFF; 11]).is_err());
+  }
+}
@@ -0,0 +1,91 @@
+use super::PacketCrypto;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// A set of keys, valid within a time window.
+#[derive(Clone, Debug)]
+struct KeySet {
+  crypto: Arc<PacketCrypto>,
+  from: SystemTime,
+  until: Option<SystemTime>,
+}
+
+impl KeySet {
+  fn is_valid_at(&self, time: SystemTime) -> bool {
+    self.from <= time && self.until.is_none_or(|until| time < until)
+  }
+}
+
+/// A schedule of encryption keys, for servers rotating their `.dat` keys.
+///
+/// Codecs using a rotation select their keys once per connection. Outbound
+/// packets use the active keys when the first packet is sent. Inbound packets
+/// use whichever valid keys decrypt the first encrypted packet, so sessions
+/// started before a rotation remain decodable while the old keys are valid.
+#[derive(Clone, Debug, Default)]
+pub struct RotatingCrypto {
+  keys: Vec<KeySet>,
+}
+
+impl RotatingCrypto {
+  /// Creates an empty key rotation.
+  pub fn new() -> Self { Self::default() }
+
+  /// Adds keys valid from a point in time, optionally until another.
+  pub fn add<C: Into<Arc<PacketCrypto>>>(
+    mut self,
+    crypto: C,
+    from: SystemTime,
+    until: Option<SystemTime>,
+  ) -> Self {
+    self.keys.push(KeySet {
+      crypto: crypto.into(),
+      from,
+      until,
+    });
+    self
+  }
+
+  /// Returns the active keys at a point in time (i.e the most recent valid).
+  pub fn active_at(&self, time: SystemTime) -> Option<&Arc<PacketCrypto>> {
+    self
+      .keys
+      .iter()
+      .filter(|keys| keys.is_valid_at(time))
+      .max_by_key(|keys| keys.from)
+      .map(|keys| &keys.crypto)
+  }
+
+  /// Returns all valid keys at a point in time, the most recent first.
+  pub fn valid_at(&self, time: SystemTime) -> Vec<&Arc<PacketCrypto>> {
+    let mut keys = self
+      .keys
+      .iter()
+      .filter(|keys| keys.is_valid_at(time))
+      .collect::<Vec<_>>();
+    keys.sort_by_key(|keys| std::cmp::Reverse(keys.from));
+    keys.into_iter().map(|keys| &keys.crypto).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::crypto::{CLIENT, SERVER};
+  use std::time::Duration;
+
+  #[test]
+  fn windows() {
+    let start = SystemTime::UNIX_EPOCH;
+    let rotation = start + Duration::from_secs(100);
+    let rotating = RotatingCrypto::new()
+      .add(CLIENT.clone(), start, Some(rotation + Duration::from_secs(60)))
+      .add(SERVER.clone(), rotation, None);
+
+    let fingerprint = |keys: Option<&Arc<PacketCrypto>>| keys.map(|keys| keys.fingerprint());
+    assert_eq!(fingerprint(rotating.active_at(start)), Some(CLIENT.fingerprint()));
+    assert_eq!(fingerprint(rotating.active_at(rotation)), Some(SERVER.fingerprint()));
+    assert_eq!(rotating.valid_at(rotation).len(), 2);
+    assert_eq!(rotating.valid_at(rotation + Duration::from_secs(60)).len(), 1);
+  }
+}
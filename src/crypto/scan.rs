@@ -0,0 +1,154 @@
+//! Extraction of encryption keys from client binaries.
+//!
+//! Clients embed their key files verbatim, so the blocks can be located by
+//! their header and loaded as if they were read from disk. The XOR table
+//! obfuscating the keys is embedded as well, and is recognized by the keys it
+//! decodes.
+
+use super::{dat, PacketCrypto, ENCRYPTION_SIZE};
+use byteorder::{ByteOrder, LittleEndian};
+use std::convert::TryFrom;
+
+/// Header of a key block; a magic value followed by the block's size.
+const KEY_BLOCK_HEADER: [u8; 6] = [0x12, 0x11, ENCRYPTION_SIZE as u8, 0x00, 0x00, 0x00];
+
+/// Upper bound of a modulus, since encrypted values are stored as 18 bits.
+const MODULUS_LIMIT: u32 = 1 << 18;
+
+/// Returns all key blocks found in a buffer, in order of appearance.
+pub fn key_blocks(haystack: &[u8]) -> Vec<&[u8; ENCRYPTION_SIZE]> {
+  let block = |offset: usize| &haystack[offset..][..ENCRYPTION_SIZE];
+  block_offsets(haystack)
+    .into_iter()
+    .map(|offset| <&[u8; ENCRYPTION_SIZE]>::try_from(block(offset)).unwrap())
+    .collect()
+}
+
+/// Returns the XOR table obfuscating the key blocks in a buffer.
+///
+/// The table (four little endian `u32`, outside of the blocks) is located by
+/// decoding the blocks with each candidate, since only the actual table
+/// yields keys within the algorithm's bounds; 18-bit moduli, smaller keys and
+/// 16-bit XOR keys.
+pub fn xor_table(haystack: &[u8]) -> Option<[u32; 4]> {
+  let (offsets, blocks) = (block_offsets(haystack), key_blocks(haystack));
+  if blocks.is_empty() {
+    return None;
+  }
+
+  // The blocks' keys are small values obfuscated by the table, so their own
+  // bytes would resemble it.
+  let overlaps = |index: usize| {
+    offsets.iter().any(|&offset| index + 16 > offset && index < offset + ENCRYPTION_SIZE)
+  };
+
+  haystack
+    .windows(16)
+    .enumerate()
+    .filter(|&(index, _)| !overlaps(index))
+    .map(|(_, window)| {
+      let mut table = [0; 4];
+      LittleEndian::read_u32_into(window, &mut table);
+      table
+    })
+    .find(|table| blocks.iter().all(|block| decodes(block, table)))
+}
+
+/// Constructs an encryption scheme from the first two key blocks in a buffer.
+///
+/// The first block is used for encryption, and the second for decryption,
+/// using the XOR table found in the same buffer.
+pub fn crypto(haystack: &[u8]) -> Option<PacketCrypto> {
+  let blocks = key_blocks(haystack);
+  if blocks.len() < 2 {
+    return None;
+  }
+
+  let table = xor_table(haystack)?;
+  PacketCrypto::from_dat(blocks[0], blocks[1], &table).ok()
+}
+
+/// Returns the offsets of all key blocks in a buffer.
+fn block_offsets(haystack: &[u8]) -> Vec<usize> {
+  let mut offsets = Vec::new();
+  let mut offset = 0;
+
+  while let Some(index) = find(&haystack[offset..], &KEY_BLOCK_HEADER) {
+    let start = offset + index;
+    if start + ENCRYPTION_SIZE > haystack.len() {
+      break;
+    }
+
+    offsets.push(start);
+    offset = start + ENCRYPTION_SIZE;
+  }
+
+  offsets
+}
+
+/// Returns whether a key block decodes to valid keys using a XOR table.
+fn decodes(block: &[u8; ENCRYPTION_SIZE], table: &[u32; 4]) -> bool {
+  // Most candidates are rejected by their first modulus alone
+  let modulus = LittleEndian::read_u32(&block[KEY_BLOCK_HEADER.len()..]) ^ table[0];
+  if modulus >= MODULUS_LIMIT {
+    return false;
+  }
+
+  match dat::decode(block, table) {
+    Ok([moduli, keys, xor]) => (0..4).all(|index| {
+      moduli[index] < MODULUS_LIMIT && keys[index] < moduli[index] && xor[index] <= 0xFFFF
+    }),
+    Err(_) => false,
+  }
+}
+
+/// Returns the position of a needle within a haystack.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  if needle.is_empty() {
+    return None;
+  }
+
+  haystack
+    .windows(needle.len())
+    .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn find_embedded_keys() {
+    let mut binary = vec![0xCC; 100];
+    binary.extend_from_slice(include_bytes!("../../res/Enc1.dat"));
+    binary.extend_from_slice(&crate::XOR_CIPHER);
+    binary.extend_from_slice(include_bytes!("../../res/Dec1.dat"));
+    binary.extend_from_slice(&KEY_BLOCK_HEADER);
+
+    assert_eq!(key_blocks(&binary).len(), 2);
+    assert_eq!(xor_table(&binary), None);
+
+    binary.extend(super::super::XOR_CIPHER.iter().flat_map(|key| key.to_le_bytes()));
+    assert_eq!(xor_table(&binary), Some(super::super::XOR_CIPHER));
+
+    let raw = [0x00, 0xF4, 0x03, 0x00, 0x00];
+    let crypto = crypto(&binary).unwrap();
+    assert_eq!(crypto.encrypt(&raw), crate::crypto::CLIENT.encrypt(&raw));
+  }
+
+  #[test]
+  fn custom_xor_table() {
+    let table = [0x1234_5678, 0x9ABC_DEF0, 0x0F1E_2D3C, 0x4B5A_6978];
+    let (mut enc, mut dec) = (Vec::new(), Vec::new());
+    crate::crypto::CLIENT.write_keys(&mut enc, &mut dec, &table).unwrap();
+
+    let mut binary = vec![0x00; 64];
+    binary.extend(table.iter().flat_map(|key| key.to_le_bytes()));
+    binary.extend_from_slice(&[0x90; 7]);
+    binary.extend_from_slice(&enc);
+    binary.extend_from_slice(&dec);
+
+    assert_eq!(xor_table(&binary), Some(table));
+    assert_eq!(crypto(&binary).unwrap().fingerprint(), crate::crypto::CLIENT.fingerprint());
+  }
+}
@@ -0,0 +1,84 @@
+use bytes::BytesMut;
+use crate::Packet;
+use std::io;
+use tokio_io::codec::{Decoder, Encoder};
+
+/// A Mu Online packet codec for datagrams (e.g UDP).
+///
+/// Each datagram contains exactly one packet, so no reassembly is done and
+/// datagrams are never encrypted with the symmetric-key algorithm.
+#[derive(Debug, Default)]
+pub struct DatagramCodec {
+  cipher: Option<&'static [u8]>,
+}
+
+impl DatagramCodec {
+  /// Creates a new datagram codec.
+  pub fn new() -> Self { Self::default() }
+
+  /// Creates a new datagram codec using an XOR cipher.
+  pub fn with_cipher(cipher: &'static [u8]) -> Self {
+    DatagramCodec {
+      cipher: Some(cipher),
+    }
+  }
+}
+
+impl Encoder for DatagramCodec {
+  type Item = Packet;
+  type Error = io::Error;
+
+  /// Encodes a packet into a datagram.
+  fn encode(&mut self, packet: Packet, output: &mut BytesMut) -> io::Result<()> {
+    output.extend_from_slice(&packet.to_bytes_ex(self.cipher, None));
+    Ok(())
+  }
+}
+
+impl Decoder for DatagramCodec {
+  type Item = Packet;
+  type Error = io::Error;
+
+  /// Decodes a packet from a datagram.
+  fn decode(&mut self, input: &mut BytesMut) -> io::Result<Option<Self::Item>> {
+    if input.is_empty() {
+      return Ok(None);
+    }
+
+    // The datagram is consumed regardless of its validity
+    let datagram = input.split_to(input.len());
+    Packet::from_datagram(&datagram, self.cipher).map(Some)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PacketError;
+
+  #[test]
+  fn one_packet_per_datagram() {
+    let mut codec = DatagramCodec::new();
+
+    let mut datagram = BytesMut::from(&[0xC1, 0x04, 0x00, 0x01][..]);
+    assert!(codec.decode(&mut datagram).unwrap().is_some());
+    assert!(datagram.is_empty());
+
+    let mut datagram = BytesMut::from(&[0xC1, 0x04, 0x00, 0x01, 0xC1][..]);
+    assert!(codec.decode(&mut datagram).is_err());
+    assert!(datagram.is_empty());
+  }
+
+  #[test]
+  fn malformed_datagrams() {
+    let mut codec = DatagramCodec::new();
+
+    for datagram in [&[0xC1, 0x02, 0xC1][..], &[0xC2, 0x00, 0x01, 0x00], &[0xC2, 0x00]] {
+      let error = codec.decode(&mut BytesMut::from(datagram)).unwrap_err();
+      assert!(matches!(PacketError::of(&error), Some(PacketError::InvalidSize(_))));
+    }
+
+    let mut datagram = BytesMut::from(&[0xC1, 0x05, 0x00, 0x01][..]);
+    assert!(codec.decode(&mut datagram).is_err());
+  }
+}
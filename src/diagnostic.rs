@@ -0,0 +1,72 @@
+use crate::{PacketError, PacketKind};
+use std::{error, fmt};
+
+/// A problem found while decoding a packet, at a byte offset.
+///
+/// These are returned directly by lenient decoding, and wrapped by the
+/// `io::Error` values returned when strict decoding fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+  offset: usize,
+  message: String,
+  packet: Option<String>,
+  error: Option<PacketError>,
+}
+
+impl Diagnostic {
+  /// Creates a new diagnostic for a byte offset in the input.
+  pub fn new<S: Into<String>>(offset: usize, message: S) -> Self {
+    Diagnostic {
+      offset,
+      message: message.into(),
+      packet: None,
+      error: None,
+    }
+  }
+
+  /// Creates a new diagnostic of a failure, for a byte offset in the input.
+  pub fn from_error(offset: usize, error: PacketError) -> Self {
+    Diagnostic {
+      error: Some(error),
+      ..Self::new(offset, error.to_string())
+    }
+  }
+
+  /// Describes the packet, by its kind and codes, in which the problem was
+  /// found.
+  pub fn in_packet(mut self, kind: PacketKind, codes: &[u8]) -> Self {
+    let mut packet = format!("{:?}", kind);
+    for code in codes {
+      packet.push_str(&format!("/{:02X}", code));
+    }
+    self.packet = Some(packet);
+    self
+  }
+
+  /// Returns the offset, in the input, at which the problem was found.
+  pub fn offset(&self) -> usize { self.offset }
+
+  /// Returns a description of the problem.
+  pub fn message(&self) -> &str { &self.message }
+
+  /// Returns the packet in which the problem was found (e.g `C1/F3/03`).
+  pub fn packet(&self) -> Option<&str> { self.packet.as_deref() }
+
+  /// Returns the failure described, if it is one.
+  pub fn error(&self) -> Option<PacketError> { self.error }
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    match &self.packet {
+      Some(packet) => write!(fmt, "at byte {} of packet {}: {}", self.offset, packet, self.message),
+      None => write!(fmt, "at byte {}: {}", self.offset, self.message),
+    }
+  }
+}
+
+impl error::Error for Diagnostic {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    self.error.as_ref().map(|error| error as &(dyn error::Error + 'static))
+  }
+}
@@ -0,0 +1,32 @@
+use std::fmt;
+
+/// The direction of a packet, relative to the local endpoint.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+  /// A packet received from the peer.
+  Inbound,
+  /// A packet sent to the peer.
+  Outbound,
+}
+
+impl Direction {
+  /// Returns the opposite direction.
+  pub fn reverse(self) -> Self {
+    match self {
+      Direction::Inbound => Direction::Outbound,
+      Direction::Outbound => Direction::Inbound,
+    }
+  }
+
+  /// Returns the direction's name, as used in logs and metrics.
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Direction::Inbound => "inbound",
+      Direction::Outbound => "outbound",
+    }
+  }
+}
+
+impl fmt::Display for Direction {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result { fmt.write_str(self.as_str()) }
+}
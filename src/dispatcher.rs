@@ -0,0 +1,328 @@
+//! Asynchronous dispatching of packets to typed handlers.
+//!
+//! This is the asynchronous counterpart of `PacketRegistry`; handlers return
+//! futures (e.g `async fn`), which are awaited by the dispatch. Packets are
+//! decoded before their handler is called, so the handlers' futures borrow
+//! neither the packet nor the dispatcher. The futures are those of the
+//! standard library, so any executor (e.g tokio 1.x) may drive them.
+//!
+//! Cross-cutting concerns (e.g authentication, rate limits, logging and
+//! metrics) are layered as middleware, applied to every packet before its
+//! handler. A connection's `Flow` may also be consulted, rejecting packets
+//! received out of phase.
+
+use crate::flow::Flow;
+use crate::registry::Handlers;
+use crate::{Direction, Packet, PacketDecodable, PacketId, PacketKind};
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::{fmt, io};
+
+/// A handler's future, boxed.
+type HandlerFuture = Pin<Box<dyn Future<Output = io::Result<()>> + Send>>;
+
+/// A function handling a packet, with a caller's context.
+type Handler<C> = Box<dyn Fn(&Packet, C) -> io::Result<HandlerFuture> + Send + Sync>;
+
+/// The future of a dispatch, resolving to whether the packet had a handler.
+pub type DispatchFuture<'a> = Pin<Box<dyn Future<Output = io::Result<bool>> + Send + 'a>>;
+
+/// A layer of a dispatcher, applied to packets before their handler.
+///
+/// Each layer is passed the remainder of the dispatch, which it may run
+/// (e.g to log or measure its result), or skip by returning early (e.g to
+/// reject unauthenticated or excessive packets).
+pub trait Middleware<C>: Send + Sync {
+  /// Handles a packet, continuing the dispatch using `next`.
+  fn call<'a>(&'a self, packet: &'a Packet, context: C, next: Next<'a, C>) -> DispatchFuture<'a>;
+}
+
+/// The remainder of a dispatch; any further layers, and the handler.
+pub struct Next<'a, C> {
+  layers: &'a [Box<dyn Middleware<C>>],
+  handlers: &'a Handlers<Handler<C>>,
+}
+
+impl<'a, C: Send + 'static> Next<'a, C> {
+  /// Runs the remainder of the dispatch.
+  pub fn run(self, packet: &'a Packet, context: C) -> DispatchFuture<'a> {
+    match self.layers.split_first() {
+      Some((layer, layers)) => layer.call(packet, context, Next { layers, ..self }),
+      None => {
+        let handler = self.handlers.get(packet);
+        Box::pin(async move {
+          match handler {
+            Some(handler) => handler(packet, context)?.await.map(|_| true),
+            None => Ok(false),
+          }
+        })
+      },
+    }
+  }
+}
+
+/// A middleware rejecting packets, using a predicate.
+struct Guard<F>(F);
+
+impl<C, F> Middleware<C> for Guard<F>
+where
+  C: Send + 'static,
+  F: Fn(&Packet, &C) -> io::Result<()> + Send + Sync,
+{
+  fn call<'a>(&'a self, packet: &'a Packet, context: C, next: Next<'a, C>) -> DispatchFuture<'a> {
+    match (self.0)(packet, &context) {
+      Ok(()) => next.run(packet, context),
+      Err(error) => Box::pin(future::ready(Err(error))),
+    }
+  }
+}
+
+/// An asynchronous dispatcher of packets.
+///
+/// Handlers are matched the same way as those of a `PacketRegistry`. Since
+/// each handler's future owns its context, the context is passed by value
+/// (e.g a cloned `Arc` of a connection's session).
+pub struct PacketDispatcher<C> {
+  handlers: Handlers<Handler<C>>,
+  layers: Vec<Box<dyn Middleware<C>>>,
+}
+
+impl<C: Send + 'static> PacketDispatcher<C> {
+  /// Creates a dispatcher, without any handlers.
+  pub fn new() -> Self {
+    PacketDispatcher {
+      handlers: Handlers::default(),
+      layers: Vec::new(),
+    }
+  }
+
+  /// Adds a middleware layer, applied to all packets.
+  ///
+  /// Layers are applied in the order they are added, so the first one added
+  /// is the outermost.
+  pub fn layer<M: Middleware<C> + 'static>(mut self, middleware: M) -> Self {
+    self.layers.push(Box::new(middleware));
+    self
+  }
+
+  /// Adds a layer rejecting packets, with the error returned by a predicate
+  /// (e.g for packets of unauthenticated sessions).
+  pub fn guard<F>(self, predicate: F) -> Self
+  where
+    F: Fn(&Packet, &C) -> io::Result<()> + Send + Sync + 'static,
+  {
+    self.layer(Guard(predicate))
+  }
+
+  /// Registers an asynchronous handler of a packet type, which is decoded
+  /// before the handler is called.
+  pub fn on<T, F, R>(self, handler: F) -> Self
+  where
+    T: PacketDecodable + 'static,
+    F: Fn(T, C) -> R + Send + Sync + 'static,
+    R: Future<Output = io::Result<()>> + Send + 'static,
+  {
+    self.insert(T::kind(), T::identifier(), move |packet, context| {
+      Ok(Box::pin(handler(T::from_packet(packet)?, context)))
+    })
+  }
+
+  /// Registers an asynchronous handler of raw packets, by their kind and
+  /// identifier.
+  pub fn on_packet<F, R>(self, kind: PacketKind, id: PacketId, handler: F) -> Self
+  where
+    F: Fn(Packet, C) -> R + Send + Sync + 'static,
+    R: Future<Output = io::Result<()>> + Send + 'static,
+  {
+    self.insert(kind, id, move |packet, context| {
+      Ok(Box::pin(handler(packet.clone(), context)))
+    })
+  }
+
+  /// Dispatches a packet through the layers to its handler, awaiting its
+  /// completion.
+  ///
+  /// Returns whether the packet had a handler, or any error from a layer,
+  /// from decoding the packet or from the handler itself.
+  pub async fn dispatch(&self, packet: &Packet, context: C) -> io::Result<bool> {
+    let next = Next {
+      layers: &self.layers,
+      handlers: &self.handlers,
+    };
+    next.run(packet, context).await
+  }
+
+  /// Dispatches a packet, once it has been checked against a connection's
+  /// flow (possibly moving it to another phase).
+  ///
+  /// Packets not permitted in the flow's phase are rejected with an error
+  /// wrapping a `FlowViolation`, before any layers or handlers. Packets sent
+  /// on the connection must be checked against the flow by the caller.
+  pub async fn dispatch_flow(
+    &self,
+    flow: &mut Flow,
+    packet: &Packet,
+    context: C,
+  ) -> io::Result<bool> {
+    flow.check(Direction::Inbound, packet)?;
+    self.dispatch(packet, context).await
+  }
+
+  /// Returns whether a packet has a handler.
+  pub fn contains(&self, packet: &Packet) -> bool { self.handlers.get(packet).is_some() }
+
+  /// Returns the number of handlers.
+  pub fn len(&self) -> usize { self.handlers.len() }
+
+  /// Returns whether the dispatcher is without any handlers.
+  pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+  fn insert<F>(mut self, kind: PacketKind, id: PacketId, handler: F) -> Self
+  where
+    F: Fn(&Packet, C) -> io::Result<HandlerFuture> + Send + Sync + 'static,
+  {
+    self.handlers.insert(kind, id, Box::new(handler));
+    self
+  }
+}
+
+impl<C: Send + 'static> Default for PacketDispatcher<C> {
+  fn default() -> Self { Self::new() }
+}
+
+impl<C> fmt::Debug for PacketDispatcher<C> {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    fmt
+      .debug_struct("PacketDispatcher")
+      .field("handlers", &self.handlers.keys())
+      .field("layers", &self.layers.len())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::flow::{FlowViolation, Phase};
+  use crate::packets::character::{DeleteCharacter, SelectCharacter};
+  use crate::PacketEncodable;
+  use std::sync::{Arc, Mutex};
+  use std::task::{Context, Poll, Wake, Waker};
+  use std::thread::{self, Thread};
+
+  type Log = Arc<Mutex<Vec<String>>>;
+
+  async fn select(packet: SelectCharacter, log: Log) -> io::Result<()> {
+    log.lock().unwrap().push(String::from_utf8_lossy(&packet.name.0[..4]).into_owned());
+    Ok(())
+  }
+
+  struct Unpark(Thread);
+
+  impl Wake for Unpark {
+    fn wake(self: Arc<Self>) { self.0.unpark(); }
+  }
+
+  fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut future = Box::pin(future);
+    loop {
+      match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+        Poll::Ready(output) => return output,
+        Poll::Pending => thread::park(),
+      }
+    }
+  }
+
+  #[test]
+  fn dispatch() {
+    let dispatcher = PacketDispatcher::<Log>::new()
+      .on(select)
+      .on_packet(PacketKind::C1, PacketId::new(0xF3), |packet, log: Log| async move {
+        log.lock().unwrap().push(format!("{:02X?}", packet.data()));
+        Ok(())
+      });
+
+    let log = Log::default();
+    let packet = SelectCharacter::new("Nova").unwrap().to_packet().unwrap();
+    assert!(block_on(dispatcher.dispatch(&packet, log.clone())).unwrap());
+
+    let mut unknown = Packet::new(PacketKind::C1, 0xF3);
+    unknown.append(&[0x7F]);
+    assert!(block_on(dispatcher.dispatch(&unknown, log.clone())).unwrap());
+    let unhandled = Packet::new(PacketKind::C1, 0xF4);
+    assert!(!block_on(dispatcher.dispatch(&unhandled, log.clone())).unwrap());
+    assert_eq!(*log.lock().unwrap(), ["Nova", "[7F]"]);
+
+    // Errors of handlers are returned by the dispatch
+    let packet = DeleteCharacter::new("Nova", "1234567").unwrap().to_packet().unwrap();
+    let dispatcher =
+      dispatcher.on(|_: DeleteCharacter, _| async { Err(io::Error::other("denied")) });
+    let error = block_on(dispatcher.dispatch(&packet, log)).unwrap_err();
+    assert_eq!(error.to_string(), "denied");
+    assert_eq!(dispatcher.len(), 3);
+  }
+
+  /// Logs the result of each dispatch.
+  struct Audit;
+
+  impl Middleware<Log> for Audit {
+    fn call<'a>(&'a self, packet: &'a Packet, log: Log, next: Next<'a, Log>) -> DispatchFuture<'a> {
+      Box::pin(async move {
+        let result = next.run(packet, log.clone()).await;
+        log.lock().unwrap().push(format!("{:02X} {:?}", packet.code(), result.as_ref().ok()));
+        result
+      })
+    }
+  }
+
+  #[test]
+  fn middleware() {
+    let dispatcher = PacketDispatcher::<Log>::new()
+      .layer(Audit)
+      .guard(|packet, log| match packet.code() {
+        0xF3 if !log.lock().unwrap().contains(&"login".into()) => {
+          Err(io::Error::other("not logged in"))
+        },
+        _ => Ok(()),
+      })
+      .on(select)
+      .on_packet(PacketKind::C1, PacketId::new(0xF1), |_, log: Log| async move {
+        log.lock().unwrap().push("login".into());
+        Ok(())
+      });
+
+    // Layers are applied before the handler, in the order they were added
+    let log = Log::default();
+    let dispatch = |packet: &Packet| block_on(dispatcher.dispatch(packet, log.clone()));
+    let select = SelectCharacter::new("Nova").unwrap().to_packet().unwrap();
+
+    let error = dispatch(&select).unwrap_err();
+    assert_eq!(error.to_string(), "not logged in");
+    assert!(dispatch(&Packet::new(PacketKind::C1, 0xF1)).unwrap());
+    assert!(dispatch(&select).unwrap());
+    assert!(!dispatch(&Packet::new(PacketKind::C1, 0xF4)).unwrap());
+
+    let expected = ["F3 None", "login", "F1 Some(true)", "Nova", "F3 Some(true)", "F4 Some(false)"];
+    assert_eq!(*log.lock().unwrap(), expected);
+  }
+
+  #[test]
+  fn flow() {
+    let dispatcher = PacketDispatcher::<Log>::new().on(select);
+    let mut flow = Flow::server_default();
+    let log = Log::default();
+
+    // Selecting a character before logging in is rejected
+    let packet = SelectCharacter::new("Nova").unwrap().to_packet().unwrap();
+    let error = block_on(dispatcher.dispatch_flow(&mut flow, &packet, log.clone())).unwrap_err();
+    let violation = FlowViolation::of(&error).unwrap();
+    assert_eq!(violation.phase, Phase::Handshake);
+    assert_eq!(violation.packet, "F3:03".parse().unwrap());
+    assert!(log.lock().unwrap().is_empty());
+
+    flow.set_phase(Phase::CharacterSelect);
+    assert!(block_on(dispatcher.dispatch_flow(&mut flow, &packet, log.clone())).unwrap());
+    assert_eq!(*log.lock().unwrap(), ["Nova"]);
+  }
+}
@@ -0,0 +1,152 @@
+use crate::{CryptoError, Diagnostic};
+use std::{error, fmt, io};
+
+/// A failure to decode, decrypt or encode a packet.
+///
+/// The crate's functions return `io::Error` values for compatibility, which
+/// wrap these (possibly within a `Diagnostic`). Use `PacketError::of` to
+/// distinguish failures, instead of matching their messages.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PacketError {
+  /// The first byte is not a packet kind.
+  UnknownKind(u8),
+  /// The input ended before the packet did.
+  Incomplete {
+    /// The number of bytes received.
+    received: usize,
+    /// The packet's declared size.
+    size: usize,
+  },
+  /// The declared size is smaller than the packet's header.
+  InvalidSize(usize),
+  /// An encrypted packet was received without any keys to decrypt it.
+  MissingDecryption,
+  /// None of a key rotation's keys are valid at the time of decoding.
+  NoValidKeys,
+  /// A packet without data was rejected.
+  Empty,
+  /// A packet is too large to be encoded.
+  TooLarge,
+  /// An output buffer is too small for a packet.
+  BufferTooSmall {
+    /// The size of the buffer.
+    size: usize,
+    /// The size required.
+    required: usize,
+  },
+  /// A frame exceeded the codec's maximum size.
+  SizeLimit {
+    /// The size of the frame.
+    size: usize,
+    /// The maximum size.
+    limit: usize,
+  },
+  /// A packet exceeded the maximum size of its code.
+  CodeLimit {
+    /// The packet's code.
+    code: u8,
+    /// The size of the packet.
+    size: usize,
+    /// The maximum size.
+    limit: usize,
+  },
+  /// A packet failed to decrypt, or to be verified.
+  Crypto(CryptoError),
+}
+
+impl PacketError {
+  /// Returns the kind of `io::Error` which wraps the failure.
+  pub fn kind(&self) -> io::ErrorKind {
+    match self {
+      PacketError::Incomplete { .. } => io::ErrorKind::UnexpectedEof,
+      PacketError::TooLarge | PacketError::BufferTooSmall { .. } => io::ErrorKind::InvalidInput,
+      _ => io::ErrorKind::InvalidData,
+    }
+  }
+
+  /// Returns the failure wrapped by an error, if any.
+  pub fn of(error: &io::Error) -> Option<PacketError> {
+    let inner = error.get_ref()?;
+    if let Some(error) = inner.downcast_ref::<PacketError>() {
+      return Some(*error);
+    }
+    if let Some(error) = inner.downcast_ref::<CryptoError>() {
+      return Some(PacketError::Crypto(*error));
+    }
+    inner.downcast_ref::<Diagnostic>()?.error()
+  }
+}
+
+impl fmt::Display for PacketError {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      PacketError::UnknownKind(_) => write!(fmt, "not a packet"),
+      PacketError::Incomplete { received, size } => {
+        write!(fmt, "missing data, {} of {} bytes received", received, size)
+      },
+      PacketError::InvalidSize(_) => write!(fmt, "invalid packet size"),
+      PacketError::MissingDecryption => write!(fmt, "missing decryption"),
+      PacketError::NoValidKeys => write!(fmt, "no valid keys in rotation"),
+      PacketError::Empty => write!(fmt, "empty packet"),
+      PacketError::TooLarge => write!(fmt, "packet too large"),
+      PacketError::BufferTooSmall { size, required } => {
+        write!(fmt, "buffer too small, {} of {} bytes", size, required)
+      },
+      PacketError::SizeLimit { .. } => write!(fmt, "max packet size exceeded"),
+      PacketError::CodeLimit { code, size, limit } => write!(
+        fmt,
+        "packet size {} exceeds limit {} for code {:02X}",
+        size, limit, code
+      ),
+      PacketError::Crypto(error) => error.fmt(fmt),
+    }
+  }
+}
+
+impl error::Error for PacketError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      PacketError::Crypto(error) => Some(error),
+      _ => None,
+    }
+  }
+}
+
+impl From<CryptoError> for PacketError {
+  fn from(error: CryptoError) -> Self { PacketError::Crypto(error) }
+}
+
+impl From<PacketError> for io::Error {
+  fn from(error: PacketError) -> Self { io::Error::new(error.kind(), error) }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Packet;
+
+  #[test]
+  fn distinguished() {
+    let error = Packet::from_bytes(&[0xAA, 0x03, 0x00]).unwrap_err();
+    assert_eq!(PacketError::of(&error), Some(PacketError::UnknownKind(0xAA)));
+
+    let error = Packet::from_bytes(&[0xC1, 0x05, 0x00]).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    let expected = PacketError::Incomplete { received: 3, size: 5 };
+    assert_eq!(PacketError::of(&error), Some(expected));
+
+    let error = Packet::from_bytes(&[0xC3, 0x03, 0x00]).unwrap_err();
+    assert_eq!(PacketError::of(&error), Some(PacketError::MissingDecryption));
+
+    let error = Packet::from_bytes(&[0xC1, 0x02, 0xC1]).unwrap_err();
+    assert_eq!(PacketError::of(&error), Some(PacketError::InvalidSize(2)));
+
+    let client = Some(&*crate::crypto::CLIENT);
+    let error = Packet::from_bytes_ex(&[0xC3, 0x01, 0x00], None, client).unwrap_err();
+    assert_eq!(PacketError::of(&error), Some(PacketError::InvalidSize(1)));
+
+    let error = io::Error::from(PacketError::Crypto(CryptoError::Checksum));
+    assert_eq!(error.to_string(), "incorrect data hash");
+    assert_eq!(PacketError::of(&io::Error::other("other")), None);
+  }
+}
@@ -0,0 +1,217 @@
+//! Validation of packet ordering, per connection phase.
+//!
+//! A connection moves through phases (e.g being greeted, logging in and
+//! playing), and each phase only permits some packets from the peer. A flow
+//! encodes this as a state machine; received packets are checked against the
+//! current phase, and both sent & received packets may move it to another.
+//! This rejects out-of-phase packets (e.g movement before logging in) before
+//! they reach any handlers.
+
+use crate::{Direction, Packet, PacketId};
+use std::{error, fmt, io};
+
+/// A phase of a connection.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Phase {
+  /// Connected, but not yet greeted by the server.
+  Handshake,
+  /// Greeted, but not yet logged in.
+  Authenticating,
+  /// Logged in, but without a character selected.
+  CharacterSelect,
+  /// Playing a character.
+  InGame,
+}
+
+impl fmt::Display for Phase {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    fmt.write_str(match self {
+      Phase::Handshake => "handshake",
+      Phase::Authenticating => "authenticating",
+      Phase::CharacterSelect => "character select",
+      Phase::InGame => "in-game",
+    })
+  }
+}
+
+/// A packet received in a phase which does not permit it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FlowViolation {
+  /// The phase of the connection.
+  pub phase: Phase,
+  /// The packet's code, along with its first subcode if any.
+  pub packet: PacketId,
+}
+
+impl fmt::Display for FlowViolation {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    write!(fmt, "packet {} not permitted during {}", self.packet, self.phase)
+  }
+}
+
+impl FlowViolation {
+  /// Returns the violation wrapped by an error, if any.
+  pub fn of(error: &io::Error) -> Option<FlowViolation> {
+    error.get_ref()?.downcast_ref::<FlowViolation>().copied()
+  }
+}
+
+impl error::Error for FlowViolation {}
+
+impl From<FlowViolation> for io::Error {
+  fn from(violation: FlowViolation) -> Self {
+    io::Error::new(io::ErrorKind::InvalidData, violation)
+  }
+}
+
+/// A transition between phases, caused by a packet.
+#[derive(Clone, Debug)]
+struct Transition {
+  from: Phase,
+  direction: Direction,
+  codes: Vec<u8>,
+  to: Phase,
+}
+
+/// A state machine of a connection's phases.
+///
+/// Codes are matched as a prefix of a packet's code & data, so an empty
+/// prefix matches any packet, and a prefix may include data (e.g a result).
+/// Only received packets are validated, since sent ones are trusted.
+#[derive(Clone, Debug)]
+pub struct Flow {
+  phase: Phase,
+  permitted: Vec<(Option<Phase>, Vec<u8>)>,
+  transitions: Vec<Transition>,
+}
+
+impl Flow {
+  /// Creates a flow in a phase, without any permitted packets.
+  pub fn new(phase: Phase) -> Self {
+    Flow {
+      phase,
+      permitted: Vec::new(),
+      transitions: Vec::new(),
+    }
+  }
+
+  /// Returns the flow of a game server, validating its clients' packets.
+  ///
+  /// Keep-alives are permitted in any phase, and any packet once in-game.
+  /// Sending the greeting, a successful login result and a character's
+  /// information each advance the phase. Returning to character selection
+  /// is left to the server, using `set_phase`.
+  pub fn server_default() -> Self {
+    Flow::new(Phase::Handshake)
+      .permit_always(&[0x0E])
+      .permit(Phase::Authenticating, &[0xF1, 0x01])
+      .permit(Phase::CharacterSelect, &[0xF3])
+      .permit(Phase::CharacterSelect, &[0xF1, 0x02])
+      .permit(Phase::InGame, &[])
+      .transition(Phase::Handshake, Direction::Outbound, &[0xF1, 0x00], Phase::Authenticating)
+      .transition(
+        Phase::Authenticating,
+        Direction::Outbound,
+        &[0xF1, 0x01, 0x01],
+        Phase::CharacterSelect,
+      )
+      .transition(Phase::CharacterSelect, Direction::Outbound, &[0xF3, 0x03], Phase::InGame)
+  }
+
+  /// Permits packets starting with codes to be received in a phase.
+  pub fn permit(mut self, phase: Phase, codes: &[u8]) -> Self {
+    self.permitted.push((Some(phase), codes.to_vec()));
+    self
+  }
+
+  /// Permits packets starting with codes to be received in any phase.
+  pub fn permit_always(mut self, codes: &[u8]) -> Self {
+    self.permitted.push((None, codes.to_vec()));
+    self
+  }
+
+  /// Moves from one phase to another, once a packet starting with codes has
+  /// been sent or received in it.
+  pub fn transition(mut self, from: Phase, direction: Direction, codes: &[u8], to: Phase) -> Self {
+    self.transitions.push(Transition {
+      from,
+      direction,
+      codes: codes.to_vec(),
+      to,
+    });
+    self
+  }
+
+  /// Returns the current phase.
+  pub fn phase(&self) -> Phase { self.phase }
+
+  /// Sets the current phase (e.g once a character logs out).
+  pub fn set_phase(&mut self, phase: Phase) { self.phase = phase; }
+
+  /// Checks a packet sent or received, moving to another phase if it causes
+  /// a transition.
+  ///
+  /// A violation is returned if a received packet is not permitted in the
+  /// current phase, in which case the phase is retained.
+  pub fn check(&mut self, direction: Direction, packet: &Packet) -> Result<(), FlowViolation> {
+    let phase = self.phase;
+    if direction == Direction::Inbound {
+      let permitted = self.permitted.iter().any(|(permitted, codes)| {
+        permitted.is_none_or(|permitted| permitted == phase) && starts_with(packet, codes)
+      });
+
+      if !permitted {
+        let packet = PacketId::of(packet, packet.data().len().min(1)).expect("at most one subcode");
+        return Err(FlowViolation { phase, packet });
+      }
+    }
+
+    let transition = self.transitions.iter().find(|transition| {
+      transition.from == phase
+        && transition.direction == direction
+        && starts_with(packet, &transition.codes)
+    });
+
+    if let Some(transition) = transition {
+      self.phase = transition.to;
+    }
+    Ok(())
+  }
+}
+
+/// Returns whether a packet's code & data start with codes.
+fn starts_with(packet: &Packet, codes: &[u8]) -> bool {
+  let content = std::iter::once(packet.code()).chain(packet.data().iter().cloned());
+  codes.iter().cloned().eq(content.take(codes.len()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn server_default() {
+    let packet = |bytes: &[u8]| Packet::from_bytes(bytes).unwrap();
+    let mut flow = Flow::server_default();
+
+    let walk = packet(&[0xC1, 0x05, 0xD4, 0x10, 0x20]);
+    let error = flow.check(Direction::Inbound, &walk).unwrap_err();
+    assert_eq!(error.to_string(), "packet D4:10 not permitted during handshake");
+    assert!(flow.check(Direction::Inbound, &packet(&[0xC1, 0x03, 0x0E])).is_ok());
+
+    flow.check(Direction::Outbound, &packet(&[0xC1, 0x04, 0xF1, 0x00])).unwrap();
+    assert_eq!(flow.phase(), Phase::Authenticating);
+    flow.check(Direction::Inbound, &packet(&[0xC1, 0x04, 0xF1, 0x01])).unwrap();
+
+    // A rejected login does not advance the phase
+    flow.check(Direction::Outbound, &packet(&[0xC1, 0x05, 0xF1, 0x01, 0x00])).unwrap();
+    assert_eq!(flow.phase(), Phase::Authenticating);
+    flow.check(Direction::Outbound, &packet(&[0xC1, 0x05, 0xF1, 0x01, 0x01])).unwrap();
+    assert_eq!(flow.phase(), Phase::CharacterSelect);
+    assert!(flow.check(Direction::Inbound, &walk).is_err());
+
+    flow.check(Direction::Outbound, &packet(&[0xC1, 0x04, 0xF3, 0x03])).unwrap();
+    assert_eq!(flow.phase(), Phase::InGame);
+    assert!(flow.check(Direction::Inbound, &walk).is_ok());
+  }
+}
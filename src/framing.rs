@@ -0,0 +1,77 @@
+//! The codec's implementation of the `tokio-util` codec traits.
+//!
+//! This lets `tokio_util::codec::Framed` from tokio 1.x be used directly
+//! with the codec. Since the codec itself is built upon the buffers of
+//! `bytes` 0.4, received bytes are moved to a buffer of its own, and encoded
+//! frames are copied to the output.
+
+use crate::{Packet, PacketCodec};
+use bytes1::BytesMut;
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+impl Encoder<Packet> for PacketCodec {
+  type Error = io::Error;
+
+  fn encode(&mut self, packet: Packet, output: &mut BytesMut) -> io::Result<()> {
+    let mut bytes = bytes::BytesMut::new();
+    tokio_io::codec::Encoder::encode(self, packet, &mut bytes)?;
+    output.extend_from_slice(&bytes);
+    Ok(())
+  }
+}
+
+impl Decoder for PacketCodec {
+  type Item = Packet;
+  type Error = io::Error;
+
+  fn decode(&mut self, input: &mut BytesMut) -> io::Result<Option<Packet>> {
+    if !input.is_empty() {
+      self.pending.extend_from_slice(input);
+      input.clear();
+    }
+
+    let mut pending = std::mem::take(&mut self.pending);
+    let result = tokio_io::codec::Decoder::decode(self, &mut pending);
+    self.pending = pending;
+    result
+  }
+
+  fn decode_eof(&mut self, input: &mut BytesMut) -> io::Result<Option<Packet>> {
+    match self.decode(input)? {
+      Some(packet) => Ok(Some(packet)),
+      None if self.pending.is_empty() => Ok(None),
+      None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "bytes remaining on stream")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{crypto, PacketCodecState};
+
+  #[test]
+  fn framed() {
+    let state = || PacketCodecState::builder().crypto(crypto::CLIENT.clone()).build();
+    let mut sender = PacketCodec::new(state(), PacketCodecState::new());
+    let mut receiver = PacketCodec::new(PacketCodecState::new(), state());
+    let ping = Packet::from_bytes(&[0xC1, 0x04, 0x0E, 0x01]).unwrap();
+
+    let mut buffer = BytesMut::new();
+    Encoder::encode(&mut sender, ping.clone(), &mut buffer).unwrap();
+    Encoder::encode(&mut sender, ping.clone(), &mut buffer).unwrap();
+    assert_eq!(buffer[0], 0xC3);
+
+    // Bytes are received in two parts, splitting the second frame
+    let mut rest = buffer.split_off(buffer.len() - 2);
+    let packet = Decoder::decode(&mut receiver, &mut buffer).unwrap().unwrap();
+    assert_eq!(packet.data(), ping.data());
+    assert!(Decoder::decode(&mut receiver, &mut buffer).unwrap().is_none());
+    assert!(Decoder::decode_eof(&mut receiver, &mut buffer).is_err());
+
+    let packet = Decoder::decode_eof(&mut receiver, &mut rest).unwrap().unwrap();
+    assert_eq!(packet.data(), ping.data());
+    assert!(Decoder::decode_eof(&mut receiver, &mut rest).unwrap().is_none());
+  }
+}
@@ -0,0 +1,86 @@
+use byteorder::{BigEndian, ByteOrder};
+use crate::{PacketError, PacketKind};
+use std::io;
+
+/// A packet's header; its kind and declared size.
+///
+/// The header is never encrypted, so frame boundaries can be determined
+/// without any keys (e.g by a proxy, before deciding whether to decrypt).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PacketHeader {
+  kind: PacketKind,
+  size: usize,
+  complete: bool,
+}
+
+impl PacketHeader {
+  /// Parses the header at the start of a buffer, without decrypting or
+  /// copying anything. Returns `None` if the header is incomplete.
+  pub(crate) fn peek(bytes: &[u8]) -> io::Result<Option<Self>> {
+    let kind = match bytes.first() {
+      Some(&byte) => PacketKind::from_byte(byte).ok_or(PacketError::UnknownKind(byte))?,
+      None => return Ok(None),
+    };
+
+    let length = 1 + kind.bytes();
+    if bytes.len() < length {
+      return Ok(None);
+    }
+
+    // The size must at least span the header, and any unencrypted code
+    let size = BigEndian::read_uint(&bytes[1..length], kind.bytes()) as usize;
+    if size < kind.offset() {
+      return Err(PacketError::InvalidSize(size).into());
+    }
+
+    Ok(Some(PacketHeader {
+      kind,
+      size,
+      complete: bytes.len() >= size,
+    }))
+  }
+
+  /// Returns the packet's kind, as declared on the wire.
+  pub fn kind(&self) -> PacketKind { self.kind }
+
+  /// Returns the packet's size, as declared on the wire (i.e including the
+  /// header, and before any decryption).
+  pub fn size(&self) -> usize { self.size }
+
+  /// Returns whether the entire frame was available when peeked.
+  pub fn is_complete(&self) -> bool { self.complete }
+
+  /// Returns the size of the header itself (i.e the kind and size bytes).
+  #[cfg(feature = "codec")]
+  pub(crate) fn len(&self) -> usize { 1 + self.kind.bytes() }
+
+  /// Rewrites the declared size of the frame at the start of a buffer.
+  #[cfg(feature = "codec")]
+  pub(crate) fn resize(&self, bytes: &mut [u8], size: usize) -> io::Result<()> {
+    if size < self.len() || size > self.kind.max_size() {
+      return Err(PacketError::InvalidSize(size).into());
+    }
+
+    BigEndian::write_uint(&mut bytes[1..self.len()], size as u64, self.kind.bytes());
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn peek() {
+    let header = PacketHeader::peek(&[0xC4, 0x00, 0x06, 0xAA]).unwrap().unwrap();
+    assert_eq!((header.kind(), header.size()), (PacketKind::C4, 6));
+    assert!(!header.is_complete());
+
+    let header = PacketHeader::peek(&[0xC1, 0x03, 0x0E, 0xC1]).unwrap().unwrap();
+    assert!(header.is_complete());
+    assert_eq!(PacketHeader::peek(&[0xC2, 0x00]).unwrap(), None);
+    assert!(PacketHeader::peek(&[0xC1, 0x01]).is_err());
+    assert!(PacketHeader::peek(&[0xC1, 0x02]).is_err());
+    assert!(PacketHeader::peek(&[0xC4, 0x00, 0x02]).is_err());
+  }
+}
@@ -0,0 +1,134 @@
+use crate::Packet;
+use std::{fmt, io, str::FromStr};
+
+/// The maximum number of subcodes of an identifier.
+const MAX_SUBCODES: usize = 3;
+
+/// A packet's identifier; its code, followed by up to three subcodes.
+///
+/// Identifiers are stored inline, so they are cheap to copy, hash & compare
+/// (e.g when looking up a handler for each packet). They are displayed as
+/// colon-separated hexadecimal (e.g `F3:01`).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PacketId {
+  len: u8,
+  codes: [u8; 1 + MAX_SUBCODES],
+}
+
+impl PacketId {
+  /// Creates an identifier of a code, without subcodes.
+  pub const fn new(code: u8) -> Self {
+    PacketId {
+      len: 1,
+      codes: [code, 0, 0, 0],
+    }
+  }
+
+  /// Creates an identifier of a code, followed by subcodes.
+  ///
+  /// Returns `None` if there are more than three subcodes.
+  pub fn with_subcodes(code: u8, subcodes: &[u8]) -> Option<Self> {
+    if subcodes.len() > MAX_SUBCODES {
+      return None;
+    }
+
+    let mut id = Self::new(code);
+    id.codes[1..=subcodes.len()].copy_from_slice(subcodes);
+    id.len += subcodes.len() as u8;
+    Some(id)
+  }
+
+  /// Creates an identifier from its bytes, i.e a code followed by subcodes.
+  pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+    let (&code, subcodes) = bytes.split_first()?;
+    Self::with_subcodes(code, subcodes)
+  }
+
+  /// Returns the identifier of a packet, using a number of subcodes.
+  ///
+  /// Returns `None` if the packet lacks the subcodes.
+  pub fn of(packet: &Packet, subcodes: usize) -> Option<Self> {
+    Self::with_subcodes(packet.code(), packet.data().get(..subcodes)?)
+  }
+
+  /// Returns the code.
+  pub fn code(&self) -> u8 { self.codes[0] }
+
+  /// Returns the subcodes.
+  pub fn subcodes(&self) -> &[u8] { &self.codes[1..self.len as usize] }
+
+  /// Returns the code, followed by the subcodes.
+  pub fn as_bytes(&self) -> &[u8] { &self.codes[..self.len as usize] }
+
+  /// Returns whether a packet starts with the code, followed by the
+  /// subcodes.
+  pub fn matches(&self, packet: &Packet) -> bool {
+    packet.code() == self.code() && packet.data().starts_with(self.subcodes())
+  }
+}
+
+impl AsRef<[u8]> for PacketId {
+  fn as_ref(&self) -> &[u8] { self.as_bytes() }
+}
+
+impl From<u8> for PacketId {
+  fn from(code: u8) -> Self { Self::new(code) }
+}
+
+impl fmt::Display for PacketId {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    for (index, code) in self.as_bytes().iter().enumerate() {
+      if index > 0 {
+        fmt.write_str(":")?;
+      }
+      write!(fmt, "{:02X}", code)?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Debug for PacketId {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result { write!(fmt, "PacketId({})", self) }
+}
+
+impl FromStr for PacketId {
+  type Err = io::Error;
+
+  /// Parses an identifier in its displayed form (e.g `F3:01`).
+  fn from_str(text: &str) -> io::Result<Self> {
+    let invalid = || {
+      let message = format!("invalid packet identifier '{}'", text);
+      io::Error::new(io::ErrorKind::InvalidInput, message)
+    };
+
+    let bytes = text
+      .split(':')
+      .map(|code| match code.len() {
+        2 => u8::from_str_radix(code, 16).map_err(|_| invalid()),
+        _ => Err(invalid()),
+      })
+      .collect::<io::Result<Vec<_>>>()?;
+    Self::from_bytes(&bytes).ok_or_else(invalid)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn identifiers() {
+    let id = PacketId::with_subcodes(0xF3, &[0x01]).unwrap();
+    assert_eq!(id.to_string(), "F3:01");
+    assert_eq!("F3:01".parse::<PacketId>().unwrap(), id);
+    assert_eq!(id.as_bytes(), [0xF3, 0x01]);
+    assert_ne!(id, PacketId::new(0xF3));
+    assert!(PacketId::with_subcodes(0xF3, &[0; 4]).is_none());
+    assert!("F3:1".parse::<PacketId>().is_err());
+
+    let packet = Packet::from_bytes(&[0xC1, 0x05, 0xF3, 0x01, 0x2A]).unwrap();
+    assert!(id.matches(&packet));
+    assert_eq!(PacketId::of(&packet, 1), Some(id));
+    assert_eq!(PacketId::of(&packet, 3), None);
+  }
+}
@@ -1,8 +1,9 @@
+use crate::PacketCrypto;
 use std::mem;
 
 /// Description of different packet kinds.
 #[repr(u8)]
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PacketKind {
   C1 = 0xC1,
   C2 = 0xC2,
@@ -22,19 +23,24 @@ impl PacketKind {
     }
   }
 
-  /// Returns a `PacketKind` for the specified size.
-  pub fn from_size(size: usize, encrypted: bool) -> Option<Self> {
-    let (lower, upper) = if encrypted {
-      (PacketKind::C3, PacketKind::C4)
-    } else {
-      (PacketKind::C1, PacketKind::C2)
+  /// Returns a `PacketKind` for the specified data size.
+  ///
+  /// The size excludes the header (i.e kind, size and code). If the packet is
+  /// to be encrypted, the expansion caused by the encryption and any counter
+  /// of the keys is accounted for.
+  pub fn from_size(size: usize, crypto: Option<&PacketCrypto>) -> Option<Self> {
+    let (lower, upper, size) = match crypto {
+      // The code, and any counter, are encrypted along with the data
+      Some(crypto) => {
+        let plain = size + 1 + usize::from(crypto.has_counter());
+        (PacketKind::C3, PacketKind::C4, PacketCrypto::encrypt_len(plain))
+      },
+      None => (PacketKind::C1, PacketKind::C2, size),
     };
 
-    let size = size + lower.offset();
-
-    if size <= lower.max_size() {
+    if size + lower.offset() <= lower.max_size() {
       Some(lower)
-    } else if size <= upper.max_size() {
+    } else if size + upper.offset() <= upper.max_size() {
       Some(upper)
     } else {
       None
@@ -44,8 +50,8 @@ impl PacketKind {
   /// Returns the maximum size for the kind.
   pub fn max_size(self) -> usize {
     match self {
-      PacketKind::C1 | PacketKind::C3 => u8::max_value() as usize,
-      PacketKind::C2 | PacketKind::C4 => u16::max_value() as usize,
+      PacketKind::C1 | PacketKind::C3 => u8::MAX as usize,
+      PacketKind::C2 | PacketKind::C4 => u16::MAX as usize,
     }
   }
 
@@ -1,24 +1,151 @@
+#[cfg(feature = "aead")]
+pub use crate::aead::FrameAead;
 #[cfg(feature = "codec")]
-pub use crate::codec::{PacketCodec, PacketCodecState, PacketCodecStateBuilder};
-pub use crate::crypto::PacketCrypto;
+pub use crate::batch::TickBatcher;
+#[cfg(feature = "codec")]
+pub use crate::budget::{Budget, BudgetExceeded, Usage};
+#[cfg(feature = "codec")]
+pub use crate::codec::{CryptoStats, PacketCodec, PacketCodecState, PacketCodecStateBuilder};
+#[cfg(any(feature = "lz4", feature = "zlib"))]
+pub use crate::compression::{Compression, CompressionAlgorithm};
+pub use crate::crc::{CrcAlgorithm, FrameCrc};
+pub use crate::crypto::{CounterMode, CryptoError, PacketCrypto};
+pub use crate::crypto::RotatingCrypto;
+#[cfg(feature = "codec")]
+pub use crate::datagram::DatagramCodec;
+pub use crate::diagnostic::Diagnostic;
+pub use crate::direction::Direction;
+#[cfg(feature = "serialize")]
+pub use crate::dispatcher::{DispatchFuture, Middleware, Next, PacketDispatcher};
+pub use crate::error::PacketError;
+pub use crate::header::PacketHeader;
+pub use crate::id::PacketId;
 pub use crate::kind::PacketKind;
+#[cfg(feature = "prometheus")]
+pub use crate::metrics::CodecMetrics;
+pub use crate::obfuscator::Obfuscator;
+#[cfg(feature = "codec")]
+pub use crate::observer::Observer;
 pub use crate::packet::Packet;
+pub use crate::packet_ref::PacketRef;
+#[cfg(feature = "codec")]
+pub use crate::packet_bytes::PacketBytes;
+pub use crate::policy::{EmptyFrames, FramePolicy, Padding};
+#[cfg(feature = "codec")]
+pub use crate::priority::{Priority, PrioritySink, QueueStats};
+pub use crate::reader::PacketReader;
+#[cfg(feature = "serialize")]
+pub use crate::registry::PacketRegistry;
+#[cfg(feature = "codec")]
+pub use crate::shutdown::{Close, GracefulClose};
+pub use crate::trailer::Trailer;
+#[cfg(all(feature = "codec", feature = "serialize"))]
+pub use crate::typed::{TypedFramed, TypedMessage};
+#[cfg(all(feature = "codec", feature = "serialize"))]
+pub use crate::codec::TypedPacketCodec;
+#[cfg(feature = "hmac")]
+pub use crate::integrity::FrameHmac;
 #[cfg(feature = "serialize")]
 pub use crate::serialize::{PacketDecodable, PacketEncodable};
+#[cfg(feature = "signed")]
+pub use crate::signature::FrameSignature;
+pub use crate::validation::ValidationError;
+pub use crate::writer::PacketWriter;
 
+// The framing of tokio-io is deprecated in favor of tokio-codec, which the
+// codec is not built upon, so the modules using it allow deprecated items.
+
+#[cfg(feature = "aead")]
+mod aead;
+#[cfg(feature = "codec")]
+mod batch;
+#[cfg(feature = "codec")]
+mod budget;
 #[cfg(feature = "codec")]
 mod codec;
+#[cfg(any(feature = "lz4", feature = "zlib"))]
+mod compression;
+mod crc;
+#[cfg(feature = "codec")]
+mod datagram;
+mod diagnostic;
+mod direction;
+#[cfg(feature = "serialize")]
+mod dispatcher;
+mod error;
+#[cfg(feature = "tokio_util")]
+mod framing;
+mod header;
+mod id;
+#[cfg(feature = "hmac")]
+mod integrity;
 mod kind;
+#[cfg(feature = "prometheus")]
+mod metrics;
+mod obfuscator;
+#[cfg(feature = "codec")]
+mod observer;
 mod packet;
+#[cfg(feature = "codec")]
+mod packet_bytes;
+mod packet_ref;
+mod policy;
+#[cfg(feature = "codec")]
+mod priority;
+mod reader;
+#[cfg(feature = "serialize")]
+mod registry;
+#[cfg(feature = "codec")]
+#[allow(deprecated)]
+mod shutdown;
+#[cfg(feature = "signed")]
+mod signature;
+mod trailer;
+#[cfg(all(feature = "codec", feature = "serialize"))]
+mod typed;
+mod validation;
+mod writer;
 
+pub mod capture;
+#[cfg(feature = "codec")]
+pub mod client;
+#[cfg(feature = "codec")]
+pub mod conformance;
 pub mod crypto;
+pub mod flow;
+#[cfg(feature = "codec")]
+pub mod loadgen;
+pub mod names;
+#[cfg(feature = "serialize")]
+pub mod packets;
+#[cfg(feature = "repl")]
+pub mod repl;
 #[cfg(feature = "serialize")]
 pub mod serialize;
+#[cfg(feature = "codec")]
+pub mod sniff;
+#[cfg(feature = "template")]
+pub mod template;
+#[cfg(feature = "test_vectors")]
+pub mod test_vectors;
+#[cfg(feature = "codec")]
+#[allow(deprecated)]
+pub mod testing;
+pub mod translate;
+#[cfg(feature = "transport")]
+#[allow(deprecated)]
+pub mod transport;
+#[cfg(feature = "yaml")]
+pub mod yaml;
 
 #[cfg(feature = "serialize")]
 #[doc(hidden)]
 pub use packet_derive::*;
 
+// Allows derived implementations to be used within the crate
+#[cfg(feature = "serialize")]
+extern crate self as muonline_packet;
+
 /// Default XOR cipher extracted from the client.
 pub static XOR_CIPHER: [u8; 32] = [
   0xE7, 0x6D, 0x3A, 0x89, 0xBC, 0xB2, 0x9F, 0x73, 0x23, 0xA8, 0xFE, 0xB6, 0x49, 0x5D, 0x39, 0x5D,
@@ -36,11 +163,16 @@ pub trait PacketType {
   /// Returns any potential subcodes of the message.
   fn subcodes() -> &'static [u8];
 
+  /// Returns the minimum size of the message's packet (including its header),
+  /// verified before it is decoded.
+  fn min_size() -> usize { 0 }
+
+  /// Validates the message's fields, after it has been decoded.
+  fn validate(&self) -> Result<(), ValidationError> { Ok(()) }
+
   /// Returns the unique identifier of the message.
-  fn identifier() -> Vec<u8> {
-    let mut id = vec![Self::CODE];
-    id.extend_from_slice(Self::subcodes());
-    id
+  fn identifier() -> PacketId {
+    PacketId::with_subcodes(Self::CODE, Self::subcodes()).expect("at most three subcodes")
   }
 }
 
@@ -106,6 +238,144 @@ mod tests {
     );
   }
 
+  #[test]
+  fn packet_c1_to_c4() {
+    let mut packet = Packet::new(PacketKind::C1, 0x20);
+    packet.append(&[0xAB; 200]);
+
+    assert_eq!(PacketKind::from_size(packet.data().len(), None), Some(PacketKind::C1));
+    let kind = PacketKind::from_size(packet.data().len(), Some(&crypto::CLIENT));
+    assert_eq!(kind, Some(PacketKind::C4));
+
+    let encoded = packet.to_bytes_ex(None, Some((&crypto::CLIENT, 0)));
+    assert_eq!(encoded[0], 0xC4);
+    assert_eq!(encoded.len(), PacketCrypto::encrypt_len(packet.data().len() + 2) + 3);
+  }
+
+  #[test]
+  fn xor_c1_to_c4() {
+    // The XOR cipher is keyed by the kind received, after the upgrade to C4
+    for &size in &[190, 200, 252] {
+      let mut packet = Packet::new(PacketKind::C1, 0x20);
+      packet.append(&(0..size).map(|value| value as u8).collect::<Vec<_>>());
+
+      let encoded = packet.to_bytes_ex(Some(&XOR_CIPHER), Some((&crypto::CLIENT, 0)));
+      let (decoded, ..) =
+        Packet::from_bytes_ex(&encoded, Some(&XOR_CIPHER), Some(&crypto::CLIENT)).unwrap();
+      assert_eq!(decoded.data(), packet.data());
+    }
+  }
+
+  #[test]
+  fn without_counter() {
+    let legacy = crypto::CLIENT.clone().with_counter_mode(CounterMode::None);
+    let packet = Packet::from_bytes(&[0xC1, 0x0A, 0xF4, 1, 2, 3, 4, 5, 6, 7]).unwrap();
+
+    // Without a counter, the code and data fit in a single block
+    let encrypted = packet.to_bytes_ex(None, Some((&legacy, 7)));
+    assert_eq!(encrypted.len(), PacketCrypto::encrypt_len(8) + 2);
+    assert_eq!(encrypted.len(), packet.encoded_len(Some(&legacy)));
+
+    let (decrypted, _, counter) = Packet::from_bytes_ex(&encrypted, None, Some(&legacy)).unwrap();
+    assert_eq!((decrypted.to_bytes(), counter), (packet.to_bytes(), None));
+
+    // Without a counter, one more byte fits before upgrading to C4
+    let mut packet = Packet::new(PacketKind::C1, 0xF4);
+    packet.append(&[0xAB; 183]);
+    assert_eq!(PacketKind::from_size(183, Some(&crypto::CLIENT)), Some(PacketKind::C4));
+    assert_eq!(PacketKind::from_size(183, Some(&legacy)), Some(PacketKind::C3));
+    assert_eq!(packet.to_bytes_ex(None, Some((&legacy, 0)))[0], 0xC3);
+    assert_eq!(packet.to_bytes_ex(None, Some((&crypto::CLIENT, 0)))[0], 0xC4);
+  }
+
+  #[test]
+  fn encoded_len() {
+    for &size in &[0, 5, 200, 300] {
+      let mut packet = Packet::new(PacketKind::from_size(size, None).unwrap(), 0x20);
+      packet.append(&vec![0xAB; size]);
+
+      let encrypted = packet.to_bytes_ex(Some(&XOR_CIPHER), Some((&crypto::CLIENT, 0)));
+      assert_eq!(packet.encoded_len(Some(&crypto::CLIENT)), encrypted.len());
+      assert_eq!(packet.encoded_len(None), packet.to_bytes().len());
+    }
+  }
+
+  #[test]
+  fn encode_to_slice() {
+    let legacy = crypto::CLIENT.clone().with_counter_mode(CounterMode::None);
+    let mut buffer = [0; 512];
+    for &size in &[0, 5, 6, 200, 300] {
+      let mut packet = Packet::new(PacketKind::from_size(size, None).unwrap(), 0x20);
+      packet.append(&(0..size).map(|value| value as u8).collect::<Vec<_>>());
+
+      for &encryption in &[None, Some((&*crypto::CLIENT, 3)), Some((&legacy, 3))] {
+        let expected = packet.to_bytes_ex(Some(&XOR_CIPHER), encryption);
+        let written = packet.encode_to_slice(&mut buffer, Some(&XOR_CIPHER), encryption).unwrap();
+        assert_eq!(&buffer[..written], &expected[..]);
+
+        let decryption = encryption.map(|(crypto, _)| crypto);
+        let (decoded, size, _) =
+          Packet::from_bytes_ex(&buffer[..written], Some(&XOR_CIPHER), decryption).unwrap();
+        assert_eq!((decoded.data(), size), (packet.data(), written));
+      }
+    }
+
+    let packet = Packet::new(PacketKind::C1, 0x20);
+    assert!(packet.encode_to_slice(&mut buffer[..2], None, None).is_err());
+  }
+
+  #[test]
+  fn packet_reader() {
+    let bytes = [0xC1, 0x0A, 0xF4, 0x03, 0x00, 0x00, 0x61, 0x62, 0x00, 0x63];
+    let packet = Packet::from_bytes(&bytes).unwrap();
+    let mut reader = packet.reader();
+
+    assert_eq!(reader.read_u8().unwrap(), 0x03);
+    assert_eq!(reader.read_u16_le().unwrap(), 0x0000);
+    assert_eq!(reader.read_str_fixed(4).unwrap(), "ab");
+    assert_eq!(reader.remaining(), 0);
+
+    let error = reader.read_u8().unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    assert!(error.to_string().contains("at byte 10"));
+  }
+
+  #[test]
+  fn packet_writer() {
+    let mut packet = Packet::new(PacketKind::C1, 0xF4);
+    packet
+      .writer()
+      .put_u8(0x03)
+      .and_then(|writer| writer.put_u16_le(0x0201))
+      .and_then(|writer| writer.put_str_fixed("ab", 4))
+      .unwrap();
+    assert_eq!(packet.data(), [0x03, 0x01, 0x02, 0x61, 0x62, 0x00, 0x00]);
+
+    let mut writer = packet.writer();
+    assert_eq!(writer.remaining(), 255 - 10);
+    assert!(writer.put_bytes(&[0; 246]).is_err());
+    assert_eq!(packet.len(), 10);
+  }
+
+  #[test]
+  fn packet_lenient() {
+    let bytes = [0xC1, 0x08, 0xF4, 0x03, 0x00];
+    let (packet, diagnostics) = Packet::from_bytes_lenient(&bytes, None, None);
+    assert_eq!(packet.unwrap().data(), [0x03, 0x00]);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].offset(), 5);
+
+    let mut packet = Packet::new(PacketKind::C1, 0xF4);
+    packet.append(&[0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A]);
+    let mut bytes = packet.to_bytes_ex(None, Some((&crypto::CLIENT, 0)));
+    bytes[20] ^= 0xFF;
+
+    let (packet, diagnostics) = Packet::from_bytes_lenient(&bytes, None, Some(&crypto::CLIENT));
+    assert_eq!(packet.unwrap().data(), [0x00, 0x01, 0x02, 0x03, 0x04, 0x05]);
+    assert_eq!(diagnostics[0].offset(), 13);
+    assert!(Packet::from_bytes_lenient(&[0x00], None, None).0.is_none());
+  }
+
   #[test]
   fn packet_c3_to_c1() {
     let bytes = [
@@ -118,5 +388,16 @@ mod tests {
 
     let decoded = packet.to_bytes();
     assert_eq!(decoded, [0xC1, 0x06, 0xF4, 0x03, 0x00, 0x00]);
+
+    let policy = FramePolicy::new().padding(Padding::Skip);
+    let (packet, len, _) =
+      Packet::from_bytes_policy(&bytes, None, Some(&crypto::CLIENT), policy).unwrap();
+    assert_eq!(len, bytes.len());
+    assert!(packet.is_some());
+
+    let policy = policy.empty(EmptyFrames::Skip);
+    let empty = Packet::from_bytes_policy(&[0xC1, 0x03, 0x0E], None, None, policy).unwrap();
+    assert!(empty.0.is_none());
+    assert_eq!(empty.1, 3);
   }
 }
@@ -0,0 +1,311 @@
+//! Generation of packet traffic for load testing servers.
+//!
+//! A generator opens a number of connections, each using a fresh copy of a
+//! codec, and sends a weighted mix of packet templates at a fixed rate.
+//! Packets received in return are decoded and counted, but otherwise ignored.
+//! Connections run on their own threads, using blocking sockets.
+
+use bytes::BytesMut;
+use crate::{Packet, PacketCodec};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio_io::codec::{Decoder, Encoder};
+
+/// Time spent reading responses between sends, when the rate is unlimited.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A load generator builder.
+pub struct LoadGeneratorBuilder {
+  connections: usize,
+  rate: Option<u32>,
+  duration: Duration,
+  templates: Vec<(Packet, u32)>,
+}
+
+impl LoadGeneratorBuilder {
+  /// Constructs a load generator.
+  pub fn build(self) -> LoadGenerator {
+    // Each template is repeated by its weight, and sent in turn
+    let mix = self
+      .templates
+      .into_iter()
+      .flat_map(|(packet, weight)| std::iter::repeat_n(packet, weight as usize))
+      .collect();
+
+    LoadGenerator {
+      connections: self.connections,
+      rate: self.rate,
+      duration: self.duration,
+      mix,
+    }
+  }
+
+  /// Sets the number of concurrent connections (defaults to one).
+  pub fn connections(mut self, connections: usize) -> Self {
+    self.connections = connections;
+    self
+  }
+
+  /// Sets the number of packets sent per second, per connection.
+  ///
+  /// By default, packets are sent as fast as possible.
+  pub fn rate(mut self, rate: u32) -> Self {
+    self.rate = Some(rate);
+    self
+  }
+
+  /// Sets how long traffic is generated for (defaults to ten seconds).
+  pub fn duration(mut self, duration: Duration) -> Self {
+    self.duration = duration;
+    self
+  }
+
+  /// Adds a packet template, sent in proportion to its weight.
+  ///
+  /// Typed packets can be used by converting them with `to_packet`.
+  pub fn template(mut self, packet: Packet, weight: u32) -> Self {
+    self.templates.push((packet, weight));
+    self
+  }
+}
+
+/// A generator of packet traffic.
+#[derive(Debug)]
+pub struct LoadGenerator {
+  connections: usize,
+  rate: Option<u32>,
+  duration: Duration,
+  mix: Vec<Packet>,
+}
+
+impl LoadGenerator {
+  /// Returns a load generator builder.
+  pub fn builder() -> LoadGeneratorBuilder {
+    LoadGeneratorBuilder {
+      connections: 1,
+      rate: None,
+      duration: Duration::from_secs(10),
+      templates: Vec::new(),
+    }
+  }
+
+  /// Generates traffic against a server, until the duration has elapsed.
+  ///
+  /// Each connection uses a clone of the codec, so counters are not shared.
+  /// Failing connections are closed and counted as errors, without stopping
+  /// the others.
+  pub fn run<A: ToSocketAddrs>(&self, address: A, codec: &PacketCodec) -> io::Result<Report> {
+    if self.mix.is_empty() {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "no packet templates"));
+    }
+
+    let address = address
+      .to_socket_addrs()?
+      .next()
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to"))?;
+
+    let start = Instant::now();
+    let deadline = start + self.duration;
+    let reports = thread::scope(|scope| {
+      let workers = (0..self.connections)
+        .map(|index| {
+          let codec = codec.clone();
+          scope.spawn(move || self.connection(index, address, codec, deadline))
+        })
+        .collect::<Vec<_>>();
+
+      workers
+        .into_iter()
+        .map(|worker| worker.join().unwrap_or_default())
+        .collect::<Vec<_>>()
+    });
+
+    let mut report = reports.into_iter().fold(Report::default(), Report::merge);
+    report.elapsed = start.elapsed();
+    Ok(report)
+  }
+
+  /// Drives a single connection, returning its statistics.
+  fn connection(
+    &self,
+    index: usize,
+    address: SocketAddr,
+    mut codec: PacketCodec,
+    deadline: Instant,
+  ) -> Report {
+    let mut report = Report { connections: 1, ..Report::default() };
+    let mut stream = match TcpStream::connect(address) {
+      Ok(stream) => stream,
+      Err(_) => {
+        report.errors += 1;
+        return report;
+      },
+    };
+
+    let interval = self.rate.map_or(POLL_INTERVAL, |rate| {
+      Duration::from_secs(1) / rate.max(1)
+    });
+
+    // Connections start at different templates, to spread the mix
+    let mut templates = self.mix.iter().cycle().skip(index % self.mix.len());
+    let mut output = BytesMut::new();
+    let mut input = BytesMut::new();
+    let mut next_send = Instant::now();
+
+    let result = (|| -> io::Result<()> {
+      while Instant::now() < deadline {
+        if Instant::now() >= next_send {
+          let packet = templates.next().expect("cycle is infinite").clone();
+          codec.encode(packet, &mut output)?;
+          stream.write_all(&output)?;
+          output.clear();
+
+          report.packets_sent += 1;
+          next_send += interval;
+        }
+
+        let wait = next_send
+          .min(deadline)
+          .saturating_duration_since(Instant::now())
+          .max(POLL_INTERVAL);
+        report.packets_received += Self::receive(&mut stream, &mut codec, &mut input, wait)?;
+      }
+      Ok(())
+    })();
+
+    if result.is_err() {
+      report.errors += 1;
+    }
+    report
+  }
+
+  /// Reads and decodes responses for up to a period, returning their count.
+  fn receive(
+    stream: &mut TcpStream,
+    codec: &mut PacketCodec,
+    input: &mut BytesMut,
+    wait: Duration,
+  ) -> io::Result<u64> {
+    let mut buffer = [0; 4096];
+    stream.set_read_timeout(Some(wait))?;
+
+    let size = match stream.read(&mut buffer) {
+      Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+      Ok(size) => size,
+      Err(ref error)
+        if error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut =>
+      {
+        return Ok(0)
+      },
+      Err(error) => return Err(error),
+    };
+
+    input.extend_from_slice(&buffer[..size]);
+    let mut count = 0;
+    while codec.decode(input)?.is_some() {
+      count += 1;
+    }
+    Ok(count)
+  }
+}
+
+/// The statistics of a load test.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+  connections: u64,
+  packets_sent: u64,
+  packets_received: u64,
+  errors: u64,
+  elapsed: Duration,
+}
+
+impl Report {
+  /// Returns the number of connections attempted.
+  pub fn connections(&self) -> u64 { self.connections }
+
+  /// Returns the number of packets sent.
+  pub fn packets_sent(&self) -> u64 { self.packets_sent }
+
+  /// Returns the number of packets received and decoded.
+  pub fn packets_received(&self) -> u64 { self.packets_received }
+
+  /// Returns the number of connections that failed.
+  pub fn errors(&self) -> u64 { self.errors }
+
+  /// Returns how long the test ran for.
+  pub fn elapsed(&self) -> Duration { self.elapsed }
+
+  /// Returns the number of packets sent per second, across all connections.
+  pub fn throughput(&self) -> f64 {
+    let seconds = self.elapsed.as_secs_f64();
+    if seconds > 0.0 {
+      self.packets_sent as f64 / seconds
+    } else {
+      0.0
+    }
+  }
+
+  /// Returns the fraction of connections that failed.
+  pub fn error_rate(&self) -> f64 {
+    if self.connections > 0 {
+      self.errors as f64 / self.connections as f64
+    } else {
+      0.0
+    }
+  }
+
+  /// Combines the statistics of two connections.
+  fn merge(self, other: Report) -> Report {
+    Report {
+      connections: self.connections + other.connections,
+      packets_sent: self.packets_sent + other.packets_sent,
+      packets_received: self.packets_received + other.packets_received,
+      errors: self.errors + other.errors,
+      elapsed: self.elapsed.max(other.elapsed),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{PacketCodecState, PacketKind};
+  use std::net::TcpListener;
+
+  #[test]
+  fn echo_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+      for stream in listener.incoming().take(2) {
+        let mut stream = stream.unwrap();
+        thread::spawn(move || {
+          let mut reader = stream.try_clone().unwrap();
+          let _ = io::copy(&mut reader, &mut stream);
+        });
+      }
+    });
+
+    let mut ping = Packet::new(PacketKind::C1, 0x0E);
+    ping.append(&[0x00, 0x01]);
+
+    let generator = LoadGenerator::builder()
+      .connections(2)
+      .rate(200)
+      .duration(Duration::from_millis(100))
+      .template(ping, 3)
+      .template(Packet::new(PacketKind::C2, 0xF3), 1)
+      .build();
+    let codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let report = generator.run(address, &codec).unwrap();
+    server.join().unwrap();
+
+    assert_eq!(report.connections(), 2);
+    assert_eq!(report.errors(), 0);
+    assert!(report.packets_sent() > 2);
+    assert!(report.packets_received() > 0);
+    assert!(report.throughput() > 0.0);
+  }
+}
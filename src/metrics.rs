@@ -0,0 +1,43 @@
+use crate::{Direction, Packet, PacketId};
+use prometheus::{IntCounterVec, Opts, Registry};
+
+/// Prometheus metrics for packet codecs.
+///
+/// The metrics are labeled by direction (`inbound` or `outbound`) and packet
+/// code. Instances are cheap to clone, and all clones share the same
+/// counters, so one instance can be shared between all codecs.
+#[derive(Clone, Debug)]
+pub struct CodecMetrics {
+  packets: IntCounterVec,
+  bytes: IntCounterVec,
+}
+
+impl CodecMetrics {
+  /// Creates codec metrics and registers them with a registry.
+  pub fn register(registry: &Registry) -> prometheus::Result<Self> {
+    let labels = &["direction", "code"];
+    let metrics = CodecMetrics {
+      packets: IntCounterVec::new(
+        Opts::new("muonline_packets_total", "Number of packets processed"),
+        labels,
+      )?,
+      bytes: IntCounterVec::new(
+        Opts::new("muonline_packet_bytes_total", "Number of bytes processed"),
+        labels,
+      )?,
+    };
+
+    registry.register(Box::new(metrics.packets.clone()))?;
+    registry.register(Box::new(metrics.bytes.clone()))?;
+    Ok(metrics)
+  }
+
+  /// Records a packet and the number of bytes it spanned.
+  pub(crate) fn record(&self, direction: Direction, packet: &Packet, size: usize) {
+    let code = PacketId::new(packet.code()).to_string();
+    let labels = &[direction.as_str(), code.as_str()];
+
+    self.packets.with_label_values(labels).inc();
+    self.bytes.with_label_values(labels).inc_by(size as u64);
+  }
+}
@@ -0,0 +1,262 @@
+//! Human readable packet names, imported from community packet lists.
+//!
+//! Lists are either CSV, with hexadecimal codes followed by a name:
+//!
+//! ```text
+//! code,subcode,name
+//! F1,00,JoinResult
+//! F3,,CharacterList
+//! F3,10,*,01,InventoryItem
+//! ```
+//!
+//! ... or C/C++ headers, with one code per define:
+//!
+//! ```text
+//! #define PROTOCOL_CHARACTER_LIST 0xF300
+//! ```
+//!
+//! Header values wider than a byte are split into a code and its subcodes. In
+//! CSV lists, a `*` matches any subcode (e.g for families dispatching on a
+//! second subcode level).
+
+use crate::{Packet, PacketId};
+use std::collections::HashMap;
+use std::io;
+
+/// A map of packet codes (including subcodes) to names.
+///
+/// Entries with wildcards, or with more subcodes than a `PacketId` holds, are
+/// matched as patterns.
+#[derive(Clone, Debug, Default)]
+pub struct PacketNames {
+  names: HashMap<PacketId, String>,
+  patterns: Vec<(Vec<Option<u8>>, String)>,
+}
+
+impl PacketNames {
+  /// Creates an empty name map.
+  pub fn new() -> Self { Self::default() }
+
+  /// Parses a CSV packet list.
+  ///
+  /// A leading header row is skipped, as are empty lines and `#` comments.
+  pub fn from_csv(csv: &str) -> Result<Self, io::Error> {
+    let mut names = Self::new();
+
+    for (entry, (index, line)) in Self::lines(csv).enumerate() {
+      let columns = line.split(',').map(str::trim).collect::<Vec<_>>();
+      let (name, codes) = columns.split_last().unwrap();
+
+      let codes = codes
+        .iter()
+        .filter(|code| !code.is_empty())
+        .map(|&code| if code == "*" { Some(None) } else { parse_byte(code).map(Some) })
+        .collect::<Option<Vec<_>>>();
+
+      match codes {
+        Some(ref codes) if !codes.is_empty() && !name.is_empty() => {
+          names.insert_pattern(codes, *name)
+        },
+        // The first row may be column titles
+        None if entry == 0 => continue,
+        _ => return Err(invalid_line(index, line)),
+      }
+    }
+
+    Ok(names)
+  }
+
+  /// Parses `#define NAME 0xCODE` lines from a C/C++ header.
+  ///
+  /// Lines other than defines with a hexadecimal value are ignored.
+  pub fn from_header(header: &str) -> Self {
+    let mut names = Self::new();
+
+    for (_, line) in Self::lines(header) {
+      let mut tokens = line.split_whitespace();
+      if tokens.next() != Some("#define") {
+        continue;
+      }
+
+      let name = tokens.next();
+      let codes = tokens.next().and_then(parse_bytes);
+
+      if let (Some(name), Some(codes)) = (name, codes) {
+        names.insert_pattern(&codes.into_iter().map(Some).collect::<Vec<_>>(), name);
+      }
+    }
+
+    names
+  }
+
+  /// Adds a name for a packet identifier.
+  pub fn insert<S: Into<String>>(&mut self, id: PacketId, name: S) {
+    self.names.insert(id, name.into());
+  }
+
+  /// Adds a name for a code, followed by any subcodes, where `None` matches
+  /// any byte.
+  pub fn insert_pattern<S: Into<String>>(&mut self, codes: &[Option<u8>], name: S) {
+    let id = codes
+      .iter()
+      .cloned()
+      .collect::<Option<Vec<_>>>()
+      .and_then(|codes| PacketId::from_bytes(&codes));
+
+    match id {
+      Some(id) => self.insert(id, name),
+      None => {
+        self.patterns.retain(|(pattern, _)| pattern.as_slice() != codes);
+        self.patterns.push((codes.to_vec(), name.into()));
+      },
+    }
+  }
+
+  /// Merges another map into this one, preferring the other's names.
+  pub fn extend(&mut self, other: PacketNames) {
+    self.names.extend(other.names);
+    for (pattern, name) in other.patterns {
+      self.insert_pattern(&pattern, name);
+    }
+  }
+
+  /// Returns the name of a packet, using the most specific matching entry.
+  ///
+  /// The entry matching the most bytes is used. If several match as many, an
+  /// exact entry is preferred over one with wildcards, and otherwise the one
+  /// with the fewest wildcards.
+  pub fn get(&self, packet: &Packet) -> Option<&str> {
+    let codes = std::iter::once(packet.code())
+      .chain(packet.data().iter().cloned())
+      .collect::<Vec<_>>();
+
+    let exact = (1..=codes.len())
+      .rev()
+      .filter_map(|length| PacketId::from_bytes(&codes[..length]))
+      .find_map(|id| self.names.get(&id).map(|name| (id.as_bytes().len(), 0, name)));
+
+    let pattern = self
+      .patterns
+      .iter()
+      .filter(|(pattern, _)| {
+        pattern.len() <= codes.len()
+          && pattern.iter().zip(&codes).all(|(byte, code)| byte.is_none_or(|byte| byte == *code))
+      })
+      .map(|(pattern, name)| (pattern.len(), pattern.iter().filter(|b| b.is_none()).count(), name))
+      .min_by_key(|&(length, wildcards, _)| (std::cmp::Reverse(length), wildcards));
+
+    exact
+      .into_iter()
+      .chain(pattern)
+      .min_by_key(|&(length, wildcards, _)| (std::cmp::Reverse(length), wildcards))
+      .map(|(_, _, name)| name.as_str())
+  }
+
+  /// Returns the code, followed by any subcodes, of a name.
+  ///
+  /// Names of entries matched as patterns are not included.
+  pub fn codes(&self, name: &str) -> Option<&[u8]> {
+    self
+      .names
+      .iter()
+      .find(|(_, entry)| entry.as_str() == name)
+      .map(|(id, _)| id.as_bytes())
+  }
+
+  /// Returns the number of names.
+  pub fn len(&self) -> usize { self.names.len() + self.patterns.len() }
+
+  /// Returns whether there are any names.
+  pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+  /// Returns the trimmed, non-empty and uncommented lines of a list, along
+  /// with their index.
+  fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text
+      .lines()
+      .map(|line| line.split("//").next().unwrap().trim())
+      .enumerate()
+      .filter(|(_, line)| !line.is_empty() && !line.starts_with("# ") && *line != "#")
+  }
+}
+
+/// Parses a single hexadecimal byte, with an optional `0x` prefix.
+pub(crate) fn parse_byte(text: &str) -> Option<u8> {
+  let text = text.trim().trim_start_matches("0x").trim_start_matches("0X");
+  u8::from_str_radix(text, 16).ok()
+}
+
+/// Parses a hexadecimal value into its big endian bytes.
+fn parse_bytes(text: &str) -> Option<Vec<u8>> {
+  let text = text.trim_start_matches("0x").trim_start_matches("0X");
+  if text.is_empty() || text.len() > 16 || !text.chars().all(|c| c.is_ascii_hexdigit()) {
+    return None;
+  }
+
+  let value = u64::from_str_radix(text, 16).ok()?;
+  let size = text.len().div_ceil(2);
+  Some(value.to_be_bytes()[8 - size..].to_vec())
+}
+
+fn invalid_line(index: usize, line: &str) -> io::Error {
+  let message = format!("invalid packet list entry on line {}: '{}'", index + 1, line);
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PacketKind;
+
+  #[test]
+  fn longest_prefix() {
+    let csv = "code,subcode,name\nF3,,Character\nF3,00,List\n";
+    let mut names = PacketNames::from_csv(csv).unwrap();
+    names.extend(PacketNames::from_header("#define PMSG_JOIN_RESULT 0xF100 // join\n"));
+    assert_eq!(names.len(), 3);
+
+    let mut packet = Packet::new(PacketKind::C1, 0xF3);
+    packet.append(&[0x00, 0x05]);
+    assert_eq!(names.get(&packet), Some("List"));
+
+    let packet = Packet::new(PacketKind::C1, 0xF3);
+    assert_eq!(names.get(&packet), Some("Character"));
+
+    let mut packet = Packet::new(PacketKind::C1, 0xF1);
+    packet.append(&[0x00]);
+    assert_eq!(names.get(&packet), Some("PMSG_JOIN_RESULT"));
+    assert_eq!(names.codes("List"), Some(&[0xF3, 0x00][..]));
+    assert!(PacketNames::from_csv("F3,Character\nzz,Invalid\n").is_err());
+
+    // Codes with more subcodes than an identifier holds are patterns
+    let names = PacketNames::from_header("#define PROTOCOL_ITEM 0xF31002010A\n");
+    let mut packet = Packet::new(PacketKind::C1, 0xF3);
+    packet.append(&[0x10, 0x02, 0x01, 0x0A, 0x00]);
+    assert_eq!(names.get(&packet), Some("PROTOCOL_ITEM"));
+    assert_eq!(names.codes("PROTOCOL_ITEM"), None);
+  }
+
+  #[test]
+  fn line_numbers() {
+    let csv = "# Community list\ncode,name\n\nF3,Character\n// Unknown\nzz,Invalid\n";
+    let error = PacketNames::from_csv(csv).unwrap_err();
+    assert_eq!(error.to_string(), "invalid packet list entry on line 6: 'zz,Invalid'");
+  }
+
+  #[test]
+  fn wildcards() {
+    let csv = "F3,10,Inventory\nF3,10,*,01,InventoryItem\nF3,*,*,01,Item\nF3,10,02,01,Equipment\n";
+    let names = PacketNames::from_csv(csv).unwrap();
+    let name = |codes: &[u8]| {
+      let mut packet = Packet::new(PacketKind::C1, codes[0]);
+      packet.append(&codes[1..]);
+      names.get(&packet).map(str::to_string)
+    };
+
+    assert_eq!(name(&[0xF3, 0x10, 0x05]).as_deref(), Some("Inventory"));
+    assert_eq!(name(&[0xF3, 0x10, 0x05, 0x01]).as_deref(), Some("InventoryItem"));
+    assert_eq!(name(&[0xF3, 0x10, 0x02, 0x01]).as_deref(), Some("Equipment"));
+    assert_eq!(name(&[0xF3, 0x11, 0x05, 0x01]).as_deref(), Some("Item"));
+    assert_eq!(name(&[0xF3, 0x11, 0x05]), None);
+  }
+}
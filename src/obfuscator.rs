@@ -0,0 +1,15 @@
+use std::fmt;
+
+/// An interface for server specific packet obfuscation.
+///
+/// Obfuscation is applied to a frame as the final step when encoding, and
+/// reverted as the first step when decoding. It covers everything after the
+/// frame's header (i.e its kind and size), which is left intact so frames
+/// can still be delimited. The data's length must be preserved.
+pub trait Obfuscator: fmt::Debug + Send + Sync {
+  /// Obfuscates an outgoing frame's data.
+  fn obfuscate(&self, data: &mut [u8]);
+
+  /// Reverts the obfuscation of an incoming frame's data.
+  fn deobfuscate(&self, data: &mut [u8]);
+}
@@ -0,0 +1,26 @@
+use crate::{CryptoError, Packet};
+use std::fmt;
+
+/// An interface for observing the events of a codec.
+///
+/// This allows metrics, logging, anti-cheat and recording to subscribe to a
+/// connection, without any bespoke hooks. All callbacks default to doing
+/// nothing, so only the relevant ones need to be implemented. They are
+/// invoked synchronously, so they should not block.
+pub trait Observer: fmt::Debug + Send + Sync {
+  /// Invoked for each packet received, with the number of bytes it spanned.
+  fn frame_received(&self, _packet: &Packet, _size: usize) {}
+
+  /// Invoked for each packet sent, with the number of bytes it spans.
+  fn frame_sent(&self, _packet: &Packet, _size: usize) {}
+
+  /// Invoked for each frame which failed to decrypt, other than by a counter
+  /// mismatch.
+  fn decrypt_failed(&self, _error: &CryptoError) {}
+
+  /// Invoked for each packet with an unexpected encryption counter.
+  fn counter_mismatch(&self, _expected: u8, _received: u8) {}
+
+  /// Invoked for each frame or packet rejected due to its size.
+  fn oversize_rejected(&self, _size: usize, _limit: usize) {}
+}
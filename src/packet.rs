@@ -1,7 +1,16 @@
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use crate::{PacketCrypto, PacketKind};
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "serialize")]
+use crate::serialize::GroupedBuilder;
+use crate::{Diagnostic, EmptyFrames, FramePolicy, PacketCrypto, PacketError, PacketKind};
+use crate::PacketHeader;
+use crate::PacketReader;
+use crate::PacketWriter;
+use smallvec::SmallVec;
 use std::io;
 
+/// Data size stored inline, without allocating (covers most C1 packets).
+const INLINE_SIZE: usize = 32;
+
 /// Packet's with this code never use an XOR cipher.
 const XOR_SKIP_CODE: u8 = 0xF4;
 
@@ -10,7 +19,7 @@ const XOR_SKIP_CODE: u8 = 0xF4;
 pub struct Packet {
   kind: PacketKind,
   code: u8,
-  data: Vec<u8>,
+  data: SmallVec<[u8; INLINE_SIZE]>,
 }
 
 impl Packet {
@@ -19,7 +28,7 @@ impl Packet {
     Packet {
       kind: kind.decrypted(),
       code,
-      data: Vec::new(),
+      data: SmallVec::new(),
     }
   }
 
@@ -28,40 +37,123 @@ impl Packet {
     Self::from_bytes_ex(bytes, None, None).map(|(packet, ..)| packet)
   }
 
+  /// Parses the header of a frame, without decrypting anything.
+  ///
+  /// Returns `None` if the header itself is incomplete.
+  pub fn peek_header(bytes: &[u8]) -> io::Result<Option<PacketHeader>> {
+    PacketHeader::peek(bytes)
+  }
+
   /// Constructs a packet from an array of potentially encrypted bytes.
+  ///
+  /// Empty packets (i.e only a code) are decoded as such, and any bytes
+  /// following the packet are left as-is.
   pub fn from_bytes_ex(
     bytes: &[u8],
     cipher: Option<&[u8]>,
     decryption: Option<&PacketCrypto>,
   ) -> Result<(Packet, usize, Option<u8>), io::Error> {
-    #[allow(unused_assignments)]
-    let mut buffer = Vec::new();
+    Self::from_bytes_buffered(bytes, cipher, decryption, &mut Vec::new())
+  }
+
+  /// Constructs a packet from a datagram, which must contain exactly one
+  /// packet.
+  ///
+  /// Datagrams are untrusted, so the header is validated before the packet
+  /// is parsed.
+  #[cfg(any(feature = "codec", feature = "serialize"))]
+  pub(crate) fn from_datagram(datagram: &[u8], cipher: Option<&[u8]>) -> io::Result<Packet> {
+    match PacketHeader::peek(datagram)? {
+      Some(header) if header.size() == datagram.len() => (),
+      Some(_) => {
+        let message = "datagram size differs from the packet's";
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+      },
+      None => return Err(PacketError::InvalidSize(datagram.len()).into()),
+    }
+
+    Self::from_bytes_ex(datagram, cipher, None).map(|(packet, ..)| packet)
+  }
+
+  /// Constructs a packet from an array of potentially encrypted bytes,
+  /// handling irregular frames according to a policy.
+  ///
+  /// Returns no packet if the frame is empty and skipped. The number of
+  /// bytes read includes any padding skipped, before and after the frame.
+  pub fn from_bytes_policy(
+    bytes: &[u8],
+    cipher: Option<&[u8]>,
+    decryption: Option<&PacketCrypto>,
+    policy: FramePolicy,
+  ) -> Result<(Option<Packet>, usize, Option<u8>), io::Error> {
+    let leading = policy.skipped_padding(bytes);
+    let (packet, size, counter) = Self::from_bytes_ex(&bytes[leading..], cipher, decryption)?;
+    let size = leading + size;
+    let size = size + policy.skipped_padding(&bytes[size..]);
+
+    if !packet.data().is_empty() {
+      return Ok((Some(packet), size, counter));
+    }
+
+    match policy.empty_frames() {
+      EmptyFrames::Surface => Ok((Some(packet), size, counter)),
+      EmptyFrames::Skip => Ok((None, size, counter)),
+      EmptyFrames::Reject => {
+        let diagnostic = Diagnostic::from_error(leading, PacketError::Empty);
+        let diagnostic = diagnostic.in_packet(packet.kind(), &[packet.code()]);
+        Err(io::Error::new(io::ErrorKind::InvalidData, diagnostic))
+      },
+    }
+  }
+
+  /// Constructs a packet from an array of potentially encrypted bytes,
+  /// decrypting into a reusable buffer.
+  pub(crate) fn from_bytes_buffered(
+    bytes: &[u8],
+    cipher: Option<&[u8]>,
+    decryption: Option<&PacketCrypto>,
+    buffer: &mut Vec<u8>,
+  ) -> Result<(Packet, usize, Option<u8>), io::Error> {
     let mut reader = io::Cursor::new(bytes);
 
     // The first byte is always the type of packet
-    let kind = PacketKind::from_byte(reader.read_u8()?)
-      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a packet"))?;
+    let byte = reader.read_u8()?;
+    let kind = PacketKind::from_byte(byte).ok_or_else(|| {
+      let diagnostic = Diagnostic::from_error(0, PacketError::UnknownKind(byte));
+      io::Error::new(io::ErrorKind::InvalidData, diagnostic)
+    })?;
 
     // ... followed by the the total package size
     let size = reader.read_uint::<BigEndian>(kind.bytes())? as usize;
 
+    if size < kind.offset() {
+      let error = PacketError::InvalidSize(size);
+      let diagnostic = Diagnostic::from_error(1, error).in_packet(kind, &[]);
+      return Err(io::Error::new(error.kind(), diagnostic));
+    }
+
     if bytes.len() < size {
-      return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "missing data"));
+      let error = PacketError::Incomplete {
+        received: bytes.len(),
+        size,
+      };
+      let diagnostic = Diagnostic::from_error(bytes.len(), error).in_packet(kind, &[]);
+      return Err(io::Error::new(error.kind(), diagnostic));
     }
 
     let (size, original_size, crypto_count) = if kind.is_encrypted() {
       if let Some(decryption) = decryption {
-        buffer = decryption.decrypt(&reader.into_inner()[kind.offset()..size])?;
-        reader = io::Cursor::new(&buffer);
+        buffer.clear();
+        decryption.decrypt_into(&reader.into_inner()[kind.offset()..size], buffer)?;
+        reader = io::Cursor::new(buffer);
 
         // This must be extracted before the packet is parsed
-        let crypto_count = reader.read_u8()?;
-        (buffer.len(), size, Some(crypto_count))
+        let crypto_count = if decryption.has_counter() { Some(reader.read_u8()?) } else { None };
+        (buffer.len(), size, crypto_count)
       } else {
-        return Err(io::Error::new(
-          io::ErrorKind::Other,
-          "missing decryption for packet",
-        ));
+        let error = PacketError::MissingDecryption;
+        let diagnostic = Diagnostic::from_error(0, error).in_packet(kind, &[]);
+        return Err(io::Error::new(error.kind(), diagnostic));
       }
     } else {
       (size, size, None)
@@ -89,6 +181,91 @@ impl Packet {
     Ok((packet, original_size, crypto_count))
   }
 
+  /// Constructs a packet from potentially malformed bytes.
+  ///
+  /// Instead of failing, as much of the packet as possible is recovered, and
+  /// any problems are described by the returned diagnostics. Truncated
+  /// packets keep the data received, and encrypted packets keep the blocks
+  /// before the first invalid one. The encryption counter is discarded.
+  pub fn from_bytes_lenient(
+    bytes: &[u8],
+    cipher: Option<&[u8]>,
+    decryption: Option<&PacketCrypto>,
+  ) -> (Option<Packet>, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+
+    let kind = match bytes.first().and_then(|&byte| PacketKind::from_byte(byte)) {
+      Some(kind) => kind,
+      None => {
+        diagnostics.push(Diagnostic::new(0, "not a packet"));
+        return (None, diagnostics);
+      },
+    };
+
+    let header = 1 + kind.bytes();
+    if bytes.len() < header {
+      diagnostics.push(Diagnostic::new(bytes.len(), "missing packet size"));
+      return (None, diagnostics);
+    }
+
+    let mut size = BigEndian::read_uint(&bytes[1..header], kind.bytes()) as usize;
+    if size > bytes.len() {
+      let message = format!("truncated packet, {} of {} bytes", bytes.len(), size);
+      diagnostics.push(Diagnostic::new(bytes.len(), message));
+      size = bytes.len();
+    } else if size < header {
+      let message = format!("invalid packet size {}", size);
+      diagnostics.push(Diagnostic::new(1, message));
+      size = bytes.len();
+    }
+
+    let mut content = bytes[header..size].to_vec();
+    if kind.is_encrypted() {
+      let decryption = match decryption {
+        Some(decryption) => decryption,
+        None => {
+          diagnostics.push(Diagnostic::new(header, "missing decryption for packet"));
+          return (None, diagnostics);
+        },
+      };
+
+      let block_size = PacketCrypto::encrypt_len(1);
+      let mut decrypted = Vec::with_capacity(PacketCrypto::decrypt_max_len(content.len()));
+
+      for (index, block) in decryption.decrypt_blocks(&content).enumerate() {
+        match block {
+          Ok((block, size)) => decrypted.extend_from_slice(&block[..size]),
+          Err(error) => {
+            let offset = header + index * block_size;
+            diagnostics.push(Diagnostic::new(offset, error.to_string()));
+            break;
+          },
+        }
+      }
+
+      // Skip the encryption counter
+      let counter = usize::from(decryption.has_counter());
+      content = decrypted.split_off(decrypted.len().min(counter));
+    }
+
+    if content.is_empty() {
+      diagnostics.push(Diagnostic::new(size, "missing packet code"));
+      return (None, diagnostics);
+    }
+
+    let mut packet = Packet::new(kind.decrypted(), content[0]);
+    packet.append(&content[1..]);
+
+    if packet.code() != XOR_SKIP_CODE {
+      if let Some(cipher) = cipher {
+        let iter = 0..packet.data.len();
+        Self::xorcrypt(cipher, packet.kind(), packet.code(), &mut packet.data, iter.rev())
+      }
+    }
+
+    (Some(packet), diagnostics)
+  }
+
   /// Appends a slice to the internal data.
   pub fn append(&mut self, slice: &[u8]) {
     self.data.extend_from_slice(slice);
@@ -119,6 +296,25 @@ impl Packet {
     self.data.as_ref()
   }
 
+  /// Returns the packet's code, followed by its data.
+  pub(crate) fn content(&self) -> Vec<u8> {
+    let mut content = vec![self.code()];
+    content.extend_from_slice(self.data());
+    content
+  }
+
+  /// Returns a reader for the packet's data.
+  pub fn reader(&self) -> PacketReader<'_> { PacketReader::new(self) }
+
+  /// Returns a writer for appending to the packet's data.
+  pub fn writer(&mut self) -> PacketWriter<'_> { PacketWriter::new(self) }
+
+  /// Returns a builder for a C2 packet with a counted list of records.
+  #[cfg(feature = "serialize")]
+  pub fn grouped_builder(code: u8, subcode: u8) -> GroupedBuilder {
+    GroupedBuilder::new(code, subcode)
+  }
+
   /// Converts a packet to raw bytes.
   pub fn to_bytes(&self) -> Vec<u8> {
     self.to_bytes_ex(None, None)
@@ -134,9 +330,11 @@ impl Packet {
 
     let mut bytes = Vec::with_capacity(self.len());
 
-    if let Some((_, crypto_counter)) = encryption {
+    if let Some((crypto, crypto_counter)) = encryption {
       // The encryption counter, validated by the client
-      bytes.push(crypto_counter);
+      if crypto.has_counter() {
+        bytes.push(crypto_counter);
+      }
     } else {
       // The packet kind and its size
       bytes.push(self.kind() as u8);
@@ -149,20 +347,28 @@ impl Packet {
     let offset = bytes.len();
     bytes.extend_from_slice(self.data());
 
+    let mut kind = self.kind();
+    if encryption.is_some() {
+      kind = kind.encrypted();
+
+      // Upgrade C3 → C4 when the encryption's expansion requires it
+      if PacketCrypto::encrypt_len(bytes.len()) + kind.offset() > kind.max_size() {
+        kind = PacketKind::C4;
+      }
+    }
+
     if self.code() != XOR_SKIP_CODE {
       if let Some(cipher) = cipher {
-        // Encrypts the data using an XOR cipher.
+        // Encrypts the data using an XOR cipher, keyed by the kind the
+        // receiver decodes (i.e C2 if upgraded to C4)
         let iter = 0..self.data.len();
-        Self::xorcrypt(cipher, self.kind(), self.code(), &mut bytes[offset..], iter);
+        Self::xorcrypt(cipher, kind.decrypted(), self.code(), &mut bytes[offset..], iter);
       }
     }
 
     if let Some((crypto, _)) = encryption {
       let encrypted = crypto.encrypt(&bytes);
-      let kind = self.kind().encrypted();
       let size = encrypted.len() + kind.offset();
-
-      // TODO: Upgrade C3 → C4 when possible
       assert!(size <= kind.max_size());
 
       bytes.clear();
@@ -176,6 +382,102 @@ impl Packet {
     bytes
   }
 
+  /// Converts a packet to raw bytes with a specific encryption, writing them
+  /// into a buffer instead of allocating.
+  ///
+  /// Returns the number of bytes written, which is always `encoded_len`. The
+  /// buffer is left partially written if it is too small.
+  pub fn encode_to_slice(
+    &self,
+    output: &mut [u8],
+    cipher: Option<&[u8]>,
+    encryption: Option<(&PacketCrypto, u8)>,
+  ) -> Result<usize, io::Error> {
+    let counter = encryption.is_some_and(|(crypto, _)| crypto.has_counter());
+    let size = self.encoded_size(encryption.is_some(), counter);
+    if self.len() > self.kind().max_size() || size > PacketKind::C4.max_size() {
+      return Err(PacketError::TooLarge.into());
+    }
+
+    if output.len() < size {
+      return Err(PacketError::BufferTooSmall {
+        size: output.len(),
+        required: size,
+      }
+      .into());
+    }
+
+    let output = &mut output[..size];
+    let mut kind = self.kind();
+    if encryption.is_some() {
+      kind = if size > kind.encrypted().max_size() { PacketKind::C4 } else { kind.encrypted() };
+    }
+
+    output[0] = kind as u8;
+    BigEndian::write_uint(&mut output[1..], size as u64, kind.bytes());
+
+    // Unencrypted content is written as-is, but encrypted content is written
+    // to the end of the buffer and encrypted, block by block, from its start.
+    // Since each block expands, it never overwrites content not yet read.
+    let plain = self.data.len() + if counter { 2 } else { 1 };
+    let start = size - plain;
+    if let (Some((_, crypto_counter)), true) = (encryption, counter) {
+      output[start] = crypto_counter;
+    }
+
+    output[size - self.data.len() - 1] = self.code();
+    output[size - self.data.len()..].copy_from_slice(self.data());
+
+    if self.code() != XOR_SKIP_CODE {
+      if let Some(cipher) = cipher {
+        let iter = 0..self.data.len();
+        let data = &mut output[size - self.data.len()..];
+        Self::xorcrypt(cipher, kind.decrypted(), self.code(), data, iter);
+      }
+    }
+
+    if let Some((crypto, _)) = encryption {
+      let header = kind.offset();
+      let block_size = PacketCrypto::encrypt_len(1);
+
+      for (index, offset) in (0..plain).step_by(8).enumerate() {
+        let mut block = [0; 8];
+        let length = (plain - offset).min(8);
+        block[..length].copy_from_slice(&output[start + offset..][..length]);
+        let encrypted = &mut output[header + index * block_size..][..block_size];
+        crypto.encrypt_block(&block[..length], encrypted);
+      }
+    }
+
+    Ok(size)
+  }
+
+  /// Returns the size of the packet once encoded, with or without
+  /// encryption, without encoding it.
+  ///
+  /// This matches the length returned by `to_bytes_ex` with the same keys,
+  /// including the counter of their `CounterMode`. An XOR cipher does not
+  /// affect the size.
+  pub fn encoded_len(&self, crypto: Option<&PacketCrypto>) -> usize {
+    self.encoded_size(crypto.is_some(), crypto.is_some_and(PacketCrypto::has_counter))
+  }
+
+  /// Returns the size of the packet once encoded, with or without encryption
+  /// and its counter.
+  fn encoded_size(&self, encrypted: bool, counter: bool) -> usize {
+    if !encrypted {
+      return self.len();
+    }
+
+    // The encryption counter and code are encrypted along with the data
+    let size = PacketCrypto::encrypt_len(self.data.len() + 1 + usize::from(counter));
+    let mut kind = self.kind().encrypted();
+    if size + kind.offset() > kind.max_size() {
+      kind = PacketKind::C4;
+    }
+    size + kind.offset()
+  }
+
   /// Toggles the encryption of the packet.
   fn xorcrypt<T: Iterator<Item = usize>>(
     cipher: &[u8],
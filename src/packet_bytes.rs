@@ -0,0 +1,102 @@
+use bytes::Bytes;
+use crate::header::PacketHeader;
+use crate::{Packet, PacketError, PacketKind};
+use std::io;
+
+/// An unencrypted packet, referencing its frame instead of a copy.
+///
+/// The frame is a shared view of the buffer it was received in, so it can
+/// be inspected and forwarded (e.g by a proxy) without copying its data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PacketBytes {
+  frame: Bytes,
+  offset: usize,
+}
+
+impl PacketBytes {
+  /// Creates a packet from an unencrypted frame (i.e C1 or C2).
+  pub fn from_frame(frame: Bytes) -> io::Result<Self> {
+    let header = PacketHeader::peek(&frame)?.ok_or(PacketError::Incomplete {
+      received: frame.len(),
+      size: PacketKind::C1.offset(),
+    })?;
+
+    if header.kind().is_encrypted() {
+      return Err(PacketError::MissingDecryption.into());
+    }
+
+    if frame.len() < header.size() {
+      let error = PacketError::Incomplete {
+        received: frame.len(),
+        size: header.size(),
+      };
+      return Err(error.into());
+    }
+
+    if header.size() <= header.len() || frame.len() > header.size() {
+      return Err(PacketError::InvalidSize(header.size()).into());
+    }
+
+    Ok(PacketBytes {
+      frame,
+      offset: header.len(),
+    })
+  }
+
+  /// Returns the packet's kind (C1 or C2 only).
+  pub fn kind(&self) -> PacketKind {
+    PacketKind::from_byte(self.frame[0]).expect("frame has a valid kind")
+  }
+
+  /// Returns the packet's code designation.
+  pub fn code(&self) -> u8 { self.frame[self.offset] }
+
+  /// Returns the length of the entire packet.
+  pub fn len(&self) -> usize { self.frame.len() }
+
+  /// Returns whether the packet is empty or not.
+  pub fn is_empty(&self) -> bool { self.frame.is_empty() }
+
+  /// Returns the content of the package.
+  pub fn data(&self) -> &[u8] { &self.frame[self.offset + 1..] }
+
+  /// Returns the packet's frame, as received.
+  pub fn frame(&self) -> &Bytes { &self.frame }
+
+  /// Copies the packet into an owned packet.
+  pub fn to_packet(&self) -> Packet {
+    let mut packet = Packet::new(self.kind(), self.code());
+    packet.append(self.data());
+    packet
+  }
+}
+
+impl From<Packet> for PacketBytes {
+  fn from(packet: Packet) -> Self {
+    PacketBytes {
+      offset: packet.kind().offset() - 1,
+      frame: Bytes::from(packet.to_bytes()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn from_frame() {
+    // Frames larger than the inline capacity of `Bytes` are shared
+    let mut frame = vec![0xC2, 0x00, 0x40, 0xF3];
+    frame.resize(0x40, 0x2A);
+    let frame = Bytes::from(frame);
+    let packet = PacketBytes::from_frame(frame.clone()).unwrap();
+    assert_eq!((packet.kind(), packet.code()), (PacketKind::C2, 0xF3));
+    assert_eq!(packet.data(), &frame[4..]);
+    assert_eq!(packet.frame().as_ptr(), frame.as_ptr());
+    assert_eq!(PacketBytes::from(packet.to_packet()), packet);
+
+    assert!(PacketBytes::from_frame(Bytes::from(&[0xC1, 0x04, 0xF3][..])).is_err());
+    assert!(PacketBytes::from_frame(Bytes::from(&[0xC3, 0x03, 0x00][..])).is_err());
+  }
+}
@@ -0,0 +1,102 @@
+use byteorder::{BigEndian, ByteOrder};
+use crate::{Packet, PacketError, PacketKind};
+use std::io;
+
+/// A view of an unencrypted packet, parsed in place.
+///
+/// Unlike `Packet::from_bytes`, nothing is copied, so traffic can be
+/// inspected (e.g by filters and loggers) without allocating. The data is
+/// as on the wire, so any XOR cipher is left as-is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PacketRef<'a> {
+  frame: &'a [u8],
+  offset: usize,
+}
+
+impl<'a> PacketRef<'a> {
+  /// Parses the packet at the start of a buffer (C1 or C2 only).
+  ///
+  /// Any bytes following the packet are ignored.
+  pub fn parse(bytes: &'a [u8]) -> io::Result<Self> {
+    let kind = match bytes.first() {
+      Some(&byte) => PacketKind::from_byte(byte).ok_or(PacketError::UnknownKind(byte))?,
+      None => return Err(PacketError::Incomplete { received: 0, size: 1 }.into()),
+    };
+
+    if kind.is_encrypted() {
+      return Err(PacketError::MissingDecryption.into());
+    }
+
+    if bytes.len() < kind.offset() {
+      let error = PacketError::Incomplete {
+        received: bytes.len(),
+        size: kind.offset(),
+      };
+      return Err(error.into());
+    }
+
+    let size = BigEndian::read_uint(&bytes[1..], kind.bytes()) as usize;
+    if size < kind.offset() {
+      return Err(PacketError::InvalidSize(size).into());
+    }
+
+    if bytes.len() < size {
+      let error = PacketError::Incomplete {
+        received: bytes.len(),
+        size,
+      };
+      return Err(error.into());
+    }
+
+    Ok(PacketRef {
+      frame: &bytes[..size],
+      offset: kind.offset(),
+    })
+  }
+
+  /// Returns the packet's kind (C1 or C2 only).
+  pub fn kind(&self) -> PacketKind {
+    PacketKind::from_byte(self.frame[0]).expect("frame has a valid kind")
+  }
+
+  /// Returns the packet's code designation.
+  pub fn code(&self) -> u8 { self.frame[self.offset - 1] }
+
+  /// Returns the length of the entire packet.
+  pub fn len(&self) -> usize { self.frame.len() }
+
+  /// Returns whether the packet is empty or not.
+  pub fn is_empty(&self) -> bool { self.frame.is_empty() }
+
+  /// Returns the content of the package.
+  pub fn data(&self) -> &'a [u8] { &self.frame[self.offset..] }
+
+  /// Returns the packet's frame.
+  pub fn as_bytes(&self) -> &'a [u8] { self.frame }
+
+  /// Copies the view into an owned packet.
+  pub fn to_owned(&self) -> Packet {
+    let mut packet = Packet::new(self.kind(), self.code());
+    packet.append(self.data());
+    packet
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse() {
+    let bytes = [0xC2, 0x00, 0x05, 0xF3, 0x2A, 0xC1];
+    let packet = PacketRef::parse(&bytes).unwrap();
+    assert_eq!((packet.kind(), packet.code(), packet.len()), (PacketKind::C2, 0xF3, 5));
+    assert_eq!(packet.data(), [0x2A]);
+    assert_eq!(packet.to_owned().to_bytes(), packet.as_bytes());
+
+    let error = PacketRef::parse(&bytes[5..]).unwrap_err();
+    assert_eq!(PacketError::of(&error), Some(PacketError::Incomplete { received: 1, size: 3 }));
+    assert!(PacketRef::parse(&[0xC1, 0x02, 0x00]).is_err());
+    assert!(PacketRef::parse(&[0xC3, 0x03, 0x00]).is_err());
+  }
+}
@@ -0,0 +1,133 @@
+//! Game server authentication packets.
+//!
+//! Upon connecting, the game server greets the client with its version. The
+//! client then logs in with its account, whose credentials are scrambled
+//! using a repeating XOR key, and later logs out (e.g to select another
+//! character). Text fields are fixed-size and NUL padded.
+
+use crate::crypto::scramble_credential;
+use crate::serialize::{width, BytesFixed};
+use crate::Packet;
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// The server's greeting, with its version for the client to check.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F1", subcode = "00")]
+#[packet(test_vector = "C1 0C F1 00 01 00 2A 31 30 34 30 35")]
+pub struct VersionCheck {
+  /// The result of the connection (1 if accepted).
+  pub result: u8,
+  /// The client's index on the server.
+  #[serde(with = "width::u16_be")]
+  pub index: u16,
+  /// The server's version (e.g `10405`).
+  pub version: BytesFixed<5>,
+}
+
+/// A login with an account.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F1", subcode = "01")]
+pub struct LoginRequest {
+  /// The account's name, scrambled.
+  pub account: BytesFixed<10>,
+  /// The account's password, scrambled.
+  #[mu(redact)]
+  pub password: BytesFixed<10>,
+  /// The number of milliseconds since the client started.
+  pub tick: u32,
+  /// The client's version (e.g `10405`).
+  pub version: BytesFixed<5>,
+  /// The client's serial.
+  pub serial: BytesFixed<16>,
+}
+
+impl LoginRequest {
+  /// Creates a login, scrambling the account's credentials.
+  pub fn new(
+    account: &str,
+    password: &str,
+    tick: u32,
+    version: [u8; 5],
+    serial: [u8; 16],
+  ) -> io::Result<Self> {
+    Ok(LoginRequest {
+      account: BytesFixed(credential(account)?),
+      password: BytesFixed(credential(password)?),
+      tick,
+      version: BytesFixed(version),
+      serial: BytesFixed(serial),
+    })
+  }
+
+  /// Returns the account's name, unscrambled.
+  pub fn account(&self) -> String { unscramble(self.account.as_bytes()) }
+
+  /// Returns the account's password, unscrambled.
+  pub fn password(&self) -> String { unscramble(self.password.as_bytes()) }
+}
+
+/// The result of a login.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F1", subcode = "01", test_vector = "C1 05 F1 01 01")]
+pub struct LoginResult {
+  /// The result (e.g 0 for an invalid password, 1 if successful, or 3 if
+  /// the account is already connected).
+  pub result: u8,
+}
+
+impl LoginResult {
+  /// The result of a successful login.
+  pub const SUCCESS: u8 = 0x01;
+
+  /// Returns whether the login was successful.
+  pub fn is_success(&self) -> bool { self.result == Self::SUCCESS }
+}
+
+/// A logout, requested by the client and confirmed by the server.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F1", subcode = "02", test_vector = "C1 05 F1 02 02")]
+pub struct Logout {
+  /// Where the client goes (i.e 0 to exit, 1 to select a server, or 2 to
+  /// select a character).
+  pub target: u8,
+}
+
+/// Scrambles a credential, padded with NUL to its size.
+fn credential<const N: usize>(value: &str) -> io::Result<[u8; N]> {
+  if value.len() > N {
+    let message = format!("credential exceeds {} bytes", N);
+    return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+  }
+
+  let mut bytes = [0; N];
+  bytes[..value.len()].copy_from_slice(value.as_bytes());
+  scramble_credential(&mut bytes);
+  Ok(bytes)
+}
+
+/// Returns a scrambled credential as text, up to its first NUL.
+fn unscramble(bytes: &[u8]) -> String {
+  let mut bytes = bytes.to_vec();
+  scramble_credential(&mut bytes);
+  let size = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+  String::from_utf8_lossy(&bytes[..size]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{PacketDecodable, PacketEncodable};
+
+  #[test]
+  fn login_request() {
+    let login = LoginRequest::new("account", "secret", 0x2A, *b"10405", [0; 16]).unwrap();
+    let packet = login.to_packet().unwrap();
+    assert_eq!(packet.len(), 0x31);
+    assert_eq!(&packet.data()[1..4], [b'a' ^ 0xFC, b'c' ^ 0xCF, b'c' ^ 0xAB]);
+
+    let decoded = LoginRequest::from_packet(&packet).unwrap();
+    assert_eq!((decoded.account(), decoded.password()), ("account".into(), "secret".into()));
+    assert!(LoginRequest::new("account", "a password too long", 0, *b"10405", [0; 16]).is_err());
+  }
+}
@@ -0,0 +1,202 @@
+//! Character management packets.
+//!
+//! After logging in, the client requests its account's characters, and may
+//! then create, delete or select one of them. All of these share the code
+//! `F3`, and are distinguished by their subcode. Names are fixed-size and NUL
+//! padded.
+
+use crate::serialize::{counted, BytesFixed};
+use crate::Packet;
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// A character's name.
+pub type CharacterName = BytesFixed<10>;
+
+/// A client's request for its account's characters.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F3", subcode = "00", test_vector = "C1 04 F3 00")]
+pub struct CharacterListRequest;
+
+/// A character, as listed for its account.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CharacterEntry {
+  /// The character's slot.
+  pub index: u8,
+  /// The character's name.
+  pub name: CharacterName,
+  /// Padding, which is ignored.
+  pub padding: u8,
+  /// The character's level.
+  pub level: u16,
+  /// The character's status (e.g 0 for normal, or 1 if blocked).
+  pub status: u8,
+  /// The character's class and equipment, as shown in the selection.
+  pub appearance: BytesFixed<18>,
+  /// The character's guild status.
+  pub guild_status: u8,
+}
+
+impl CharacterEntry {
+  /// Returns the character's name, as text.
+  pub fn name(&self) -> String { text(self.name.as_bytes()) }
+}
+
+/// The account's characters, sent in response to a `CharacterListRequest`.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F3", subcode = "00")]
+pub struct CharacterList {
+  /// The most advanced class which may be created.
+  pub max_class: u8,
+  /// The number of times a character may be moved to another server.
+  pub move_count: u8,
+  /// The account's characters.
+  #[serde(with = "counted::by_u8")]
+  pub characters: Vec<CharacterEntry>,
+}
+
+/// A client's request to create a character.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F3", subcode = "01")]
+#[packet(test_vector = "C1 0F F3 01 4E 6F 76 61 00 00 00 00 00 00 10")]
+pub struct CreateCharacter {
+  /// The character's name.
+  pub name: CharacterName,
+  /// The character's class.
+  pub class: u8,
+}
+
+impl CreateCharacter {
+  /// Creates a request for a character of a class.
+  pub fn new(name: &str, class: u8) -> io::Result<Self> {
+    Ok(CreateCharacter { name: padded(name)?, class })
+  }
+}
+
+/// The result of creating a character.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F3", subcode = "01")]
+pub struct CreateCharacterResult {
+  /// The result (e.g 0 if the name is in use, or 1 if successful).
+  pub result: u8,
+  /// The character's name.
+  pub name: CharacterName,
+  /// The character's slot.
+  pub index: u8,
+  /// The character's level.
+  pub level: u16,
+  /// The character's class.
+  pub class: u8,
+}
+
+impl CreateCharacterResult {
+  /// Returns whether the character was created.
+  pub fn is_success(&self) -> bool { self.result == 0x01 }
+}
+
+/// A client's request to delete a character.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F3", subcode = "02")]
+pub struct DeleteCharacter {
+  /// The character's name.
+  pub name: CharacterName,
+  /// The account's security code (i.e personal ID).
+  #[mu(redact)]
+  pub security_code: BytesFixed<10>,
+}
+
+impl DeleteCharacter {
+  /// Creates a request to delete a character, confirmed by a security code.
+  pub fn new(name: &str, security_code: &str) -> io::Result<Self> {
+    let security_code = padded(security_code)?;
+    Ok(DeleteCharacter { name: padded(name)?, security_code })
+  }
+}
+
+/// The result of deleting a character.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F3", subcode = "02", test_vector = "C1 05 F3 02 01")]
+pub struct DeleteCharacterResult {
+  /// The result (e.g 0 if the character is blocked, 1 if successful, or 2
+  /// for an invalid security code).
+  pub result: u8,
+}
+
+impl DeleteCharacterResult {
+  /// Returns whether the character was deleted.
+  pub fn is_success(&self) -> bool { self.result == 0x01 }
+}
+
+/// A client's selection of the character to play.
+///
+/// The server responds with the character's information, and the client then
+/// enters the game.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F3", subcode = "03")]
+#[packet(test_vector = "C1 0E F3 03 4E 6F 76 61 00 00 00 00 00 00")]
+pub struct SelectCharacter {
+  /// The character's name.
+  pub name: CharacterName,
+}
+
+impl SelectCharacter {
+  /// Creates a selection of a character.
+  pub fn new(name: &str) -> io::Result<Self> { Ok(SelectCharacter { name: padded(name)? }) }
+}
+
+/// Returns text as a fixed-size field, padded with NUL.
+fn padded(value: &str) -> io::Result<CharacterName> {
+  let mut bytes = [0; 10];
+  if value.len() > bytes.len() {
+    let message = format!("text exceeds {} bytes", bytes.len());
+    return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+  }
+
+  bytes[..value.len()].copy_from_slice(value.as_bytes());
+  Ok(BytesFixed(bytes))
+}
+
+/// Returns a fixed-size field as text, up to its first NUL.
+fn text(bytes: &[u8]) -> String {
+  let size = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+  String::from_utf8_lossy(&bytes[..size]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{PacketDecodable, PacketEncodable};
+
+  #[test]
+  fn character_list() {
+    let list = CharacterList {
+      max_class: 3,
+      move_count: 0,
+      characters: vec![CharacterEntry {
+        index: 0,
+        name: padded("Nova").unwrap(),
+        padding: 0,
+        level: 400,
+        status: 0,
+        appearance: BytesFixed([0x10; 18]),
+        guild_status: 0xFF,
+      }],
+    };
+
+    let packet = list.to_packet().unwrap();
+    assert_eq!(packet.len(), 0x29);
+    assert_eq!(&packet.to_bytes()[..9], [0xC1, 0x29, 0xF3, 0x00, 0x03, 0x00, 0x01, 0x00, b'N']);
+
+    let decoded = CharacterList::from_packet(&packet).unwrap();
+    assert_eq!(decoded.characters[0].name(), "Nova");
+    assert_eq!(decoded, list);
+  }
+
+  #[test]
+  fn subcodes() {
+    // Requests of the same code are only decoded by their own type
+    let packet = SelectCharacter::new("Nova").unwrap().to_packet().unwrap();
+    assert!(DeleteCharacter::from_packet(&packet).is_err());
+    assert!(CreateCharacter::new("NameTooLong", 0x10).is_err());
+  }
+}
@@ -0,0 +1,133 @@
+//! Connect server packets.
+//!
+//! Clients connect to the connect server to select a game server; they are
+//! greeted, request the list of servers, and then the address of the one
+//! selected. Game servers announce their status to it over UDP.
+
+use crate::serialize::{counted, BytesFixed};
+use crate::{Packet, PacketDecodable, PacketEncodable};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// The greeting sent by the connect server upon connecting.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "00", subcode = "01", test_vector = "C1 04 00 01")]
+pub struct Hello;
+
+/// A client's request for the list of game servers.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F4", subcode = "06", test_vector = "C1 04 F4 06")]
+pub struct ServerListRequest;
+
+/// A game server, as listed by the connect server.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ServerEntry {
+  /// The game server's code.
+  pub code: u16,
+  /// The server's load, as a percentage.
+  pub load: u8,
+  /// Padding, which is ignored.
+  pub padding: u8,
+}
+
+/// The list of game servers, sent in response to a `ServerListRequest`.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C2", code = "F4", subcode = "06")]
+#[packet(test_vector = "C2 00 0B F4 06 00 01 00 00 05 77")]
+pub struct ServerList {
+  /// The game servers available.
+  #[serde(with = "counted::by_u16_be")]
+  pub servers: Vec<ServerEntry>,
+}
+
+/// A client's request for the address of a game server.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F4", subcode = "03", test_vector = "C1 06 F4 03 00 00")]
+pub struct ServerInfoRequest {
+  /// The game server's code.
+  pub code: u16,
+}
+
+/// The address of a game server, sent in response to a `ServerInfoRequest`.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F4", subcode = "03")]
+pub struct ServerInfo {
+  /// The server's IP address, as text.
+  pub address: BytesFixed<16>,
+  /// The port the server accepts clients on.
+  pub port: u16,
+}
+
+/// Server status, announced periodically by game servers over UDP.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "01")]
+pub struct ServerStatus {
+  /// The game server's code.
+  pub code: u16,
+  /// The server's load, as a percentage.
+  pub load: u8,
+  /// The number of connected users.
+  pub users: u16,
+  /// The number of authenticated accounts.
+  pub accounts: u16,
+  /// The number of PC room users.
+  pub pc_room_users: u16,
+  /// The maximum number of users.
+  pub max_users: u16,
+}
+
+impl ServerStatus {
+  /// Encodes the status as a datagram.
+  pub fn to_datagram(&self) -> Result<Vec<u8>, io::Error> { Ok(self.to_packet()?.to_bytes()) }
+
+  /// Decodes the status from a datagram.
+  ///
+  /// The datagram must contain exactly the packet, without any trailing bytes.
+  pub fn from_datagram(datagram: &[u8]) -> Result<Self, io::Error> {
+    Self::from_packet(&Packet::from_datagram(datagram, None)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn server_status() {
+    let status = ServerStatus {
+      code: 1,
+      load: 25,
+      users: 100,
+      accounts: 98,
+      pc_room_users: 0,
+      max_users: 400,
+    };
+
+    let datagram = status.to_datagram().unwrap();
+    assert_eq!(
+      datagram,
+      [0xC1, 0x0E, 0x01, 0x01, 0x00, 0x19, 0x64, 0x00, 0x62, 0x00, 0x00, 0x00, 0x90, 0x01]
+    );
+    assert_eq!(ServerStatus::from_datagram(&datagram).unwrap(), status);
+
+    // Datagrams are untrusted, so malformed ones are rejected
+    assert!(ServerStatus::from_datagram(&[0xC1, 0x02, 0xC1]).is_err());
+    assert!(ServerStatus::from_datagram(&[&datagram[..], &[0x00]].concat()).is_err());
+  }
+
+  #[test]
+  fn server_info() {
+    let mut address = [0; 16];
+    address[..9].copy_from_slice(b"127.0.0.1");
+    let info = ServerInfo {
+      address: BytesFixed(address),
+      port: 55901,
+    };
+
+    let packet = info.to_packet().unwrap();
+    assert_eq!(packet.len(), 0x16);
+    assert_eq!(&packet.to_bytes()[..7], [0xC1, 0x16, 0xF4, 0x03, b'1', b'2', b'7']);
+    assert_eq!(&packet.to_bytes()[20..], [0x5D, 0xDA]);
+    assert_eq!(ServerInfo::from_packet(&packet).unwrap(), info);
+  }
+}
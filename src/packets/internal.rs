@@ -0,0 +1,93 @@
+//! Internal packets between game servers and the join server.
+//!
+//! These use the same C1/C2 framing as clients, but without any encryption
+//! (see `PacketCodecState::internal_default`). Codes overlap with client
+//! packets, so they must only be decoded on internal links. Text fields are
+//! fixed-size and NUL padded.
+
+use crate::serialize::BytesFixed;
+use crate::Packet;
+use serde::{Deserialize, Serialize};
+
+/// A game server's registration, sent upon connecting to the join server.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "00")]
+pub struct ServerInfo {
+  /// The server's type (e.g 1 for game servers).
+  pub kind: u8,
+  /// The port the server accepts clients on.
+  pub port: u16,
+  /// The server's name.
+  pub name: BytesFixed<50>,
+  /// The server's code.
+  pub code: u16,
+}
+
+/// An account login, forwarded by a game server for verification.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "01")]
+pub struct AccountRequest {
+  /// The user's index on the game server.
+  pub index: u16,
+  /// The account's name.
+  pub account: BytesFixed<11>,
+  /// The account's password.
+  #[mu(redact)]
+  pub password: BytesFixed<11>,
+  /// The user's IP address, as text.
+  pub address: BytesFixed<16>,
+}
+
+/// The join server's verification of an account login.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "01")]
+pub struct AccountResult {
+  /// The user's index on the game server.
+  pub index: u16,
+  /// The account's name.
+  pub account: BytesFixed<11>,
+  /// The account's personal code, used for deleting characters.
+  pub personal_code: BytesFixed<14>,
+  /// The login's result, using the same values as for clients.
+  pub result: u8,
+  /// The account's block code (zero if not blocked).
+  pub block_code: u8,
+}
+
+/// An account logout, sent by a game server when a user disconnects.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "02")]
+pub struct AccountLogout {
+  /// The account's name.
+  pub account: BytesFixed<11>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{PacketDecodable, PacketEncodable};
+
+  fn text<const N: usize>(text: &str) -> BytesFixed<N> {
+    let mut field = [0; N];
+    field[..text.len()].copy_from_slice(text.as_bytes());
+    BytesFixed(field)
+  }
+
+  #[test]
+  fn account_request() {
+    let request = AccountRequest {
+      index: 0x2A,
+      account: text("account"),
+      password: text("password"),
+      address: text("127.0.0.1"),
+    };
+
+    let packet = request.to_packet().unwrap();
+    assert_eq!(packet.len(), 3 + 2 + 11 + 11 + 16);
+    assert_eq!(&packet.data()[..4], &[0x2A, 0x00, b'a', b'c']);
+    assert_eq!(AccountRequest::from_packet(&packet).unwrap(), request);
+
+    let logout = AccountLogout { account: text("account") };
+    assert_eq!(AccountLogout::from_packet(&logout.to_packet().unwrap()).unwrap(), logout);
+  }
+}
@@ -0,0 +1,7 @@
+//! Typed definitions of common packets.
+
+pub mod auth;
+pub mod character;
+pub mod connect;
+pub mod internal;
+pub mod negotiate;
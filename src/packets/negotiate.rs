@@ -0,0 +1,150 @@
+//! Negotiation of protocol extensions between custom clients & servers.
+//!
+//! After connecting, a client offers the extensions it supports, and the
+//! server accepts the subset it supports as well. Stock servers ignore the
+//! offer, so a client without a reply must assume no extensions. Extensions
+//! are enabled by both sides directly after the acceptance.
+
+use crate::Packet;
+use serde::{Deserialize, Serialize};
+use std::{io, ops};
+
+/// The version of the negotiation protocol.
+pub const VERSION: u8 = 1;
+
+/// A set of protocol extensions.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Extensions(pub u32);
+
+impl Extensions {
+  /// No extensions.
+  pub const NONE: Extensions = Extensions(0);
+  /// LZ4 compression of packet data.
+  pub const LZ4: Extensions = Extensions(1 << 0);
+  /// Zlib compression of packet data.
+  pub const ZLIB: Extensions = Extensions(1 << 1);
+  /// ChaCha20-Poly1305 sealing of frames.
+  pub const AEAD: Extensions = Extensions(1 << 2);
+  /// Frames larger than stock clients support.
+  pub const BIG_FRAMES: Extensions = Extensions(1 << 3);
+
+  /// Returns whether all extensions of another set are included.
+  pub fn contains(self, other: Extensions) -> bool { self.0 & other.0 == other.0 }
+
+  /// Returns the extensions included in both sets.
+  pub fn intersection(self, other: Extensions) -> Extensions { Extensions(self.0 & other.0) }
+
+  /// Returns whether the set is empty.
+  pub fn is_empty(self) -> bool { self.0 == 0 }
+}
+
+impl ops::BitOr for Extensions {
+  type Output = Extensions;
+
+  fn bitor(self, other: Extensions) -> Extensions { Extensions(self.0 | other.0) }
+}
+
+/// The extensions offered by a client.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "FA", subcode = "00")]
+pub struct Offer {
+  /// The client's negotiation version.
+  pub version: u8,
+  /// The extensions supported by the client.
+  pub extensions: Extensions,
+}
+
+/// The extensions accepted by a server.
+#[derive(Serialize, Deserialize, Packet, Debug, Clone, PartialEq, Eq)]
+#[packet(kind = "C1", code = "FA", subcode = "01")]
+pub struct Accept {
+  /// The server's negotiation version.
+  pub version: u8,
+  /// The extensions enabled for the session.
+  pub extensions: Extensions,
+}
+
+/// The negotiation state of one side of a session.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Negotiation {
+  supported: Extensions,
+  agreed: Option<Extensions>,
+}
+
+impl Negotiation {
+  /// Creates a negotiation for the extensions supported locally.
+  pub fn new(supported: Extensions) -> Self {
+    Negotiation {
+      supported,
+      agreed: None,
+    }
+  }
+
+  /// Returns the offer sent by a client.
+  pub fn offer(&self) -> Offer {
+    Offer {
+      version: VERSION,
+      extensions: self.supported,
+    }
+  }
+
+  /// Responds to a client's offer, agreeing upon the common extensions.
+  pub fn respond(&mut self, offer: &Offer) -> Accept {
+    let extensions = self.supported.intersection(offer.extensions);
+    self.agreed = Some(extensions);
+    Accept {
+      version: VERSION,
+      extensions,
+    }
+  }
+
+  /// Completes a client's negotiation, using the server's acceptance.
+  ///
+  /// An acceptance of extensions that were never offered is rejected.
+  pub fn accepted(&mut self, accept: &Accept) -> io::Result<Extensions> {
+    if !self.supported.contains(accept.extensions) {
+      let message = "server accepted extensions that were not offered";
+      return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+
+    self.agreed = Some(accept.extensions);
+    Ok(accept.extensions)
+  }
+
+  /// Completes a client's negotiation without a reply (e.g from a stock
+  /// server), disabling all extensions.
+  pub fn unanswered(&mut self) { self.agreed = Some(Extensions::NONE); }
+
+  /// Returns the extensions agreed upon, once negotiated.
+  pub fn agreed(&self) -> Option<Extensions> { self.agreed }
+
+  /// Returns whether an extension is enabled for the session.
+  pub fn is_enabled(&self, extension: Extensions) -> bool {
+    self.agreed.is_some_and(|agreed| agreed.contains(extension))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{PacketDecodable, PacketEncodable};
+
+  #[test]
+  fn negotiate() {
+    let mut client = Negotiation::new(Extensions::LZ4 | Extensions::AEAD);
+    let mut server = Negotiation::new(Extensions::LZ4 | Extensions::ZLIB);
+
+    let offer = client.offer().to_packet().unwrap();
+    assert_eq!(offer.to_bytes(), [0xC1, 0x09, 0xFA, 0x00, 0x01, 0x05, 0x00, 0x00, 0x00]);
+
+    let accept = server.respond(&Offer::from_packet(&offer).unwrap());
+    let accept = Accept::from_packet(&accept.to_packet().unwrap()).unwrap();
+    assert_eq!(client.accepted(&accept).unwrap(), Extensions::LZ4);
+    assert_eq!(client.agreed(), server.agreed());
+    assert!(client.is_enabled(Extensions::LZ4));
+    assert!(!client.is_enabled(Extensions::AEAD));
+
+    let forged = Accept { version: VERSION, extensions: Extensions::ZLIB };
+    assert!(client.accepted(&forged).is_err());
+  }
+}
@@ -0,0 +1,64 @@
+/// The handling of frames without any payload (i.e only a code).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum EmptyFrames {
+  /// Empty frames are decoded as packets without data.
+  #[default]
+  Surface,
+  /// Empty frames are consumed, without decoding a packet.
+  Skip,
+  /// Empty frames fail with an error.
+  Reject,
+}
+
+/// The handling of zero bytes where a frame is expected.
+///
+/// Some link layers pad frames with trailing zero bytes, which would
+/// otherwise be mistaken for the start of another frame.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Padding {
+  /// Padding fails with an error, as it is not a packet.
+  #[default]
+  Reject,
+  /// Padding is consumed.
+  Skip,
+}
+
+/// The policies of decoding irregular frames.
+///
+/// By default, empty frames are decoded and padding is rejected.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FramePolicy {
+  empty: EmptyFrames,
+  padding: Padding,
+}
+
+impl FramePolicy {
+  /// Creates the default policies.
+  pub fn new() -> Self { Self::default() }
+
+  /// Sets the handling of empty frames.
+  pub fn empty(mut self, empty: EmptyFrames) -> Self {
+    self.empty = empty;
+    self
+  }
+
+  /// Sets the handling of padding.
+  pub fn padding(mut self, padding: Padding) -> Self {
+    self.padding = padding;
+    self
+  }
+
+  /// Returns the handling of empty frames.
+  pub fn empty_frames(&self) -> EmptyFrames { self.empty }
+
+  /// Returns the handling of padding.
+  pub fn padding_bytes(&self) -> Padding { self.padding }
+
+  /// Returns the number of padding bytes at the start of a buffer to skip.
+  pub(crate) fn skipped_padding(&self, bytes: &[u8]) -> usize {
+    match self.padding {
+      Padding::Reject => 0,
+      Padding::Skip => bytes.iter().take_while(|&&byte| byte == 0).count(),
+    }
+  }
+}
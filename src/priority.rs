@@ -0,0 +1,178 @@
+//! Prioritization of outbound packets.
+//!
+//! On a congested connection, a burst of bulk packets (e.g shop or warehouse
+//! listings) may otherwise delay latency sensitive ones (e.g keep-alives or
+//! combat). Packets are queued per priority class, and passed on to the
+//! underlying sink in order of priority, as it makes room for them.
+
+use crate::Packet;
+use futures::{Async, AsyncSink, Poll, Sink, StartSend};
+use std::collections::VecDeque;
+
+/// Default number of packets queued per priority class.
+const DEFAULT_CAPACITY: usize = 256;
+
+/// A priority class of outbound packets.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Priority {
+  /// Latency sensitive packets (e.g keep-alives or combat).
+  Critical,
+  /// Regular packets.
+  #[default]
+  Normal,
+  /// Large or deferrable packets (e.g shop or warehouse listings).
+  Bulk,
+}
+
+impl Priority {
+  /// All priority classes, from highest to lowest.
+  pub const ALL: [Priority; 3] = [Priority::Critical, Priority::Normal, Priority::Bulk];
+
+  fn index(self) -> usize { self as usize }
+}
+
+/// The statistics of a priority class queue.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueueStats {
+  queued: usize,
+  peak: usize,
+  sent: u64,
+}
+
+impl QueueStats {
+  /// Returns the number of packets currently queued.
+  pub fn queued(&self) -> usize { self.queued }
+
+  /// Returns the largest number of packets queued at once.
+  pub fn peak(&self) -> usize { self.peak }
+
+  /// Returns the number of packets passed on to the underlying sink.
+  pub fn sent(&self) -> u64 { self.sent }
+}
+
+/// A sink of packets, prioritized by class.
+///
+/// Items are sent as `(Priority, Packet)`. Each class has a bounded queue,
+/// and a full queue applies back pressure to its class only.
+#[derive(Debug)]
+pub struct PrioritySink<S> {
+  inner: S,
+  capacity: usize,
+  queues: [VecDeque<Packet>; 3],
+  stats: [QueueStats; 3],
+}
+
+impl<S> PrioritySink<S>
+where
+  S: Sink<SinkItem = Packet>,
+{
+  /// Creates a prioritizing sink, wrapping another.
+  pub fn new(inner: S) -> Self { Self::with_capacity(inner, DEFAULT_CAPACITY) }
+
+  /// Creates a prioritizing sink, with a number of packets queued per class.
+  pub fn with_capacity(inner: S, capacity: usize) -> Self {
+    PrioritySink {
+      inner,
+      capacity,
+      queues: Default::default(),
+      stats: Default::default(),
+    }
+  }
+
+  /// Returns the statistics of a priority class.
+  pub fn stats(&self, priority: Priority) -> QueueStats { self.stats[priority.index()] }
+
+  /// Returns a reference to the underlying sink.
+  pub fn get_ref(&self) -> &S { &self.inner }
+
+  /// Returns a mutable reference to the underlying sink.
+  pub fn get_mut(&mut self) -> &mut S { &mut self.inner }
+
+  /// Consumes the sink, returning the underlying one (dropping any queued
+  /// packets).
+  pub fn into_inner(self) -> S { self.inner }
+
+  /// Passes queued packets on to the underlying sink, highest priority
+  /// first, until it is full.
+  fn drain(&mut self) -> Result<(), S::SinkError> {
+    for priority in Priority::ALL.iter() {
+      let index = priority.index();
+      while let Some(packet) = self.queues[index].pop_front() {
+        if let AsyncSink::NotReady(packet) = self.inner.start_send(packet)? {
+          self.queues[index].push_front(packet);
+          return Ok(());
+        }
+
+        self.stats[index].queued -= 1;
+        self.stats[index].sent += 1;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl<S> Sink for PrioritySink<S>
+where
+  S: Sink<SinkItem = Packet>,
+{
+  type SinkItem = (Priority, Packet);
+  type SinkError = S::SinkError;
+
+  fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, S::SinkError> {
+    let (priority, packet) = item;
+    let index = priority.index();
+
+    if self.queues[index].len() >= self.capacity {
+      self.drain()?;
+      if self.queues[index].len() >= self.capacity {
+        return Ok(AsyncSink::NotReady((priority, packet)));
+      }
+    }
+
+    self.queues[index].push_back(packet);
+    let stats = &mut self.stats[index];
+    stats.queued += 1;
+    stats.peak = stats.peak.max(stats.queued);
+    Ok(AsyncSink::Ready)
+  }
+
+  fn poll_complete(&mut self) -> Poll<(), S::SinkError> {
+    loop {
+      self.drain()?;
+      let flushed = self.inner.poll_complete()?.is_ready();
+      let empty = self.queues.iter().all(VecDeque::is_empty);
+
+      match (flushed, empty) {
+        (true, true) => return Ok(Async::Ready(())),
+        // The underlying sink made room, so more packets can be passed on
+        (true, false) => continue,
+        (false, _) => return Ok(Async::NotReady),
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PacketKind;
+  use futures::Future;
+
+  #[test]
+  fn critical_first() {
+    let mut sink = PrioritySink::with_capacity(Vec::new(), 2);
+    let packet = |code| Packet::new(PacketKind::C1, code);
+
+    assert!(sink.start_send((Priority::Bulk, packet(0x01))).unwrap().is_ready());
+    assert!(sink.start_send((Priority::Bulk, packet(0x02))).unwrap().is_ready());
+    assert!(sink.start_send((Priority::Critical, packet(0x03))).unwrap().is_ready());
+    assert_eq!(sink.stats(Priority::Bulk).queued(), 2);
+
+    let sink = sink.flush().wait().unwrap();
+    let codes = sink.get_ref().iter().map(Packet::code).collect::<Vec<_>>();
+    assert_eq!(codes, [0x03, 0x01, 0x02]);
+    assert_eq!(sink.stats(Priority::Bulk).sent(), 2);
+    assert_eq!(sink.stats(Priority::Bulk).peak(), 2);
+    assert_eq!(sink.stats(Priority::Critical).queued(), 0);
+  }
+}
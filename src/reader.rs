@@ -0,0 +1,74 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use crate::Packet;
+use std::io;
+
+/// A cursor for reading a packet's data, for packets without a typed struct.
+///
+/// Errors describe the offset at which reading failed, relative to the start
+/// of the packet (i.e including its header).
+#[derive(Debug, Clone)]
+pub struct PacketReader<'a> {
+  data: &'a [u8],
+  header: usize,
+  position: usize,
+}
+
+impl<'a> PacketReader<'a> {
+  /// Creates a new reader for a packet's data.
+  pub(crate) fn new(packet: &'a Packet) -> Self {
+    PacketReader {
+      data: packet.data(),
+      header: packet.kind().offset(),
+      position: 0,
+    }
+  }
+
+  /// Returns the reader's position within the packet's data.
+  pub fn position(&self) -> usize { self.position }
+
+  /// Returns the number of bytes remaining.
+  pub fn remaining(&self) -> usize { self.data.len() - self.position }
+
+  /// Reads a byte.
+  pub fn read_u8(&mut self) -> io::Result<u8> { Ok(self.read_bytes(1)?[0]) }
+
+  /// Reads a little endian `u16`.
+  pub fn read_u16_le(&mut self) -> io::Result<u16> {
+    self.read_bytes(2).map(LittleEndian::read_u16)
+  }
+
+  /// Reads a big endian `u16`.
+  pub fn read_u16_be(&mut self) -> io::Result<u16> { self.read_bytes(2).map(BigEndian::read_u16) }
+
+  /// Reads a little endian `u32`.
+  pub fn read_u32_le(&mut self) -> io::Result<u32> {
+    self.read_bytes(4).map(LittleEndian::read_u32)
+  }
+
+  /// Reads a big endian `u32`.
+  pub fn read_u32_be(&mut self) -> io::Result<u32> { self.read_bytes(4).map(BigEndian::read_u32) }
+
+  /// Reads a fixed-size, NUL padded string.
+  pub fn read_str_fixed(&mut self, size: usize) -> io::Result<String> {
+    let bytes = self.read_bytes(size)?;
+    let length = bytes.iter().position(|&byte| byte == 0).unwrap_or(size);
+    Ok(String::from_utf8_lossy(&bytes[..length]).into_owned())
+  }
+
+  /// Reads a number of bytes.
+  pub fn read_bytes(&mut self, size: usize) -> io::Result<&'a [u8]> {
+    if size > self.remaining() {
+      let message = format!(
+        "unexpected end of packet at byte {}, {} bytes needed but {} remaining",
+        self.header + self.position,
+        size,
+        self.remaining()
+      );
+      return Err(io::Error::new(io::ErrorKind::UnexpectedEof, message));
+    }
+
+    let bytes = &self.data[self.position..self.position + size];
+    self.position += size;
+    Ok(bytes)
+  }
+}
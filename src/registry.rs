@@ -0,0 +1,170 @@
+//! Dispatching of packets to handlers, by their identifier.
+//!
+//! Instead of matching on each packet's code (and subcodes), handlers are
+//! registered per packet type, and each packet is decoded into the type of
+//! its handler before being passed to it.
+
+use crate::{Packet, PacketDecodable, PacketId, PacketKind};
+use std::collections::HashMap;
+use std::{fmt, io};
+
+/// A function handling a packet, with a caller's context.
+type Handler<C> = Box<dyn Fn(&Packet, &mut C) -> io::Result<()> + Send + Sync>;
+
+/// A table of handlers, keyed by kind and identifier.
+pub(crate) struct Handlers<H> {
+  handlers: HashMap<(PacketKind, PacketId), H>,
+  subcodes: usize,
+}
+
+impl<H> Handlers<H> {
+  /// Inserts a handler, replacing any of the same kind and identifier.
+  pub(crate) fn insert(&mut self, kind: PacketKind, id: PacketId, handler: H) {
+    self.subcodes = self.subcodes.max(id.subcodes().len());
+
+    // Packets are always of their decrypted kind
+    self.handlers.insert((kind.decrypted(), id), handler);
+  }
+
+  /// Returns the handler of a packet, matching the most subcodes.
+  pub(crate) fn get(&self, packet: &Packet) -> Option<&H> {
+    (0..=self.subcodes.min(packet.data().len()))
+      .rev()
+      .filter_map(|subcodes| PacketId::of(packet, subcodes))
+      .find_map(|id| self.handlers.get(&(packet.kind(), id)))
+  }
+
+  /// Returns the registered kinds and identifiers, in order.
+  pub(crate) fn keys(&self) -> Vec<(PacketKind, PacketId)> {
+    let mut keys = self.handlers.keys().cloned().collect::<Vec<_>>();
+    keys.sort_by_key(|&(kind, id)| (kind as u8, id));
+    keys
+  }
+
+  pub(crate) fn len(&self) -> usize { self.handlers.len() }
+}
+
+impl<H> Default for Handlers<H> {
+  fn default() -> Self {
+    Handlers {
+      handlers: HashMap::new(),
+      subcodes: 0,
+    }
+  }
+}
+
+/// A registry of packet handlers.
+///
+/// Handlers are registered against a kind, code and subcodes, and each packet
+/// dispatched is passed to the handler matching the most of its subcodes
+/// (e.g `F3:01` takes precedence over `F3`). Since requests and responses
+/// often share identifiers, a registry handles a single direction.
+/// Registering an identifier again replaces its handler.
+///
+/// The context (e.g a connection's session) is passed to each handler.
+pub struct PacketRegistry<C = ()> {
+  handlers: Handlers<Handler<C>>,
+}
+
+impl<C> PacketRegistry<C> {
+  /// Creates a registry, without any handlers.
+  pub fn new() -> Self {
+    PacketRegistry {
+      handlers: Handlers::default(),
+    }
+  }
+
+  /// Registers a handler of a packet type, which is decoded before the
+  /// handler is called.
+  pub fn on<T, F>(self, handler: F) -> Self
+  where
+    T: PacketDecodable + 'static,
+    F: Fn(T, &mut C) -> io::Result<()> + Send + Sync + 'static,
+  {
+    self.on_packet(T::kind(), T::identifier(), move |packet, context| {
+      handler(T::from_packet(packet)?, context)
+    })
+  }
+
+  /// Registers a handler of raw packets, by their kind and identifier.
+  pub fn on_packet<F>(mut self, kind: PacketKind, id: PacketId, handler: F) -> Self
+  where
+    F: Fn(&Packet, &mut C) -> io::Result<()> + Send + Sync + 'static,
+  {
+    self.handlers.insert(kind, id, Box::new(handler));
+    self
+  }
+
+  /// Dispatches a packet to its handler.
+  ///
+  /// Returns whether the packet had a handler, or any error from decoding
+  /// the packet or from the handler itself.
+  pub fn dispatch(&self, packet: &Packet, context: &mut C) -> io::Result<bool> {
+    match self.handlers.get(packet) {
+      Some(handler) => handler(packet, context).map(|_| true),
+      None => Ok(false),
+    }
+  }
+
+  /// Returns whether a packet has a handler.
+  pub fn contains(&self, packet: &Packet) -> bool { self.handlers.get(packet).is_some() }
+
+  /// Returns the number of handlers.
+  pub fn len(&self) -> usize { self.handlers.len() }
+
+  /// Returns whether the registry is without any handlers.
+  pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+impl<C> Default for PacketRegistry<C> {
+  fn default() -> Self { Self::new() }
+}
+
+impl<C> fmt::Debug for PacketRegistry<C> {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    fmt.debug_tuple("PacketRegistry").field(&self.handlers.keys()).finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::packets::character::{CharacterListRequest, SelectCharacter};
+  use crate::{PacketEncodable, PacketType};
+
+  #[test]
+  fn dispatch() {
+    let registry = PacketRegistry::<Vec<PacketId>>::new()
+      .on(|_: CharacterListRequest, log| {
+        log.push(CharacterListRequest::identifier());
+        Ok(())
+      })
+      .on(|select: SelectCharacter, log| {
+        assert_eq!(&select.name.0[..4], b"Nova");
+        log.push(SelectCharacter::identifier());
+        Ok(())
+      })
+      .on_packet(PacketKind::C1, PacketId::new(0xF3), |packet, log| {
+        log.push(PacketId::of(packet, 1).unwrap());
+        Ok(())
+      });
+
+    let mut log = Vec::new();
+    let select = SelectCharacter::new("Nova").unwrap().to_packet().unwrap();
+    assert!(registry.dispatch(&select, &mut log).unwrap());
+    assert!(registry.dispatch(&CharacterListRequest.to_packet().unwrap(), &mut log).unwrap());
+
+    let mut delete = Packet::new(PacketKind::C1, 0xF3);
+    delete.append(&[0x02]);
+    assert!(registry.dispatch(&delete, &mut log).unwrap());
+    assert!(!registry.dispatch(&Packet::new(PacketKind::C1, 0xF4), &mut log).unwrap());
+    assert!(!registry.contains(&Packet::new(PacketKind::C2, 0xF3)));
+    assert_eq!(log, ["F3:03".parse().unwrap(), "F3:00".parse().unwrap(), "F3:02".parse().unwrap()]);
+
+    // Packets failing to decode are reported as errors
+    let mut truncated = Packet::new(PacketKind::C1, 0xF3);
+    truncated.append(&[0x03, b'N']);
+    assert!(registry.dispatch(&truncated, &mut log).is_err());
+    assert_eq!(registry.len(), 3);
+  }
+}
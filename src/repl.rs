@@ -0,0 +1,219 @@
+//! An interactive console for sending packets to a server.
+//!
+//! The console connects through a codec, and reads commands line by line:
+//!
+//! ```text
+//! send CharacterList          # a named packet, from the name map
+//! send F3 00                  # a code, followed by subcodes & data
+//! send JoinResult 01 2A 00    # a named packet, followed by data
+//! recv 500                    # wait for responses (in milliseconds)
+//! quit
+//! ```
+//!
+//! Data is given as hexadecimal bytes. Responses are printed after each
+//! command, along with their names when known.
+
+use bytes::BytesMut;
+use crate::names::PacketNames;
+use crate::{Packet, PacketCodec, PacketKind};
+use std::io::{self, BufRead, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use tokio_io::codec::{Decoder, Encoder};
+
+/// Time spent waiting for responses after a packet is sent.
+const RESPONSE_WAIT: Duration = Duration::from_millis(100);
+
+/// An interactive packet console connected to a server.
+#[derive(Debug)]
+pub struct Console {
+  stream: TcpStream,
+  codec: PacketCodec,
+  names: PacketNames,
+  input: BytesMut,
+}
+
+impl Console {
+  /// Connects to a server, naming packets using a map.
+  pub fn connect<A: ToSocketAddrs>(
+    address: A,
+    codec: PacketCodec,
+    names: PacketNames,
+  ) -> io::Result<Self> {
+    Ok(Console {
+      stream: TcpStream::connect(address)?,
+      codec,
+      names,
+      input: BytesMut::new(),
+    })
+  }
+
+  /// Reads & executes commands until the input ends or `quit` is entered.
+  ///
+  /// Failing commands are reported to the output, without stopping the
+  /// console, but a closed connection does.
+  pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+    write!(output, "> ")?;
+    output.flush()?;
+
+    for line in input.lines() {
+      let line = line?;
+      if line.trim() == "quit" {
+        break;
+      }
+
+      match self.execute(&line) {
+        Ok(lines) => lines.iter().try_for_each(|line| writeln!(output, "{}", line))?,
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Err(error),
+        Err(error) => writeln!(output, "error: {}", error)?,
+      }
+
+      write!(output, "> ")?;
+      output.flush()?;
+    }
+    Ok(())
+  }
+
+  /// Executes a single command, returning the lines to print.
+  pub fn execute(&mut self, line: &str) -> io::Result<Vec<String>> {
+    let mut tokens = line.split_whitespace();
+    match tokens.next() {
+      None => Ok(Vec::new()),
+      Some("send") => {
+        let packet = self.parse_packet(tokens)?;
+        let mut output = BytesMut::new();
+        self.codec.encode(packet.clone(), &mut output)?;
+        self.stream.write_all(&output)?;
+
+        let mut lines = vec![self.describe("->", &packet)];
+        lines.extend(self.receive(RESPONSE_WAIT)?);
+        Ok(lines)
+      },
+      Some("recv") => {
+        let millis = match tokens.next() {
+          Some(millis) => millis.parse().map_err(|_| invalid_input("invalid duration"))?,
+          None => RESPONSE_WAIT.as_millis() as u64,
+        };
+        self.receive(Duration::from_millis(millis))
+      },
+      Some("help") => Ok(vec![
+        "send <name|code> [bytes...]  sends a packet".into(),
+        "recv [milliseconds]          waits for responses".into(),
+        "quit                         closes the console".into(),
+      ]),
+      Some(command) => Err(invalid_input(format!("unknown command '{}'", command))),
+    }
+  }
+
+  /// Parses a packet from a name or code, followed by hexadecimal bytes.
+  fn parse_packet<'a, I: Iterator<Item = &'a str>>(&self, mut tokens: I) -> io::Result<Packet> {
+    let target = tokens
+      .next()
+      .ok_or_else(|| invalid_input("missing packet name or code"))?;
+
+    let mut bytes = match self.names.codes(target) {
+      Some(codes) => codes.to_vec(),
+      None => vec![parse_byte(target)?],
+    };
+    for token in tokens {
+      bytes.push(parse_byte(token)?);
+    }
+
+    let kind = PacketKind::from_size(bytes.len() - 1, None)
+      .ok_or_else(|| invalid_input("packet is too large"))?;
+    let mut packet = Packet::new(kind, bytes[0]);
+    packet.append(&bytes[1..]);
+    Ok(packet)
+  }
+
+  /// Waits for responses, describing each one received.
+  fn receive(&mut self, wait: Duration) -> io::Result<Vec<String>> {
+    let deadline = Instant::now() + wait;
+    let mut lines = Vec::new();
+    let mut buffer = [0; 4096];
+
+    loop {
+      while let Some(packet) = self.codec.decode(&mut self.input)? {
+        lines.push(self.describe("<-", &packet));
+      }
+
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      if remaining == Duration::from_secs(0) {
+        break;
+      }
+
+      self.stream.set_read_timeout(Some(remaining))?;
+      match self.stream.read(&mut buffer) {
+        Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed")),
+        Ok(size) => self.input.extend_from_slice(&buffer[..size]),
+        Err(ref error)
+          if error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut =>
+        {
+          break
+        },
+        Err(error) => return Err(error),
+      }
+    }
+
+    Ok(lines)
+  }
+
+  /// Returns a single line description of a packet.
+  fn describe(&self, arrow: &str, packet: &Packet) -> String {
+    let name = self.names.get(packet).unwrap_or("?");
+    let data = packet
+      .data()
+      .iter()
+      .map(|byte| format!("{:02X}", byte))
+      .collect::<Vec<_>>()
+      .join(" ");
+    format!("{} {} ({:?} {:02X}) {}", arrow, name, packet.kind(), packet.code(), data)
+      .trim_end()
+      .to_string()
+  }
+}
+
+/// Parses a single hexadecimal byte.
+fn parse_byte(token: &str) -> io::Result<u8> {
+  u8::from_str_radix(token.trim_start_matches("0x"), 16)
+    .map_err(|_| invalid_input(format!("invalid byte '{}'", token)))
+}
+
+fn invalid_input<S: Into<String>>(message: S) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{PacketCodecState, PacketId};
+  use std::net::TcpListener;
+  use std::thread;
+
+  #[test]
+  fn echo_session() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut reader = stream.try_clone().unwrap();
+      let _ = io::copy(&mut reader, &mut stream);
+    });
+
+    let mut names = PacketNames::new();
+    names.insert(PacketId::with_subcodes(0xF3, &[0x00]).unwrap(), "CharacterList");
+
+    let codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let mut console = Console::connect(address, codec, names).unwrap();
+    let script = "send CharacterList 01\nsend 0E\nbogus\nquit\nsend 0E\n";
+
+    let mut output = Vec::new();
+    console.run(script.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(output.contains("-> CharacterList (C1 F3) 00 01\n<- CharacterList (C1 F3) 00 01"));
+    assert!(output.contains("-> ? (C1 0E)\n<- ? (C1 0E)"));
+    assert!(output.contains("error: unknown command 'bogus'"));
+    assert_eq!(output.matches("-> ").count(), 2);
+  }
+}
@@ -0,0 +1,146 @@
+//! Serialization of lists preceded by their number of entries.
+//!
+//! Lists in packets (e.g of servers or characters) are preceded by a count
+//! of a specific width and byte order, instead of the length prefix used by
+//! bincode. Use with `#[serde(with = "...::serialize::counted::by_u8")]` on a
+//! `Vec` field.
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::{self, SerializeTuple};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// The maximum number of entries allocated for ahead of decoding them.
+const PREALLOCATED: usize = 64;
+
+macro_rules! counted {
+  ($($name:ident: $size:expr, $big_endian:expr;)*) => {
+    $(
+      #[doc = concat!("Serialization of lists counted ", stringify!($name), ".")]
+      pub mod $name {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        /// Serializes a list, preceded by its count.
+        pub fn serialize<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+        where
+          T: Serialize,
+          S: Serializer,
+        {
+          super::serialize(values, $size, $big_endian, serializer)
+        }
+
+        /// Deserializes a list, preceded by its count.
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+        where
+          T: Deserialize<'de>,
+          D: Deserializer<'de>,
+        {
+          super::deserialize($size, $big_endian, deserializer)
+        }
+      }
+    )*
+  };
+}
+
+counted! {
+  by_u8: 1, false;
+  by_u16_le: 2, false;
+  by_u16_be: 2, true;
+}
+
+fn serialize<T: Serialize, S: Serializer>(
+  values: &[T],
+  size: usize,
+  big_endian: bool,
+  serializer: S,
+) -> Result<S::Ok, S::Error> {
+  let count = values.len() as u64;
+  if count >> (8 * size) != 0 {
+    let message = format!("{} entries do not fit in a count of {} bytes", count, size);
+    return Err(<S::Error as ser::Error>::custom(message));
+  }
+
+  let bytes = count.to_le_bytes();
+  let mut tuple = serializer.serialize_tuple(size + values.len())?;
+  for index in 0..size {
+    let index = if big_endian { size - 1 - index } else { index };
+    tuple.serialize_element(&bytes[index])?;
+  }
+
+  for value in values {
+    tuple.serialize_element(value)?;
+  }
+  tuple.end()
+}
+
+fn deserialize<'de, T, D>(
+  size: usize,
+  big_endian: bool,
+  deserializer: D,
+) -> Result<Vec<T>, D::Error>
+where
+  T: Deserialize<'de>,
+  D: Deserializer<'de>,
+{
+  // The number of entries is unknown until the count has been read
+  let visitor = CountedVisitor { size, big_endian, entry: PhantomData };
+  deserializer.deserialize_tuple(usize::MAX, visitor)
+}
+
+struct CountedVisitor<T> {
+  size: usize,
+  big_endian: bool,
+  entry: PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for CountedVisitor<T> {
+  type Value = Vec<T>;
+
+  fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    write!(fmt, "a list preceded by a count of {} bytes", self.size)
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<T>, A::Error> {
+    let mut count = 0;
+    for index in 0..self.size {
+      let byte: u8 = seq
+        .next_element()?
+        .ok_or_else(|| de::Error::invalid_length(index, &self))?;
+
+      let shift = if self.big_endian { self.size - 1 - index } else { index };
+      count |= usize::from(byte) << (8 * shift);
+    }
+
+    let mut values = Vec::with_capacity(count.min(PREALLOCATED));
+    for index in 0..count {
+      let value = seq
+        .next_element()?
+        .ok_or_else(|| de::Error::invalid_length(self.size + index, &self))?;
+      values.push(value);
+    }
+    Ok(values)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use bincode::Options;
+  use serde::{Deserialize, Serialize};
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq)]
+  struct Servers {
+    #[serde(with = "super::by_u16_be")]
+    codes: Vec<u16>,
+    trailing: u8,
+  }
+
+  #[test]
+  fn counted() {
+    let value = Servers { codes: vec![1, 2], trailing: 0xFF };
+    let bytes = super::super::options().serialize(&value).unwrap();
+    assert_eq!(bytes, [0x00, 0x02, 0x01, 0x00, 0x02, 0x00, 0xFF]);
+    assert_eq!(super::super::options().deserialize::<Servers>(&bytes).unwrap(), value);
+    assert!(super::super::options().deserialize::<Servers>(&bytes[..4]).is_err());
+  }
+}
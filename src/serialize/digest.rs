@@ -0,0 +1,137 @@
+use super::BytesFixed;
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Size of a digest (e.g MD5 hashes, serials or hardware identifiers).
+const DIGEST_SIZE: usize = 16;
+
+/// A 16-byte digest, stored as raw bytes.
+///
+/// Launchers & anti-cheat packets commonly carry MD5 hashes or hardware
+/// identifiers in this form.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Digest(pub [u8; DIGEST_SIZE]);
+
+impl Digest {
+  /// Parses a digest from a hexadecimal string (of either case).
+  pub fn from_hex(hex: &str) -> Option<Self> {
+    if hex.len() != DIGEST_SIZE * 2 || !hex.is_ascii() {
+      return None;
+    }
+
+    let mut bytes = [0; DIGEST_SIZE];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+      let pair = std::str::from_utf8(pair).ok()?;
+      *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(Digest(bytes))
+  }
+
+  /// Returns the digest as a lowercase hexadecimal string.
+  pub fn to_hex(&self) -> String { self.to_string() }
+
+  /// Returns the digest's bytes.
+  pub fn as_bytes(&self) -> &[u8; DIGEST_SIZE] { &self.0 }
+}
+
+impl From<[u8; DIGEST_SIZE]> for Digest {
+  fn from(bytes: [u8; DIGEST_SIZE]) -> Self { Digest(bytes) }
+}
+
+impl From<Digest> for [u8; DIGEST_SIZE] {
+  fn from(digest: Digest) -> Self { digest.0 }
+}
+
+impl fmt::Display for Digest {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    self.0.iter().try_for_each(|byte| write!(fmt, "{:02x}", byte))
+  }
+}
+
+impl Serialize for Digest {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    BytesFixed(self.0).serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Digest {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    BytesFixed::<DIGEST_SIZE>::deserialize(deserializer).map(|field| Digest(field.0))
+  }
+}
+
+/// A 16-byte digest, stored as hexadecimal text in an `N` byte field.
+///
+/// The text is padded with NUL bytes, so the field must be at least 32 bytes.
+/// Both cases are accepted when decoding, but lowercase is always encoded.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HexDigest<const N: usize>(pub Digest);
+
+impl<const N: usize> From<Digest> for HexDigest<N> {
+  fn from(digest: Digest) -> Self { HexDigest(digest) }
+}
+
+impl<const N: usize> From<[u8; DIGEST_SIZE]> for HexDigest<N> {
+  fn from(bytes: [u8; DIGEST_SIZE]) -> Self { HexDigest(Digest(bytes)) }
+}
+
+impl<const N: usize> fmt::Display for HexDigest<N> {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result { self.0.fmt(fmt) }
+}
+
+impl<const N: usize> Serialize for HexDigest<N> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let hex = self.0.to_hex();
+    if hex.len() > N {
+      let message = format!("digest does not fit in {} bytes", N);
+      return Err(<S::Error as ser::Error>::custom(message));
+    }
+
+    let mut field = [0; N];
+    field[..hex.len()].copy_from_slice(hex.as_bytes());
+    BytesFixed(field).serialize(serializer)
+  }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for HexDigest<N> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let field = BytesFixed::<N>::deserialize(deserializer)?;
+    let end = field.0.iter().position(|&byte| byte == 0).unwrap_or(N);
+
+    std::str::from_utf8(&field.0[..end])
+      .ok()
+      .and_then(Digest::from_hex)
+      .map(HexDigest)
+      .ok_or_else(|| <D::Error as de::Error>::custom("invalid hexadecimal digest"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bincode::Options;
+
+  const HEX: &str = "00112233445566778899AABBCCDDEEFF";
+
+  #[test]
+  fn raw_and_hex() {
+    let digest = Digest::from_hex(HEX).unwrap();
+    assert_eq!(digest.0[15], 0xFF);
+    assert_eq!(digest.to_hex(), HEX.to_lowercase());
+    assert_eq!(Digest::from_hex("0011"), None);
+
+    let bytes = crate::serialize::options().serialize(&digest).unwrap();
+    assert_eq!(bytes, digest.0);
+
+    let mut field = HEX.as_bytes().to_vec();
+    field.push(0);
+    let decoded: HexDigest<33> = crate::serialize::options().deserialize(&field).unwrap();
+    assert_eq!(decoded.0, digest);
+
+    let bytes = crate::serialize::options().serialize(&decoded).unwrap();
+    assert_eq!(bytes, [HEX.to_lowercase().as_bytes(), &[0]].concat());
+    assert!(crate::serialize::options()
+      .serialize(&HexDigest::<16>(digest))
+      .is_err());
+  }
+}
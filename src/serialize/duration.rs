@@ -0,0 +1,47 @@
+//! Serialization of millisecond durations.
+//!
+//! Cooldowns and latencies are sent as a little endian `u32` of milliseconds.
+//! Use with `#[serde(with = "...::serialize::duration")]` on a `Duration`
+//! field.
+
+use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// Serializes a duration as milliseconds.
+pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+  let millis = u32::try_from(duration.as_millis())
+    .map_err(|_| ser::Error::custom("duration exceeds u32 milliseconds"))?;
+  millis.to_le_bytes().serialize(serializer)
+}
+
+/// Deserializes milliseconds as a duration.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+  let bytes = <[u8; 4]>::deserialize(deserializer)?;
+  Ok(Duration::from_millis(u64::from(u32::from_le_bytes(bytes))))
+}
+
+#[cfg(test)]
+mod tests {
+  use bincode::Options;
+  use serde::{Deserialize, Serialize};
+  use std::time::Duration;
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq)]
+  struct Cooldown(#[serde(with = "super")] Duration);
+
+  #[test]
+  fn millis() {
+    let cooldown = Cooldown(Duration::from_millis(0x0102_0304));
+    let bytes = super::super::options().serialize(&cooldown).unwrap();
+    assert_eq!(bytes, [0x04, 0x03, 0x02, 0x01]);
+    assert_eq!(super::super::options().deserialize::<Cooldown>(&bytes).unwrap(), cooldown);
+  }
+
+  #[test]
+  fn overflow() {
+    let cooldown = Cooldown(Duration::from_millis(u64::from(u32::MAX) + 1));
+    let error = super::super::options().serialize(&cooldown).unwrap_err();
+    assert_eq!(error.to_string(), "duration exceeds u32 milliseconds");
+  }
+}
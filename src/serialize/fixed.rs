@@ -0,0 +1,81 @@
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// A fixed-size field of raw bytes.
+///
+/// This is intended for fields that are not text (e.g security codes or
+/// hardware identifiers), so all bytes are kept, including any after a NUL.
+/// Unlike arrays, it supports any size.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BytesFixed<const N: usize>(pub [u8; N]);
+
+impl<const N: usize> BytesFixed<N> {
+  /// Returns the field's bytes.
+  pub fn as_bytes(&self) -> &[u8; N] { &self.0 }
+
+  /// Returns the field's bytes as a vector.
+  pub fn to_vec(&self) -> Vec<u8> { self.0.to_vec() }
+}
+
+impl<const N: usize> Default for BytesFixed<N> {
+  fn default() -> Self { BytesFixed([0; N]) }
+}
+
+impl<const N: usize> From<[u8; N]> for BytesFixed<N> {
+  fn from(bytes: [u8; N]) -> Self { BytesFixed(bytes) }
+}
+
+impl<const N: usize> Serialize for BytesFixed<N> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut tuple = serializer.serialize_tuple(N)?;
+    for byte in self.0.iter() {
+      tuple.serialize_element(byte)?;
+    }
+    tuple.end()
+  }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for BytesFixed<N> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct BytesVisitor<const N: usize>;
+
+    impl<'de, const N: usize> Visitor<'de> for BytesVisitor<N> {
+      type Value = BytesFixed<N>;
+
+      fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result { write!(fmt, "{} bytes", N) }
+
+      fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = [0; N];
+        for (index, byte) in bytes.iter_mut().enumerate() {
+          *byte = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(index, &self))?;
+        }
+        Ok(BytesFixed(bytes))
+      }
+    }
+
+    deserializer.deserialize_tuple(N, BytesVisitor::<N>)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bincode::Options;
+
+  #[test]
+  fn keeps_all_bytes() {
+    let field = BytesFixed([0x31, 0x00, 0x32, 0x33, 0x00, 0x00, 0x00, 0x00, 0x34, 0x00]);
+    let bytes = crate::serialize::options().serialize(&field).unwrap();
+    assert_eq!(&bytes[..], &field.0[..]);
+
+    let decoded: BytesFixed<10> = crate::serialize::options().deserialize(&bytes).unwrap();
+    assert_eq!(decoded, field);
+    assert!(crate::serialize::options()
+      .deserialize::<BytesFixed<10>>(&bytes[..9])
+      .is_err());
+  }
+}
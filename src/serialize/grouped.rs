@@ -0,0 +1,89 @@
+use super::options;
+use bincode::Options;
+use crate::{Packet, PacketKind};
+use serde::Serialize;
+use std::io;
+
+/// A builder for C2 packets with a list of records, preceded by their count.
+///
+/// The packet's content consists of a code, a subcode and a count byte,
+/// followed by the serialized records.
+#[derive(Clone, Debug)]
+pub struct GroupedBuilder {
+  packet: Packet,
+  records: Vec<u8>,
+  count: u8,
+}
+
+impl GroupedBuilder {
+  /// Creates a new builder for a code and subcode.
+  pub fn new(code: u8, subcode: u8) -> Self {
+    let mut packet = Packet::new(PacketKind::C2, code);
+    packet.append(&[subcode]);
+
+    GroupedBuilder {
+      packet,
+      records: Vec::new(),
+      count: 0,
+    }
+  }
+
+  /// Returns the number of records.
+  pub fn count(&self) -> u8 { self.count }
+
+  /// Appends a record.
+  ///
+  /// This fails if the count or the packet's size would overflow.
+  pub fn push<T: Serialize>(&mut self, record: &T) -> Result<&mut Self, io::Error> {
+    let count = self
+      .count
+      .checked_add(1)
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "too many records"))?;
+
+    // The size limit accounts for the count byte and any previous records
+    let remaining = PacketKind::C2.max_size() - self.packet.len() - 1 - self.records.len();
+    let content = options()
+      .with_limit(remaining as u64)
+      .serialize(record)
+      .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+    self.records.extend_from_slice(&content);
+    self.count = count;
+    Ok(self)
+  }
+
+  /// Builds the packet.
+  pub fn build(self) -> Packet {
+    let mut packet = self.packet;
+    packet.append(&[self.count]);
+    packet.append(&self.records);
+    packet
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Serialize)]
+  struct Server {
+    code: u16,
+    load: u8,
+  }
+
+  #[test]
+  fn grouped_records() {
+    let mut builder = Packet::grouped_builder(0xF4, 0x06);
+    builder
+      .push(&Server { code: 0, load: 10 })
+      .and_then(|builder| builder.push(&Server { code: 1, load: 20 }))
+      .unwrap();
+    assert_eq!(builder.count(), 2);
+
+    let packet = builder.build();
+    assert_eq!(
+      packet.to_bytes(),
+      [0xC2, 0x00, 0x0C, 0xF4, 0x06, 0x02, 0x00, 0x00, 0x0A, 0x01, 0x00, 0x14]
+    );
+  }
+}
@@ -0,0 +1,148 @@
+use bincode::Options;
+use crate::{Diagnostic, Packet, PacketType};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{self, Read};
+
+pub use self::digest::{Digest, HexDigest};
+pub use self::fixed::BytesFixed;
+pub use self::grouped::GroupedBuilder;
+pub use self::scaled::Scaled;
+
+pub mod counted;
+pub mod duration;
+pub mod tick;
+pub mod trailing;
+pub mod width;
+
+mod digest;
+mod fixed;
+mod grouped;
+mod scaled;
+
+/// A trait for encoding types to a packet.
+pub trait PacketEncodable: PacketType {
+  /// Creates a packet from the type.
+  fn to_packet(&self) -> Result<Packet, io::Error> { self.to_packet_with(&[]) }
+
+  /// Creates a packet from the type, with runtime subcodes following the
+  /// type's own (e.g for packet families whose first byte varies).
+  fn to_packet_with(&self, subcodes: &[u8]) -> Result<Packet, io::Error>;
+}
+
+/// A trait for decoding types from a packet.
+pub trait PacketDecodable: PacketType + Sized {
+  /// Creates the type from a packet.
+  fn from_packet(packet: &Packet) -> Result<Self, io::Error> { Self::from_packet_with(packet, &[]) }
+
+  /// Creates the type from a packet, expecting runtime subcodes following the
+  /// type's own.
+  fn from_packet_with(packet: &Packet, subcodes: &[u8]) -> Result<Self, io::Error>;
+}
+
+/// Implement packet encoding for all serializable types.
+impl<T> PacketEncodable for T
+where
+  T: PacketType + Serialize,
+{
+  /// Creates a packet from an encodable type.
+  fn to_packet_with(&self, subcodes: &[u8]) -> Result<Packet, io::Error> {
+    let mut packet = Packet::new(T::kind(), T::CODE);
+    packet.append(T::subcodes());
+    packet.append(subcodes);
+
+    let content = options()
+      .with_limit((T::kind().max_size() - packet.len()) as u64)
+      .serialize(&self)
+      .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    packet.append(&content);
+    Ok(packet)
+  }
+}
+
+/// Implement packet decoding for all deserializeable types.
+impl<T> PacketDecodable for T
+where
+  T: PacketType + DeserializeOwned,
+{
+  /// Creates a decodable type from a packet.
+  fn from_packet_with(packet: &Packet, subcodes: &[u8]) -> Result<Self, io::Error> {
+    if packet.kind() == T::kind() && packet.code() == T::CODE {
+      if packet.len() < T::min_size() {
+        let message = format!(
+          "packet size {} is below the minimum {}",
+          packet.len(),
+          T::min_size()
+        );
+        let diagnostic = Diagnostic::new(packet.len(), message);
+        let diagnostic = diagnostic.in_packet(T::kind(), T::identifier().as_bytes());
+        return Err(io::Error::new(io::ErrorKind::InvalidData, diagnostic));
+      }
+
+      let codes = T::subcodes().iter().chain(subcodes.iter());
+      let length = T::subcodes().len() + subcodes.len();
+
+      if length <= packet.data().len() && codes.zip(packet.data().iter()).all(|(x, y)| x == y) {
+        // TODO: Throw error if packet size do not match?
+        let content = &packet.data()[length..];
+        let value: T = options().deserialize(content).map_err(|error| {
+          let offset = packet.kind().offset() + length + failure_offset::<T>(content);
+          let diagnostic = Diagnostic::new(offset, error.to_string());
+          let diagnostic = diagnostic.in_packet(T::kind(), T::identifier().as_bytes());
+          io::Error::new(io::ErrorKind::InvalidData, diagnostic)
+        })?;
+
+        value
+          .validate()
+          .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        return Ok(value);
+      }
+    }
+
+    Err(io::Error::other("codes differ from the type's"))
+  }
+}
+
+/// Returns the offset in packet content at which decoding a type fails.
+///
+/// This decodes the content again, through a reader tracking the bytes
+/// consumed, so the successful path is unaffected.
+fn failure_offset<T: DeserializeOwned>(content: &[u8]) -> usize {
+  let mut reader = ContentReader { content, position: 0 };
+  let _ = options().deserialize_from::<_, T>(&mut reader);
+  reader.position
+}
+
+/// A reader of packet content, tracking the bytes consumed.
+struct ContentReader<'a> {
+  content: &'a [u8],
+  position: usize,
+}
+
+impl Read for ContentReader<'_> {
+  fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+    let size = (&self.content[self.position..]).read(buffer)?;
+    self.position += size;
+    Ok(size)
+  }
+
+  fn read_exact(&mut self, buffer: &mut [u8]) -> io::Result<()> {
+    // Incomplete values are not consumed, so the offset is at their start
+    let end = self.position + buffer.len();
+    if end > self.content.len() {
+      return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    buffer.copy_from_slice(&self.content[self.position..end]);
+    self.position = end;
+    Ok(())
+  }
+}
+
+/// Returns the bincode options used for packet content.
+fn options() -> impl Options {
+  bincode::DefaultOptions::new()
+    .with_fixint_encoding()
+    .with_native_endian()
+    .allow_trailing_bytes()
+}
@@ -0,0 +1,92 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// An integer representing a fraction of `MAX`.
+///
+/// Viewport packets encode ratios such as HP, MP & AG this way (e.g `u8`
+/// values from 0–250 meaning 0–100%). The raw value is kept, so it always
+/// round-trips, and values above `MAX` are rejected when decoding.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Scaled<T, const MAX: u32>(T);
+
+impl<T, const MAX: u32> Scaled<T, MAX>
+where
+  T: Copy + Into<u32> + TryFrom<u32>,
+{
+  /// Creates a scaled value from a raw integer, if it is within range.
+  pub fn new(raw: T) -> Option<Self> {
+    if raw.into() <= MAX {
+      Some(Scaled(raw))
+    } else {
+      None
+    }
+  }
+
+  /// Creates a scaled value from a ratio, clamped to `0.0..=1.0`.
+  pub fn from_ratio(ratio: f32) -> Self {
+    let raw = (ratio.clamp(0.0, 1.0) * MAX as f32).round() as u32;
+    Scaled(T::try_from(raw.min(MAX)).ok().expect("MAX must fit the raw type"))
+  }
+
+  /// Creates a scaled value from a percentage, clamped to `0.0..=100.0`.
+  pub fn from_percent(percent: f32) -> Self { Self::from_ratio(percent / 100.0) }
+
+  /// Returns the raw integer.
+  pub fn raw(&self) -> T { self.0 }
+
+  /// Returns the value as a ratio within `0.0..=1.0`.
+  pub fn ratio(&self) -> f32 {
+    if MAX == 0 {
+      0.0
+    } else {
+      self.0.into() as f32 / MAX as f32
+    }
+  }
+
+  /// Returns the value as a percentage within `0.0..=100.0`.
+  pub fn percent(&self) -> f32 { self.ratio() * 100.0 }
+}
+
+impl<T: Serialize, const MAX: u32> Serialize for Scaled<T, MAX> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    self.0.serialize(serializer)
+  }
+}
+
+impl<'de, T, const MAX: u32> Deserialize<'de> for Scaled<T, MAX>
+where
+  T: Copy + Into<u32> + TryFrom<u32> + Deserialize<'de> + fmt::Display,
+{
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let raw = T::deserialize(deserializer)?;
+    Self::new(raw).ok_or_else(|| {
+      let message = format!("scaled value {} exceeds {}", raw, MAX);
+      <D::Error as de::Error>::custom(message)
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bincode::Options;
+
+  type Percentage = Scaled<u8, 250>;
+
+  #[test]
+  fn ratios() {
+    let health = Percentage::from_percent(50.0);
+    assert_eq!(health.raw(), 125);
+    assert_eq!(health.ratio(), 0.5);
+    assert_eq!(Percentage::from_ratio(2.0).raw(), 250);
+    assert_eq!(Percentage::new(251), None);
+
+    let bytes = crate::serialize::options().serialize(&health).unwrap();
+    assert_eq!(bytes, [125]);
+    assert_eq!(crate::serialize::options().deserialize::<Percentage>(&bytes).unwrap(), health);
+    assert!(crate::serialize::options()
+      .deserialize::<Percentage>(&[251])
+      .is_err());
+  }
+}
@@ -0,0 +1,41 @@
+//! Serialization of client ticks.
+//!
+//! The client reports its uptime (`GetTickCount`) as a wrapping, little endian
+//! `u32` of milliseconds. Use with `#[serde(with = "...::serialize::tick")]`
+//! on a `Duration` field.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::time::Duration;
+
+/// Serializes a duration as a wrapping millisecond tick.
+pub fn serialize<S: Serializer>(tick: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+  // The tick count wraps around after ~49.7 days, just like the client's
+  let millis = tick.as_millis() as u32;
+  millis.to_le_bytes().serialize(serializer)
+}
+
+/// Deserializes a millisecond tick as a duration.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+  let bytes = <[u8; 4]>::deserialize(deserializer)?;
+  Ok(Duration::from_millis(u64::from(u32::from_le_bytes(bytes))))
+}
+
+#[cfg(test)]
+mod tests {
+  use bincode::Options;
+  use serde::{Deserialize, Serialize};
+  use std::time::Duration;
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq)]
+  struct Ping(#[serde(with = "super")] Duration);
+
+  #[test]
+  fn tick_wraps() {
+    let tick = Duration::from_millis(u64::from(u32::MAX) + 0x0102);
+    let bytes = super::super::options().serialize(&Ping(tick)).unwrap();
+    assert_eq!(bytes, [0x01, 0x01, 0x00, 0x00]);
+
+    let ping: Ping = super::super::options().deserialize(&bytes).unwrap();
+    assert_eq!(ping, Ping(Duration::from_millis(0x0101)));
+  }
+}
@@ -0,0 +1,39 @@
+//! Serialization of optional trailing fields.
+//!
+//! Later protocol revisions may append fields to a packet, which are absent
+//! when received from an earlier revision. Such fields are marked
+//! `#[mu(default)]` along with
+//! `#[serde(with = "...::serialize::trailing")]`, and are decoded as
+//! `Default::default()` when the packet ends before them. Only the last
+//! fields of a packet may be optional.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Serializes a field as-is.
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+  T: Serialize,
+  S: Serializer,
+{
+  value.serialize(serializer)
+}
+
+/// Deserializes a field, or its default if the packet ends before it (or
+/// within it, since bincode does not tell the two apart).
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+  T: Deserialize<'de> + Default,
+  D: Deserializer<'de>,
+{
+  match T::deserialize(deserializer) {
+    Err(ref error) if is_eof(error) => Ok(T::default()),
+    result => result,
+  }
+}
+
+/// Returns whether decoding failed since the content ended.
+///
+/// The error is opaque, so bincode's description of a failed read is matched;
+/// packet content is always read from a slice, which only fails at its end.
+fn is_eof<E: fmt::Display>(error: &E) -> bool { error.to_string().starts_with("io error:") }
@@ -0,0 +1,141 @@
+//! Serialization of integers with an unusual width.
+//!
+//! Some packets contain integers of 3, 5, 6 or 7 bytes (e.g experience
+//! deltas and aggregate stats in custom packets), or big endian integers
+//! (e.g a player's index). Each module serializes an integer as exactly that
+//! many bytes, in a specific byte order. Use with
+//! `#[serde(with = "...::serialize::width::u24_le")]` on an integer field.
+//!
+//! Values too large for the width are rejected when serializing.
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::{self, SerializeTuple};
+use serde::{Deserializer, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+
+macro_rules! width {
+  ($($name:ident: $size:expr, $big_endian:expr;)*) => {
+    $(
+      #[doc = concat!("Serialization of ", stringify!($name), " integers.")]
+      pub mod $name {
+        use serde::{Deserializer, Serializer};
+        use std::convert::TryFrom;
+
+        /// Serializes an integer with the module's width.
+        pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+        where
+          T: Copy + Into<u64>,
+          S: Serializer,
+        {
+          super::serialize((*value).into(), $size, $big_endian, serializer)
+        }
+
+        /// Deserializes an integer with the module's width.
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+        where
+          T: TryFrom<u64>,
+          D: Deserializer<'de>,
+        {
+          super::deserialize($size, $big_endian, deserializer)
+        }
+      }
+    )*
+  };
+}
+
+width! {
+  u16_be: 2, true;
+  u24_le: 3, false;
+  u24_be: 3, true;
+  u32_be: 4, true;
+  u40_le: 5, false;
+  u40_be: 5, true;
+  u48_le: 6, false;
+  u48_be: 6, true;
+  u56_le: 7, false;
+  u56_be: 7, true;
+}
+
+fn serialize<S: Serializer>(
+  value: u64,
+  size: usize,
+  big_endian: bool,
+  serializer: S,
+) -> Result<S::Ok, S::Error> {
+  if value >> (8 * size) != 0 {
+    let message = format!("{} does not fit in {} bytes", value, size);
+    return Err(<S::Error as ser::Error>::custom(message));
+  }
+
+  let bytes = value.to_le_bytes();
+  let mut tuple = serializer.serialize_tuple(size)?;
+  for index in 0..size {
+    let index = if big_endian { size - 1 - index } else { index };
+    tuple.serialize_element(&bytes[index])?;
+  }
+  tuple.end()
+}
+
+fn deserialize<'de, T, D>(size: usize, big_endian: bool, deserializer: D) -> Result<T, D::Error>
+where
+  T: TryFrom<u64>,
+  D: Deserializer<'de>,
+{
+  let value = deserializer.deserialize_tuple(size, WidthVisitor { size, big_endian })?;
+  T::try_from(value).map_err(|_| {
+    let message = format!("{} does not fit in the field's type", value);
+    <D::Error as de::Error>::custom(message)
+  })
+}
+
+struct WidthVisitor {
+  size: usize,
+  big_endian: bool,
+}
+
+impl<'de> Visitor<'de> for WidthVisitor {
+  type Value = u64;
+
+  fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    write!(fmt, "an integer of {} bytes", self.size)
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<u64, A::Error> {
+    let mut value = 0;
+    for index in 0..self.size {
+      let byte: u8 = seq
+        .next_element()?
+        .ok_or_else(|| de::Error::invalid_length(index, &self))?;
+
+      let shift = if self.big_endian { self.size - 1 - index } else { index };
+      value |= u64::from(byte) << (8 * shift);
+    }
+    Ok(value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use bincode::Options;
+  use serde::{Deserialize, Serialize};
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq)]
+  struct Experience {
+    #[serde(with = "super::u24_le")]
+    delta: u32,
+    #[serde(with = "super::u40_be")]
+    total: u64,
+  }
+
+  #[test]
+  fn widths() {
+    let value = Experience { delta: 0x010203, total: 0x0102030405 };
+    let bytes = super::super::options().serialize(&value).unwrap();
+    assert_eq!(bytes, [0x03, 0x02, 0x01, 0x01, 0x02, 0x03, 0x04, 0x05]);
+    assert_eq!(super::super::options().deserialize::<Experience>(&bytes).unwrap(), value);
+
+    let overflow = Experience { delta: 0x01000000, total: 0 };
+    assert!(super::super::options().serialize(&overflow).is_err());
+  }
+}
@@ -0,0 +1,83 @@
+//! Graceful shutdown of framed connections.
+
+use futures::{try_ready, Async, AsyncSink, Future, Poll, Sink};
+use std::io;
+use tokio_io::codec::{Encoder, Framed};
+use tokio_io::AsyncWrite;
+
+/// An extension for closing framed connections gracefully.
+pub trait GracefulClose<T, C: Encoder> {
+  /// Returns a future which flushes all queued packets, optionally sends a
+  /// final notification (e.g a disconnect packet), and then shuts down the
+  /// connection, resolving to the underlying I/O object.
+  fn graceful_close(self, notification: Option<C::Item>) -> Close<T, C>;
+}
+
+impl<T, C> GracefulClose<T, C> for Framed<T, C>
+where
+  T: AsyncWrite,
+  C: Encoder<Error = io::Error>,
+{
+  fn graceful_close(self, notification: Option<C::Item>) -> Close<T, C> {
+    Close {
+      framed: Some(self),
+      notification,
+    }
+  }
+}
+
+/// A future closing a framed connection.
+#[must_use = "futures do nothing unless polled"]
+pub struct Close<T, C: Encoder> {
+  framed: Option<Framed<T, C>>,
+  notification: Option<C::Item>,
+}
+
+impl<T, C> Future for Close<T, C>
+where
+  T: AsyncWrite,
+  C: Encoder<Error = io::Error>,
+{
+  type Item = T;
+  type Error = io::Error;
+
+  fn poll(&mut self) -> Poll<T, io::Error> {
+    let framed = self.framed.as_mut().expect("polled after completion");
+
+    while let Some(notification) = self.notification.take() {
+      if let AsyncSink::NotReady(notification) = framed.start_send(notification)? {
+        self.notification = Some(notification);
+        // Make room by flushing the queued packets first
+        try_ready!(framed.poll_complete());
+      }
+    }
+
+    // Complete all writes before shutting down, to avoid cutting off a frame
+    try_ready!(framed.poll_complete());
+    try_ready!(framed.get_mut().shutdown());
+
+    let framed = self.framed.take().unwrap();
+    Ok(Async::Ready(framed.into_inner()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Packet, PacketCodec, PacketCodecState};
+  use std::io::Cursor;
+  use tokio_io::AsyncRead;
+
+  #[test]
+  fn flush_and_notify() {
+    let codec = PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let mut framed = Cursor::new(Vec::new()).framed(codec);
+
+    let packet = Packet::from_bytes(&[0xC1, 0x04, 0x00, 0x01]).unwrap();
+    let notification = Packet::from_bytes(&[0xC1, 0x04, 0xF1, 0x02]).unwrap();
+    assert!(framed.start_send(packet).unwrap().is_ready());
+
+    let io = framed.graceful_close(Some(notification)).wait().unwrap();
+    assert_eq!(io.into_inner(), [0xC1, 0x04, 0x00, 0x01, 0xC1, 0x04, 0xF1, 0x02]);
+  }
+}
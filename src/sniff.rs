@@ -0,0 +1,241 @@
+//! Passive decoding of captured connections.
+//!
+//! A passive decoder consumes the bytes of both directions of a connection
+//! (e.g from a network tap or a packet capture), without taking part in it.
+//! Each direction's frames are decrypted with known keys, and their counters
+//! are tracked independently. Since a capture may start mid-stream, the
+//! decoder searches for the first valid frame, and adopts the counter of the
+//! first encrypted packet. Only ciphers and encryption are applied, so
+//! connections using obfuscation or trailers are not supported.
+
+use crate::header::PacketHeader;
+use crate::{Direction, Packet, PacketCodecState};
+
+/// An event of a passively decoded connection.
+///
+/// Directions are relative to the client, i.e outbound packets are sent by
+/// the client, and inbound packets by the server.
+#[derive(Clone, Debug)]
+pub enum Sniffed {
+  /// A packet was decoded.
+  Packet(Direction, Packet),
+  /// Bytes were discarded while searching for a valid frame.
+  Skipped(Direction, usize),
+  /// An encrypted packet's counter was not the one expected.
+  ///
+  /// The received counter is adopted, so only gaps are reported.
+  CounterGap {
+    /// The direction of the packet.
+    direction: Direction,
+    /// The counter expected.
+    expected: u8,
+    /// The counter received.
+    received: u8,
+  },
+}
+
+/// The decoding state of one direction.
+#[derive(Debug)]
+struct Stream {
+  state: PacketCodecState,
+  buffer: Vec<u8>,
+  counter: Option<u8>,
+  synced: bool,
+}
+
+impl Stream {
+  fn new(state: PacketCodecState) -> Self {
+    Stream {
+      state,
+      buffer: Vec::new(),
+      counter: None,
+      synced: false,
+    }
+  }
+}
+
+/// The outcome of parsing a frame.
+enum Frame {
+  Valid(Packet, Option<u8>, usize),
+  Incomplete,
+  Invalid,
+}
+
+/// A decoder of both directions of a captured connection.
+#[derive(Debug)]
+pub struct PassiveDecoder {
+  streams: [Stream; 2],
+}
+
+impl PassiveDecoder {
+  /// Creates a passive decoder, from the states a server and a client would
+  /// use to decrypt the packets they receive.
+  pub fn new(outbound: PacketCodecState, inbound: PacketCodecState) -> Self {
+    PassiveDecoder {
+      streams: [Stream::new(outbound), Stream::new(inbound)],
+    }
+  }
+
+  /// Creates a passive decoder using the default keys of the client and the
+  /// server.
+  pub fn with_default_keys() -> Self {
+    let (_, outbound) = PacketCodecState::server_default();
+    let (_, inbound) = PacketCodecState::client_default();
+    Self::new(outbound, inbound)
+  }
+
+  /// Consumes captured bytes of a direction, returning the events they
+  /// complete.
+  pub fn feed(&mut self, direction: Direction, bytes: &[u8]) -> Vec<Sniffed> {
+    let stream = &mut self.streams[Self::index(direction)];
+    stream.buffer.extend_from_slice(bytes);
+
+    let mut events = Vec::new();
+    let mut offset = 0;
+
+    while offset < stream.buffer.len() {
+      let (start, packet, counter, size) = if stream.synced {
+        match Self::frame(&mut stream.state, &stream.buffer[offset..]) {
+          Frame::Valid(packet, counter, size) => (offset, packet, counter, size),
+          Frame::Incomplete => break,
+          Frame::Invalid => {
+            stream.synced = false;
+            continue;
+          },
+        }
+      } else {
+        // A valid looking header may be a coincidence, so the first complete
+        // and valid frame is used, rather than waiting on the first header.
+        let mut pending = None;
+        let mut found = None;
+
+        for start in offset..stream.buffer.len() {
+          match Self::frame(&mut stream.state, &stream.buffer[start..]) {
+            Frame::Valid(packet, counter, size) => {
+              found = Some((start, packet, counter, size));
+              break;
+            },
+            Frame::Incomplete => {
+              pending.get_or_insert(start);
+            },
+            Frame::Invalid => (),
+          }
+        }
+
+        match found {
+          Some(frame) => frame,
+          None => {
+            let end = pending.unwrap_or(stream.buffer.len());
+            if end > offset {
+              events.push(Sniffed::Skipped(direction, end - offset));
+            }
+            offset = end;
+            break;
+          },
+        }
+      };
+
+      if start > offset {
+        events.push(Sniffed::Skipped(direction, start - offset));
+      }
+
+      if let Some(received) = counter {
+        match stream.counter {
+          Some(expected) if expected != received => events.push(Sniffed::CounterGap {
+            direction,
+            expected,
+            received,
+          }),
+          _ => (),
+        }
+        stream.counter = Some(received.wrapping_add(1));
+      }
+
+      stream.synced = true;
+      events.push(Sniffed::Packet(direction, packet));
+      offset = start + size;
+    }
+
+    stream.buffer.drain(..offset);
+    events
+  }
+
+  /// Parses the frame at the start of an input.
+  fn frame(state: &mut PacketCodecState, input: &[u8]) -> Frame {
+    match PacketHeader::peek(input) {
+      Ok(Some(header)) if input.len() >= header.size() => {
+        match state.parse(&input[..header.size()]) {
+          Ok((packet, _, counter)) => Frame::Valid(packet, counter, header.size()),
+          Err(_) => Frame::Invalid,
+        }
+      },
+      Ok(_) => Frame::Incomplete,
+      Err(_) => Frame::Invalid,
+    }
+  }
+
+  /// Returns whether a direction is aligned with its frames, i.e whether the
+  /// last bytes consumed were part of a valid frame.
+  pub fn is_synced(&self, direction: Direction) -> bool {
+    self.streams[Self::index(direction)].synced
+  }
+
+  fn index(direction: Direction) -> usize {
+    match direction {
+      Direction::Outbound => 0,
+      Direction::Inbound => 1,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{crypto, PacketKind, XOR_CIPHER};
+
+  fn packets(events: &[Sniffed]) -> Vec<&Packet> {
+    events
+      .iter()
+      .filter_map(|event| match event {
+        Sniffed::Packet(_, packet) => Some(packet),
+        _ => None,
+      })
+      .collect()
+  }
+
+  #[test]
+  fn mid_stream() {
+    let mut decoder = PassiveDecoder::with_default_keys();
+    let mut packet = Packet::new(PacketKind::C1, 0xF1);
+    packet.append(&[0x01, 0x02, 0x03]);
+
+    // The capture starts within a frame, after counters 0..5
+    let mut capture = packet.to_bytes_ex(Some(&XOR_CIPHER), Some((&crypto::CLIENT, 5)));
+    capture = capture.split_off(4);
+    capture.extend(packet.to_bytes_ex(Some(&XOR_CIPHER), Some((&crypto::CLIENT, 6))));
+    capture.extend(packet.to_bytes_ex(Some(&XOR_CIPHER), Some((&crypto::CLIENT, 8))));
+
+    let (first, second) = capture.split_at(20);
+    let mut events = decoder.feed(Direction::Outbound, first);
+    events.extend(decoder.feed(Direction::Outbound, second));
+
+    let skipped = events.iter().map(|event| match event {
+      Sniffed::Skipped(_, count) => *count,
+      _ => 0,
+    });
+    assert_eq!(skipped.sum::<usize>(), 9);
+    assert_eq!(packets(&events).len(), 2);
+    assert!(packets(&events).iter().all(|decoded| decoded.data() == packet.data()));
+    assert!(events.iter().any(|event| matches!(event, Sniffed::CounterGap {
+      expected: 7,
+      received: 8,
+      ..
+    })));
+    assert!(decoder.is_synced(Direction::Outbound));
+
+    // The other direction is unaffected
+    let reply = Packet::from_bytes(&[0xC1, 0x05, 0xF1, 0x00, 0x01]).unwrap();
+    let events = decoder.feed(Direction::Inbound, &reply.to_bytes());
+    assert_eq!(packets(&events)[0].data(), reply.data());
+  }
+}
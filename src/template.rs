@@ -0,0 +1,352 @@
+//! Packet templates, defined in TOML and instantiated with runtime values.
+//!
+//! This allows test scenarios to be written as data files. Each table
+//! defines a template, with its code, optional subcode and fields in order:
+//!
+//! ```toml
+//! [login]
+//! kind = "C1"
+//! code = "F1"
+//! subcode = "01"
+//! fields = [
+//!   { name = "account", type = "string", size = 10 },
+//!   { name = "version", type = "bytes", size = 5, default = "31 30 34 30 34" },
+//!   { name = "tick", type = "u32" },
+//!   { type = "bytes", default = "00 00" },
+//! ]
+//! ```
+//!
+//! Integers are little-endian, unless their type ends with `be`. Strings are
+//! NUL-padded to their size. Fields without a name are constants, and must
+//! have a default. If no kind is specified, the smallest one is used. Whether
+//! a packet is encrypted is decided by the codec sending it.
+
+use crate::names::parse_byte;
+use crate::{Packet, PacketKind};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::{fs, io};
+
+/// A value of a template field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+  /// An integer.
+  Integer(u64),
+  /// A string.
+  Text(String),
+  /// Raw bytes.
+  Bytes(Vec<u8>),
+}
+
+impl From<u64> for Value {
+  fn from(value: u64) -> Self { Value::Integer(value) }
+}
+
+impl From<u32> for Value {
+  fn from(value: u32) -> Self { Value::Integer(value.into()) }
+}
+
+impl From<&str> for Value {
+  fn from(value: &str) -> Self { Value::Text(value.to_string()) }
+}
+
+impl From<String> for Value {
+  fn from(value: String) -> Self { Value::Text(value) }
+}
+
+impl From<&[u8]> for Value {
+  fn from(value: &[u8]) -> Self { Value::Bytes(value.to_vec()) }
+}
+
+impl From<Vec<u8>> for Value {
+  fn from(value: Vec<u8>) -> Self { Value::Bytes(value) }
+}
+
+/// Runtime values of template fields, by name.
+#[derive(Clone, Debug, Default)]
+pub struct Values(HashMap<String, Value>);
+
+impl Values {
+  /// Creates an empty set of values.
+  pub fn new() -> Self { Self::default() }
+
+  /// Sets the value of a field.
+  pub fn set<N: Into<String>, V: Into<Value>>(mut self, name: N, value: V) -> Self {
+    self.0.insert(name.into(), value.into());
+    self
+  }
+
+  /// Returns the value of a field.
+  pub fn get(&self, name: &str) -> Option<&Value> { self.0.get(name) }
+}
+
+/// The type of a template field.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FieldType {
+  U8,
+  U16,
+  U16be,
+  U32,
+  U32be,
+  U64,
+  U64be,
+  String,
+  Bytes,
+}
+
+/// The definition of a template field.
+#[derive(Clone, Debug, Deserialize)]
+struct Field {
+  name: Option<String>,
+  #[serde(rename = "type")]
+  kind: FieldType,
+  size: Option<usize>,
+  default: Option<toml::Value>,
+}
+
+/// The definition of a template.
+#[derive(Clone, Debug, Deserialize)]
+struct Definition {
+  kind: Option<String>,
+  code: String,
+  subcode: Option<String>,
+  #[serde(default)]
+  fields: Vec<Field>,
+}
+
+/// A packet template.
+#[derive(Clone, Debug)]
+pub struct Template {
+  kind: Option<PacketKind>,
+  codes: Vec<u8>,
+  fields: Vec<Field>,
+}
+
+impl Template {
+  /// Returns the template's code, followed by its subcode if any.
+  pub fn codes(&self) -> &[u8] { &self.codes }
+
+  /// Returns the names of the template's fields, in order.
+  pub fn fields(&self) -> impl Iterator<Item = &str> {
+    self.fields.iter().filter_map(|field| field.name.as_deref())
+  }
+
+  /// Instantiates a packet, using runtime values for its fields.
+  ///
+  /// Fields without a value use their default, and an error is returned if
+  /// they have none.
+  pub fn instantiate(&self, values: &Values) -> io::Result<Packet> {
+    let mut data = self.codes[1..].to_vec();
+    for field in &self.fields {
+      let value = match field.name.as_deref().and_then(|name| values.get(name)) {
+        Some(value) => value.clone(),
+        None => field
+          .default
+          .as_ref()
+          .map(|default| default_value(field, default))
+          .transpose()?
+          .ok_or_else(|| invalid(format!("missing value of field {}", field.label())))?,
+      };
+      field.write(&value, &mut data)?;
+    }
+
+    let kind = match self.kind {
+      Some(kind) if kind.offset() + data.len() > kind.max_size() => {
+        return Err(invalid("template packet too large".into()))
+      },
+      Some(kind) => kind,
+      None => PacketKind::from_size(data.len(), None)
+        .ok_or_else(|| invalid("template packet too large".into()))?,
+    };
+
+    let mut packet = Packet::new(kind, self.codes[0]);
+    packet.append(&data);
+    Ok(packet)
+  }
+
+  fn from_definition(name: &str, definition: Definition) -> io::Result<Self> {
+    let context = |message: &str| invalid(format!("template {}: {}", name, message));
+    let kind = definition
+      .kind
+      .map(|kind| parse_byte(&kind).and_then(PacketKind::from_byte).map(PacketKind::decrypted))
+      .map(|kind| kind.ok_or_else(|| context("invalid packet kind")))
+      .transpose()?;
+
+    let mut codes = vec![parse_byte(&definition.code).ok_or_else(|| context("invalid code"))?];
+    if let Some(subcode) = definition.subcode {
+      codes.push(parse_byte(&subcode).ok_or_else(|| context("invalid subcode"))?);
+    }
+
+    for field in &definition.fields {
+      let sized = field.kind == FieldType::String || field.kind == FieldType::Bytes;
+      if field.kind == FieldType::String && field.size.is_none() {
+        return Err(context(&format!("field {} is missing a size", field.label())));
+      }
+      if !sized && field.size.is_some() {
+        return Err(context(&format!("field {} has an implicit size", field.label())));
+      }
+      if field.name.is_none() && field.default.is_none() {
+        return Err(context("constant field is missing a default"));
+      }
+    }
+
+    Ok(Template {
+      kind,
+      codes,
+      fields: definition.fields,
+    })
+  }
+}
+
+impl Field {
+  fn label(&self) -> &str { self.name.as_deref().unwrap_or("<constant>") }
+
+  /// Appends a value to packet data, according to the field's type.
+  fn write(&self, value: &Value, data: &mut Vec<u8>) -> io::Result<()> {
+    let mismatch = || invalid(format!("invalid value of field {}", self.label()));
+    let integer = |width: u32| match *value {
+      Value::Integer(integer) if width == 64 || integer >> width == 0 => Ok(integer),
+      _ => Err(mismatch()),
+    };
+
+    match self.kind {
+      FieldType::U8 => data.push(integer(8)? as u8),
+      FieldType::U16 => data.extend_from_slice(&(integer(16)? as u16).to_le_bytes()),
+      FieldType::U16be => data.extend_from_slice(&(integer(16)? as u16).to_be_bytes()),
+      FieldType::U32 => data.extend_from_slice(&(integer(32)? as u32).to_le_bytes()),
+      FieldType::U32be => data.extend_from_slice(&(integer(32)? as u32).to_be_bytes()),
+      FieldType::U64 => data.extend_from_slice(&integer(64)?.to_le_bytes()),
+      FieldType::U64be => data.extend_from_slice(&integer(64)?.to_be_bytes()),
+      FieldType::String | FieldType::Bytes => {
+        let bytes = match value {
+          Value::Text(text) => text.as_bytes(),
+          Value::Bytes(bytes) => bytes.as_slice(),
+          Value::Integer(_) => return Err(mismatch()),
+        };
+
+        match self.size {
+          Some(size) if bytes.len() > size => {
+            return Err(invalid(format!("value of field {} is too long", self.label())))
+          },
+          Some(size) => {
+            data.extend_from_slice(bytes);
+            data.resize(data.len() + size - bytes.len(), 0);
+          },
+          None => data.extend_from_slice(bytes),
+        }
+      },
+    }
+    Ok(())
+  }
+}
+
+/// A collection of packet templates, by name.
+#[derive(Clone, Debug, Default)]
+pub struct Templates(HashMap<String, Template>);
+
+impl Templates {
+  /// Parses templates from TOML.
+  pub fn parse(toml: &str) -> io::Result<Self> {
+    let definitions: HashMap<String, Definition> =
+      toml::from_str(toml).map_err(|error| invalid(error.to_string()))?;
+
+    definitions
+      .into_iter()
+      .map(|(name, definition)| {
+        let template = Template::from_definition(&name, definition)?;
+        Ok((name, template))
+      })
+      .collect::<io::Result<_>>()
+      .map(Templates)
+  }
+
+  /// Parses templates from a TOML file.
+  pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    Self::parse(&fs::read_to_string(path)?)
+  }
+
+  /// Returns a template by name.
+  pub fn get(&self, name: &str) -> Option<&Template> { self.0.get(name) }
+
+  /// Returns the names of all templates.
+  pub fn names(&self) -> impl Iterator<Item = &str> { self.0.keys().map(String::as_str) }
+
+  /// Instantiates a packet from a template by name.
+  pub fn instantiate(&self, name: &str, values: &Values) -> io::Result<Packet> {
+    self
+      .get(name)
+      .ok_or_else(|| invalid(format!("unknown template {}", name)))?
+      .instantiate(values)
+  }
+}
+
+/// Converts a default in TOML to a value, according to a field's type.
+///
+/// Defaults of byte fields are written as hexadecimal strings.
+fn default_value(field: &Field, default: &toml::Value) -> io::Result<Value> {
+  let mismatch = || invalid(format!("invalid default of field {}", field.label()));
+  match (field.kind, default) {
+    (FieldType::Bytes, toml::Value::String(hex)) => hex
+      .split_whitespace()
+      .map(parse_byte)
+      .collect::<Option<Vec<_>>>()
+      .map(Value::Bytes)
+      .ok_or_else(mismatch),
+    (_, toml::Value::String(text)) => Ok(Value::Text(text.clone())),
+    (_, &toml::Value::Integer(integer)) if integer >= 0 => Ok(Value::Integer(integer as u64)),
+    _ => Err(mismatch()),
+  }
+}
+
+fn invalid(message: String) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, message) }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TEMPLATES: &str = r#"
+    [login]
+    kind = "C2"
+    code = "F1"
+    subcode = "01"
+    fields = [
+      { name = "account", type = "string", size = 4 },
+      { name = "tick", type = "u32" },
+      { type = "bytes", default = "AA BB" },
+    ]
+
+    [ping]
+    code = "0E"
+
+    [blob]
+    code = "0F"
+    fields = [{ name = "data", type = "bytes" }]
+  "#;
+
+  #[test]
+  fn instantiate() {
+    let templates = Templates::parse(TEMPLATES).unwrap();
+    let values = Values::new().set("account", "abc").set("tick", 0x0102u32);
+
+    let packet = templates.instantiate("login", &values).unwrap();
+    assert_eq!(
+      packet.to_bytes(),
+      [0xC2, 0x00, 0x0F, 0xF1, 0x01, 0x61, 0x62, 0x63, 0x00, 0x02, 0x01, 0x00, 0x00, 0xAA, 0xBB]
+    );
+
+    let ping = templates.instantiate("ping", &Values::new()).unwrap();
+    assert_eq!(ping.to_bytes(), [0xC1, 0x03, 0x0E]);
+
+    // The kind is inferred from the size of the data, excluding the code
+    let blob = |size| templates.instantiate("blob", &Values::new().set("data", vec![0; size]));
+    assert_eq!((blob(252).unwrap().kind(), blob(252).unwrap().len()), (PacketKind::C1, 255));
+    assert_eq!(blob(253).unwrap().kind(), PacketKind::C2);
+
+    assert!(templates.instantiate("login", &Values::new().set("tick", 1u32)).is_err());
+    let long = values.set("account", "abcde");
+    assert!(templates.instantiate("login", &long).is_err());
+    assert!(Templates::parse("[bad]\ncode = \"F1\"\nfields = [{ type = \"u8\" }]").is_err());
+  }
+}
@@ -0,0 +1,98 @@
+//! Known frames with their decoded forms, for asserting compatibility.
+
+use crate::PacketCrypto;
+
+/// An encryption scheme used by a test vector.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Scheme {
+  /// The default client scheme (`crypto::CLIENT`).
+  Client,
+  /// The default server scheme (`crypto::SERVER`).
+  Server,
+}
+
+impl Scheme {
+  /// Returns the scheme's encryption.
+  pub fn crypto(self) -> &'static PacketCrypto {
+    match self {
+      Scheme::Client => &crate::crypto::CLIENT,
+      Scheme::Server => &crate::crypto::SERVER,
+    }
+  }
+}
+
+/// A frame as sent on the wire, and its decoded form.
+#[derive(Copy, Clone, Debug)]
+pub struct TestVector {
+  /// A short description of the frame.
+  pub name: &'static str,
+  /// The frame as sent on the wire.
+  pub wire: &'static [u8],
+  /// The frame after all decryption.
+  pub plain: &'static [u8],
+  /// Whether the frame uses the default XOR cipher (`XOR_CIPHER`).
+  pub cipher: bool,
+  /// The frame's encryption scheme and counter, if any.
+  pub crypto: Option<(Scheme, u8)>,
+}
+
+/// Server hello, sent on connect.
+pub const HELLO: TestVector = TestVector {
+  name: "hello",
+  wire: &[0xC1, 0x04, 0x00, 0x01],
+  plain: &[0xC1, 0x04, 0x00, 0x01],
+  cipher: false,
+  crypto: None,
+};
+
+/// Server list response, from the connect server.
+pub const SERVER_LIST: TestVector = TestVector {
+  name: "server list",
+  wire: &[0xC2, 0x00, 0x0B, 0xF4, 0x06, 0x00, 0x01, 0x00, 0x00, 0x05, 0x77],
+  plain: &[0xC2, 0x00, 0x0B, 0xF4, 0x06, 0x00, 0x01, 0x00, 0x00, 0x05, 0x77],
+  cipher: true,
+  crypto: None,
+};
+
+/// Server info request, encrypted by a client.
+pub const SERVER_INFO: TestVector = TestVector {
+  name: "server info",
+  wire: &[0xC3, 0x0D, 0xE3, 0xB3, 0x53, 0x9A, 0x4F, 0xC8, 0x32, 0x7D, 0x04, 0x37, 0x0F],
+  plain: &[0xC1, 0x06, 0xF4, 0x03, 0x00, 0x00],
+  cipher: true,
+  crypto: Some((Scheme::Client, 0)),
+};
+
+/// Client frame using the XOR cipher.
+pub const XOR_CIPHERED: TestVector = TestVector {
+  name: "xor ciphered",
+  wire: &[0xC1, 0x06, 0xA9, 0x20, 0x9C, 0x2F],
+  plain: &[0xC1, 0x06, 0xA9, 0x00, 0x00, 0x01],
+  cipher: true,
+  crypto: None,
+};
+
+/// All known test vectors.
+pub const ALL: &[TestVector] = &[HELLO, SERVER_LIST, SERVER_INFO, XOR_CIPHERED];
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Packet, XOR_CIPHER};
+
+  #[test]
+  fn vectors_round_trip() {
+    for vector in ALL {
+      let cipher = if vector.cipher { Some(&XOR_CIPHER[..]) } else { None };
+      let crypto = vector.crypto.map(|(scheme, _)| scheme.crypto());
+      let (packet, size, counter) = Packet::from_bytes_ex(vector.wire, cipher, crypto).unwrap();
+
+      assert_eq!(size, vector.wire.len(), "{}", vector.name);
+      assert_eq!(counter, vector.crypto.map(|(_, counter)| counter), "{}", vector.name);
+      assert_eq!(packet.to_bytes(), vector.plain, "{}", vector.name);
+
+      let crypto = vector.crypto.map(|(scheme, counter)| (scheme.crypto(), counter));
+      assert_eq!(packet.to_bytes_ex(cipher, crypto), vector.wire, "{}", vector.name);
+    }
+  }
+}
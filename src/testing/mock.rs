@@ -0,0 +1,220 @@
+use crate::{Packet, PacketCodec};
+use futures::task::{self, Task};
+use futures::{Async, Future, Poll, Sink, Stream};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use tokio_io::codec::Framed;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A framed in-memory transport.
+pub type Transport = Framed<MemoryStream, PacketCodec>;
+
+/// One direction of an in-memory stream.
+#[derive(Debug, Default)]
+struct Pipe {
+  buffer: VecDeque<u8>,
+  reader: Option<Task>,
+  closed: bool,
+}
+
+impl Pipe {
+  fn close(&mut self) {
+    self.closed = true;
+    if let Some(reader) = self.reader.take() {
+      reader.notify();
+    }
+  }
+}
+
+/// One end of a connected pair of in-memory streams.
+///
+/// Reads must be performed within a task (e.g by a framed transport), since
+/// they wait by registering the current task. Dropping or shutting down an
+/// end closes the peer's input.
+#[derive(Debug)]
+pub struct MemoryStream {
+  input: Arc<Mutex<Pipe>>,
+  output: Arc<Mutex<Pipe>>,
+}
+
+impl MemoryStream {
+  /// Creates a connected pair of streams.
+  pub fn pair() -> (MemoryStream, MemoryStream) {
+    let (first, second) = (Arc::default(), Arc::default());
+    let client = MemoryStream {
+      input: Arc::clone(&first),
+      output: Arc::clone(&second),
+    };
+    let server = MemoryStream {
+      input: second,
+      output: first,
+    };
+    (client, server)
+  }
+}
+
+impl Read for MemoryStream {
+  fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut pipe = self.input.lock().unwrap();
+    if pipe.buffer.is_empty() {
+      if pipe.closed {
+        return Ok(0);
+      }
+
+      pipe.reader = Some(task::current());
+      return Err(io::ErrorKind::WouldBlock.into());
+    }
+
+    let size = buffer.len().min(pipe.buffer.len());
+    for (target, byte) in buffer.iter_mut().zip(pipe.buffer.drain(..size)) {
+      *target = byte;
+    }
+    Ok(size)
+  }
+}
+
+impl Write for MemoryStream {
+  fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+    let mut pipe = self.output.lock().unwrap();
+    if pipe.closed {
+      return Err(io::ErrorKind::BrokenPipe.into());
+    }
+
+    pipe.buffer.extend(bytes);
+    if let Some(reader) = pipe.reader.take() {
+      reader.notify();
+    }
+    Ok(bytes.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+impl AsyncRead for MemoryStream {}
+
+impl AsyncWrite for MemoryStream {
+  fn shutdown(&mut self) -> Poll<(), io::Error> {
+    self.output.lock().unwrap().close();
+    Ok(Async::Ready(()))
+  }
+}
+
+impl Drop for MemoryStream {
+  fn drop(&mut self) {
+    if let Ok(mut pipe) = self.output.lock() {
+      pipe.close();
+    }
+  }
+}
+
+/// Returns a connected pair of in-memory transports, using a codec each.
+///
+/// This allows testing connection handling without any sockets.
+pub fn duplex(client: PacketCodec, server: PacketCodec) -> (Transport, Transport) {
+  let (client_stream, server_stream) = MemoryStream::pair();
+  (client_stream.framed(client), server_stream.framed(server))
+}
+
+/// A scripted step of a mock peer.
+#[derive(Clone, Debug)]
+enum MockStep {
+  /// Expects a packet's code & data, either exactly or as a prefix.
+  Expect(Vec<u8>, bool),
+  /// Sends a packet.
+  Send(Packet),
+}
+
+/// A peer which verifies received packets and replies from a script.
+#[derive(Clone, Debug, Default)]
+pub struct MockPeer {
+  script: Vec<MockStep>,
+}
+
+impl MockPeer {
+  /// Creates a peer with an empty script.
+  pub fn new() -> Self { Self::default() }
+
+  /// Expects the next packet received to equal a packet.
+  pub fn expect(mut self, packet: &Packet) -> Self {
+    self.script.push(MockStep::Expect(packet.content(), true));
+    self
+  }
+
+  /// Expects the next packet received to start with a code, followed by any
+  /// subcodes.
+  pub fn expect_code(mut self, codes: &[u8]) -> Self {
+    self.script.push(MockStep::Expect(codes.to_vec(), false));
+    self
+  }
+
+  /// Sends a packet to the other side.
+  pub fn send(mut self, packet: Packet) -> Self {
+    self.script.push(MockStep::Send(packet));
+    self
+  }
+
+  /// Runs the script on a transport, blocking the current thread.
+  ///
+  /// Returns the transport once the script is completed, or an error
+  /// describing the first packet that did not match.
+  pub fn run(self, mut transport: Transport) -> io::Result<Transport> {
+    for (index, step) in self.script.into_iter().enumerate() {
+      match step {
+        MockStep::Send(packet) => transport = transport.send(packet).wait()?,
+        MockStep::Expect(expected, exact) => {
+          let (packet, rest) = transport.into_future().wait().map_err(|(error, _)| error)?;
+          transport = rest;
+
+          let packet = packet.ok_or_else(|| {
+            let message = format!("step #{}: connection closed", index + 1);
+            io::Error::new(io::ErrorKind::UnexpectedEof, message)
+          })?;
+
+          let received = packet.content();
+          let matches = if exact { received == expected } else { received.starts_with(&expected) };
+          if !matches {
+            let message = format!(
+              "step #{}: received {:02X?}, expected {:02X?}",
+              index + 1,
+              received,
+              expected
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+          }
+        },
+      }
+    }
+    Ok(transport)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{PacketCodecState, PacketKind};
+  use std::thread;
+
+  #[test]
+  fn mock_peer() {
+    let codec = || PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let (client, server) = duplex(codec(), codec());
+
+    let mut login = Packet::new(PacketKind::C1, 0xF1);
+    login.append(&[0x01, 0x61]);
+    let mut result = Packet::new(PacketKind::C1, 0xF1);
+    result.append(&[0x01, 0x01]);
+
+    let peer = MockPeer::new().expect(&login).send(result.clone()).expect_code(&[0xF1, 0x02]);
+    let server = thread::spawn(move || peer.run(server));
+
+    let client = client.send(login).wait().unwrap();
+    let (reply, client) = client.into_future().wait().map_err(|(error, _)| error).unwrap();
+    assert_eq!(reply.unwrap().to_bytes(), result.to_bytes());
+
+    let logout = Packet::from_bytes(&[0xC1, 0x05, 0xF1, 0x03, 0x00]).unwrap();
+    client.send(logout).wait().unwrap();
+    let error = server.join().unwrap().unwrap_err();
+    assert!(error.to_string().contains("step #3: received [F1, 03, 00]"));
+  }
+}
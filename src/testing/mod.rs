@@ -0,0 +1,10 @@
+//! Utilities for testing code built on the packet codecs.
+
+pub use self::mock::{duplex, MemoryStream, MockPeer, Transport};
+pub use self::sequence::{Expected, Sequence};
+pub use crate::expect_sequence;
+
+pub mod simulate;
+
+mod mock;
+mod sequence;
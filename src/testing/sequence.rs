@@ -0,0 +1,257 @@
+use super::Transport;
+use crate::Packet;
+#[cfg(feature = "serialize")]
+use crate::PacketDecodable;
+use futures::{Future, Stream};
+use std::{fmt, io};
+
+/// A function matching a received packet.
+type Matcher = Box<dyn Fn(&Packet) -> io::Result<bool> + Send>;
+
+/// An expected packet of a sequence.
+pub struct Expected {
+  description: String,
+  codes: Vec<u8>,
+  matcher: Matcher,
+}
+
+impl Expected {
+  /// Expects a packet starting with a code, followed by any subcodes.
+  pub fn code(codes: &[u8]) -> Self { Self::packet(codes, |_| true) }
+
+  /// Expects a packet starting with a code, which matches a predicate.
+  pub fn packet<F>(codes: &[u8], predicate: F) -> Self
+  where
+    F: Fn(&Packet) -> bool + Send + 'static,
+  {
+    Expected {
+      description: format!("{:02X?}", codes),
+      codes: codes.to_vec(),
+      matcher: Box::new(move |packet| Ok(predicate(packet))),
+    }
+  }
+
+  /// Expects a packet of a type.
+  #[cfg(feature = "serialize")]
+  pub fn typed<T: PacketDecodable>() -> Self { Self::typed_with(|_: &T| true) }
+
+  /// Expects a packet of a type, which matches a predicate (e.g of its
+  /// fields).
+  ///
+  /// A packet with the type's codes which fails to decode is an error.
+  #[cfg(feature = "serialize")]
+  pub fn typed_with<T, F>(predicate: F) -> Self
+  where
+    T: PacketDecodable,
+    F: Fn(&T) -> bool + Send + 'static,
+  {
+    Expected {
+      description: std::any::type_name::<T>().to_string(),
+      codes: T::identifier().as_bytes().to_vec(),
+      matcher: Box::new(move |packet| T::from_packet(packet).map(|packet| predicate(&packet))),
+    }
+  }
+
+  fn matches(&self, packet: &Packet) -> io::Result<bool> {
+    if !self.codes.iter().cloned().eq(identifier(packet).take(self.codes.len())) {
+      return Ok(false);
+    }
+    (self.matcher)(packet)
+  }
+}
+
+impl fmt::Debug for Expected {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    fmt.debug_tuple("Expected").field(&self.description).finish()
+  }
+}
+
+impl fmt::Display for Expected {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result { fmt.write_str(&self.description) }
+}
+
+/// A declarative sequence of packets, expected from a transport.
+///
+/// Each packet received must match the next expected packet, except for
+/// ignored ones (e.g keep-alives), which may be interleaved anywhere. The
+/// `expect_sequence!` macro provides a concise syntax for this.
+#[derive(Debug, Default)]
+pub struct Sequence {
+  expected: Vec<Expected>,
+  ignored: Vec<Vec<u8>>,
+}
+
+impl Sequence {
+  /// Creates an empty sequence, without any ignored packets.
+  pub fn new() -> Self { Self::default() }
+
+  /// Appends an expected packet to the sequence.
+  pub fn then(mut self, expected: Expected) -> Self {
+    self.expected.push(expected);
+    self
+  }
+
+  /// Ignores packets starting with a code, followed by any subcodes.
+  pub fn ignore(mut self, codes: &[u8]) -> Self {
+    self.ignored.push(codes.to_vec());
+    self
+  }
+
+  /// Receives the sequence from a transport, blocking the current thread.
+  ///
+  /// Returns the transport once each packet has been received, or an error
+  /// describing the first packet that did not match.
+  pub fn run(self, mut transport: Transport) -> io::Result<Transport> {
+    for (index, expected) in self.expected.iter().enumerate() {
+      loop {
+        let (packet, rest) = transport.into_future().wait().map_err(|(error, _)| error)?;
+        transport = rest;
+
+        let packet = packet.ok_or_else(|| {
+          let message = format!("packet #{}: connection closed, expected {}", index + 1, expected);
+          io::Error::new(io::ErrorKind::UnexpectedEof, message)
+        })?;
+
+        if self.is_ignored(&packet) {
+          continue;
+        }
+
+        if !expected.matches(&packet)? {
+          let message = format!(
+            "packet #{}: received {:02X?}, expected {}",
+            index + 1,
+            identifier(&packet).collect::<Vec<_>>(),
+            expected
+          );
+          return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        break;
+      }
+    }
+    Ok(transport)
+  }
+
+  fn is_ignored(&self, packet: &Packet) -> bool {
+    self
+      .ignored
+      .iter()
+      .any(|codes| codes.iter().cloned().eq(identifier(packet).take(codes.len())))
+  }
+}
+
+/// Returns a packet's code, followed by its data.
+fn identifier(packet: &Packet) -> impl Iterator<Item = u8> + Clone + '_ {
+  std::iter::once(packet.code()).chain(packet.data().iter().cloned())
+}
+
+/// Asserts that a transport yields a sequence of packets, returning the
+/// transport.
+///
+/// Packets are expected by their codes (`[0xF1, 0x00]`) or their type
+/// (`LoginResult`), optionally followed by `where` and a predicate. Packets
+/// listed after `ignore` may be interleaved anywhere:
+///
+/// ```ignore
+/// let transport = expect_sequence!(transport, ignore [[0x0E]];
+///   [0xF1, 0x00],
+///   [0xF3, 0x03] where |packet| packet.data().len() > 1,
+///   ServerStatus where |status| status.load < 100,
+/// );
+/// ```
+#[macro_export]
+macro_rules! expect_sequence {
+  ($transport:expr, ignore [$([$($ignore:expr),+ $(,)?]),* $(,)?]; $($rest:tt)*) => {
+    $crate::expect_sequence!(
+      @munch $crate::testing::Sequence::new()$(.ignore(&[$($ignore),+]))*, $transport; $($rest)*
+    )
+  };
+  (@munch $sequence:expr, $transport:expr;) => {
+    $sequence.run($transport).unwrap_or_else(|error| panic!("unexpected sequence: {}", error))
+  };
+  (@munch $sequence:expr, $transport:expr; [$($code:expr),+] where $predicate:expr
+    $(, $($rest:tt)*)?) => {
+    $crate::expect_sequence!(
+      @munch $sequence.then($crate::testing::Expected::packet(&[$($code),+], $predicate)),
+      $transport; $($($rest)*)?
+    )
+  };
+  (@munch $sequence:expr, $transport:expr; [$($code:expr),+] $(, $($rest:tt)*)?) => {
+    $crate::expect_sequence!(
+      @munch $sequence.then($crate::testing::Expected::code(&[$($code),+])),
+      $transport; $($($rest)*)?
+    )
+  };
+  (@munch $sequence:expr, $transport:expr; $type:ty where $predicate:expr $(, $($rest:tt)*)?) => {
+    $crate::expect_sequence!(
+      @munch $sequence.then($crate::testing::Expected::typed_with::<$type, _>($predicate)),
+      $transport; $($($rest)*)?
+    )
+  };
+  (@munch $sequence:expr, $transport:expr; $type:ty $(, $($rest:tt)*)?) => {
+    $crate::expect_sequence!(
+      @munch $sequence.then($crate::testing::Expected::typed::<$type>()),
+      $transport; $($($rest)*)?
+    )
+  };
+  ($transport:expr; $($rest:tt)*) => {
+    $crate::expect_sequence!(@munch $crate::testing::Sequence::new(), $transport; $($rest)*)
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::duplex;
+  use crate::{PacketCodec, PacketCodecState};
+  use futures::Sink;
+  use std::thread;
+
+  fn send(client: Transport, packets: &[&[u8]]) -> Transport {
+    packets.iter().fold(client, |client, bytes| {
+      client.send(Packet::from_bytes(bytes).unwrap()).wait().unwrap()
+    })
+  }
+
+  #[test]
+  fn sequence() {
+    let codec = || PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let (client, server) = duplex(codec(), codec());
+
+    let client = thread::spawn(move || {
+      send(client, &[
+        &[0xC1, 0x05, 0xF1, 0x00, 0x01],
+        &[0xC1, 0x03, 0x0E],
+        &[0xC1, 0x05, 0xF3, 0x03, 0x2A],
+        &[0xC1, 0x04, 0xF3, 0x00],
+      ])
+    });
+
+    let server = expect_sequence!(server, ignore [[0x0E]];
+      [0xF1, 0x00],
+      [0xF3, 0x03] where |packet: &Packet| packet.data()[1] == 0x2A,
+    );
+
+    let error = Sequence::new().then(Expected::code(&[0xF3, 0x01])).run(server).unwrap_err();
+    assert_eq!(error.to_string(), "packet #1: received [F3, 00], expected [F3, 01]");
+    drop(client.join().unwrap());
+  }
+
+  #[test]
+  #[cfg(feature = "serialize")]
+  fn typed() {
+    use crate::packets::connect::ServerStatus;
+
+    let codec = || PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let (client, server) = duplex(codec(), codec());
+    let status = [
+      0xC1, 0x0E, 0x01, 0x01, 0x00, 0x19, 0x64, 0x00, 0x62, 0x00, 0x00, 0x00, 0x90, 0x01,
+    ];
+    let client = thread::spawn(move || send(client, &[&status, &status]));
+
+    let server = expect_sequence!(server;
+      ServerStatus,
+      ServerStatus where |status: &ServerStatus| status.load == 25,
+    );
+    drop((server, client.join().unwrap()));
+  }
+}
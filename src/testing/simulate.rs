@@ -0,0 +1,220 @@
+//! A deterministic simulation of a connection between two codecs.
+//!
+//! A script of steps controls when packets are written, how their bytes are
+//! split and reordered in transit, and when they are read. Every transition
+//! is recorded, so desynchronization reported from production logs can be
+//! reproduced step by step.
+
+use bytes::BytesMut;
+use crate::{Packet, PacketCodec};
+use std::collections::VecDeque;
+use tokio_io::codec::{Decoder, Encoder};
+
+/// One side of a simulated connection.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Side {
+  /// The side connecting.
+  Client,
+  /// The side accepting.
+  Server,
+}
+
+impl Side {
+  /// Returns the opposite side.
+  pub fn peer(self) -> Side {
+    match self {
+      Side::Client => Side::Server,
+      Side::Server => Side::Client,
+    }
+  }
+
+  fn index(self) -> usize { self as usize }
+}
+
+/// A scripted step of a simulation.
+#[derive(Clone, Debug)]
+pub enum Step {
+  /// Encodes a packet, queueing its bytes in transit to the peer.
+  Write(Side, Packet),
+  /// Splits the first chunk in transit from a side at a byte offset.
+  Split(Side, usize),
+  /// Swaps the first two chunks in transit from a side.
+  Swap(Side),
+  /// Delivers the first chunk in transit to a side, and decodes all it can.
+  Read(Side),
+}
+
+/// A recorded transition of a simulation.
+#[derive(Clone, Debug)]
+pub enum Event {
+  /// A side encoded a packet into bytes.
+  Encoded(Side, Vec<u8>),
+  /// A side received a chunk of bytes.
+  Received(Side, Vec<u8>),
+  /// A side decoded a packet.
+  Decoded(Side, Packet),
+  /// A side's encryption counters (for sending and receiving) changed.
+  Counters(Side, u8, u8),
+  /// A side failed to encode or decode, discarding its buffered input.
+  Error(Side, String),
+  /// A step could not be performed, since nothing was in transit.
+  Idle(Side),
+}
+
+/// A pair of codecs connected by a scripted transport.
+#[derive(Debug)]
+pub struct Simulation {
+  codecs: [PacketCodec; 2],
+  transit: [VecDeque<Vec<u8>>; 2],
+  buffers: [BytesMut; 2],
+  events: Vec<Event>,
+}
+
+impl Simulation {
+  /// Creates a simulation between a client and a server codec.
+  pub fn new(client: PacketCodec, server: PacketCodec) -> Self {
+    Simulation {
+      codecs: [client, server],
+      transit: [VecDeque::new(), VecDeque::new()],
+      buffers: [BytesMut::new(), BytesMut::new()],
+      events: Vec::new(),
+    }
+  }
+
+  /// Performs a step of the simulation.
+  pub fn step(&mut self, step: &Step) {
+    match *step {
+      Step::Write(side, ref packet) => self.write(side, packet.clone()),
+      Step::Split(side, offset) => {
+        let transit = &mut self.transit[side.index()];
+        match transit.pop_front() {
+          Some(mut chunk) => {
+            let rest = chunk.split_off(offset.min(chunk.len()));
+            transit.push_front(rest);
+            transit.push_front(chunk);
+          },
+          None => self.events.push(Event::Idle(side)),
+        }
+      },
+      Step::Swap(side) => {
+        let transit = &mut self.transit[side.index()];
+        if transit.len() < 2 {
+          self.events.push(Event::Idle(side));
+        } else {
+          transit.swap(0, 1);
+        }
+      },
+      Step::Read(side) => self.read(side),
+    }
+  }
+
+  /// Performs each step of a script.
+  pub fn run(&mut self, steps: &[Step]) {
+    for step in steps {
+      self.step(step);
+    }
+  }
+
+  /// Returns the recorded transitions.
+  pub fn events(&self) -> &[Event] { &self.events }
+
+  /// Returns the packets decoded by a side.
+  pub fn decoded(&self, side: Side) -> Vec<&Packet> {
+    self
+      .events
+      .iter()
+      .filter_map(|event| match event {
+        Event::Decoded(decoder, packet) if *decoder == side => Some(packet),
+        _ => None,
+      }).collect()
+  }
+
+  /// Returns a side's codec.
+  pub fn codec(&self, side: Side) -> &PacketCodec { &self.codecs[side.index()] }
+
+  fn write(&mut self, side: Side, packet: Packet) {
+    let mut bytes = BytesMut::new();
+    match self.codecs[side.index()].encode(packet, &mut bytes) {
+      Ok(()) => {
+        self.events.push(Event::Encoded(side, bytes.to_vec()));
+        self.transit[side.index()].push_back(bytes.to_vec());
+        self.record_counters(side);
+      },
+      Err(error) => self.events.push(Event::Error(side, error.to_string())),
+    }
+  }
+
+  fn read(&mut self, side: Side) {
+    let chunk = match self.transit[side.peer().index()].pop_front() {
+      Some(chunk) => chunk,
+      None => return self.events.push(Event::Idle(side)),
+    };
+
+    self.events.push(Event::Received(side, chunk.clone()));
+    self.buffers[side.index()].extend_from_slice(&chunk);
+
+    loop {
+      match self.codecs[side.index()].decode(&mut self.buffers[side.index()]) {
+        Ok(Some(packet)) => {
+          self.events.push(Event::Decoded(side, packet));
+          self.record_counters(side);
+        },
+        Ok(None) => break,
+        Err(error) => {
+          self.buffers[side.index()].clear();
+          self.events.push(Event::Error(side, error.to_string()));
+          break;
+        },
+      }
+    }
+  }
+
+  fn record_counters(&mut self, side: Side) {
+    let (encrypt, decrypt) = self.codecs[side.index()].counters();
+    self.events.push(Event::Counters(side, encrypt, decrypt));
+  }
+}
+
+/// Runs a script through a client and a server codec, returning all events.
+pub fn run(client: PacketCodec, server: PacketCodec, steps: &[Step]) -> Vec<Event> {
+  let mut simulation = Simulation::new(client, server);
+  simulation.run(steps);
+  simulation.events
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{crypto, PacketCodecState};
+
+  fn codec() -> PacketCodec {
+    let state = || PacketCodecState::builder().crypto(crypto::CLIENT.clone()).build();
+    PacketCodec::new(state(), state())
+  }
+
+  #[test]
+  fn reordered_frames() {
+    let packet = |code| Packet::new(crate::PacketKind::C1, code);
+    let mut simulation = Simulation::new(codec(), codec());
+    simulation.run(&[
+      Step::Write(Side::Client, packet(0x01)),
+      Step::Write(Side::Client, packet(0x02)),
+      Step::Split(Side::Client, 3),
+      Step::Read(Side::Server),
+      Step::Read(Side::Server),
+    ]);
+    assert_eq!(simulation.decoded(Side::Server).len(), 1);
+
+    simulation.run(&[Step::Swap(Side::Client), Step::Read(Side::Server)]);
+    assert_eq!(simulation.decoded(Side::Server).len(), 2);
+    assert!(simulation.events().iter().any(|event| matches!(event, Event::Idle(Side::Client))));
+
+    let events = run(codec(), codec(), &[
+      Step::Write(Side::Client, packet(0x01)),
+      Step::Write(Side::Client, packet(0x02)),
+      Step::Swap(Side::Client),
+      Step::Read(Side::Server),
+    ]);
+    assert!(matches!(events.last(), Some(Event::Error(Side::Server, _))));
+  }
+}
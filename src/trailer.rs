@@ -0,0 +1,25 @@
+use std::{fmt, io};
+
+/// An interface for authenticating frames with a trailer.
+///
+/// A trailer follows each frame on the wire, by default without being
+/// included in the frame's size. It covers the frame as sent (i.e after any
+/// obfuscation, and with its size as declared on the wire).
+pub trait Trailer: fmt::Debug + Send + Sync {
+  /// Returns the size of the trailer.
+  fn size(&self) -> usize;
+
+  /// Returns whether the trailer is included in the frame's size, instead
+  /// of following it.
+  fn included(&self) -> bool { false }
+
+  /// Returns the trailer for an outgoing frame.
+  fn sign(&mut self, frame: &[u8]) -> io::Result<Vec<u8>>;
+
+  /// Verifies the trailer of an incoming frame.
+  fn verify(&mut self, frame: &[u8], trailer: &[u8]) -> io::Result<()>;
+
+  /// Returns a copy of the trailer for a new connection (i.e without any
+  /// sequence state).
+  fn fresh(&self) -> Box<dyn Trailer>;
+}
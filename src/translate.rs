@@ -0,0 +1,266 @@
+//! Translation of packets between protocol versions.
+//!
+//! Between client versions, only a handful of packets usually change (e.g a
+//! renumbered code, or a field added to a structure). By rewriting these, a
+//! proxy can let an older client talk to a newer server. Typed packets are
+//! translated in their raw form, i.e once encoded.
+
+use crate::{Packet, PacketKind};
+use std::collections::HashMap;
+use std::{fmt, io};
+
+/// A version of the protocol, identified by the client's version.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProtocolVersion {
+  major: u8,
+  minor: u8,
+  patch: u8,
+}
+
+impl ProtocolVersion {
+  /// Creates a protocol version (e.g `1.04.05`).
+  pub const fn new(major: u8, minor: u8, patch: u8) -> Self {
+    ProtocolVersion { major, minor, patch }
+  }
+
+  /// Parses a version from its form in the login packet (e.g `10405`).
+  pub fn from_client_version(version: &[u8; 5]) -> Option<Self> {
+    let digit = |index: usize| match version[index] {
+      byte @ b'0'..=b'9' => Some(byte - b'0'),
+      _ => None,
+    };
+
+    Some(ProtocolVersion {
+      major: digit(0)?,
+      minor: digit(1)? * 10 + digit(2)?,
+      patch: digit(3)? * 10 + digit(4)?,
+    })
+  }
+}
+
+impl fmt::Display for ProtocolVersion {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    write!(fmt, "{}.{:02}.{:02}", self.major, self.minor, self.patch)
+  }
+}
+
+/// A rewrite of packets matching a code, followed by any subcodes.
+#[derive(Clone, Debug)]
+enum Rewrite {
+  /// Replaces the codes with others.
+  Renumber(Vec<u8>),
+  /// Inserts bytes at an offset of the data.
+  Insert(usize, Vec<u8>),
+  /// Removes a number of bytes at an offset of the data.
+  Remove(usize, usize),
+}
+
+/// The rewrites of packets from one protocol version to another.
+///
+/// Rewrites are matched against a packet's original codes, and applied in
+/// the order they were added. Offsets are relative to the packet's data
+/// (i.e after its code), as it is after any previous rewrites.
+#[derive(Clone, Debug)]
+pub struct Translation {
+  from: ProtocolVersion,
+  to: ProtocolVersion,
+  rewrites: Vec<(Vec<u8>, Rewrite)>,
+}
+
+impl Translation {
+  /// Creates a translation without any rewrites.
+  pub fn new(from: ProtocolVersion, to: ProtocolVersion) -> Self {
+    Translation {
+      from,
+      to,
+      rewrites: Vec::new(),
+    }
+  }
+
+  /// Renumbers packets, replacing their codes with others.
+  pub fn renumber(self, codes: &[u8], to: &[u8]) -> Self {
+    assert!(!to.is_empty(), "renumbered codes must not be empty");
+    self.push(codes, Rewrite::Renumber(to.to_vec()))
+  }
+
+  /// Inserts bytes into packets (e.g a field added in the later version).
+  pub fn insert(self, codes: &[u8], offset: usize, bytes: &[u8]) -> Self {
+    self.push(codes, Rewrite::Insert(offset, bytes.to_vec()))
+  }
+
+  /// Removes bytes from packets (e.g a field removed in the later version).
+  pub fn remove(self, codes: &[u8], offset: usize, size: usize) -> Self {
+    self.push(codes, Rewrite::Remove(offset, size))
+  }
+
+  /// Returns the version translated from.
+  pub fn from_version(&self) -> ProtocolVersion { self.from }
+
+  /// Returns the version translated to.
+  pub fn to_version(&self) -> ProtocolVersion { self.to }
+
+  /// Translates a packet, returning it as-is if no rewrites apply.
+  pub fn translate(&self, packet: Packet) -> io::Result<Packet> {
+    let original = packet.content();
+    let mut content = original.clone();
+    let mut rewritten = false;
+
+    for (codes, rewrite) in &self.rewrites {
+      if !original.starts_with(codes) {
+        continue;
+      }
+
+      rewritten = true;
+      match rewrite {
+        Rewrite::Renumber(to) => {
+          if codes.len() > content.len() {
+            return Err(truncated(&original));
+          }
+          content.splice(..codes.len(), to.iter().cloned());
+        },
+        Rewrite::Insert(offset, bytes) => {
+          let index = 1 + offset;
+          if index > content.len() {
+            return Err(truncated(&original));
+          }
+          content.splice(index..index, bytes.iter().cloned());
+        },
+        Rewrite::Remove(offset, size) => {
+          let index = 1 + offset;
+          if index + size > content.len() {
+            return Err(truncated(&original));
+          }
+          content.drain(index..index + size);
+        },
+      }
+    }
+
+    if !rewritten {
+      return Ok(packet);
+    }
+
+    // The kind of the original is retained whenever the content still fits,
+    // since it may be expected. The content includes the code.
+    let size = content.len() - 1;
+    let kind = if size + packet.kind().offset() <= packet.kind().max_size() {
+      packet.kind()
+    } else {
+      PacketKind::from_size(size, None)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "translated packet too large"))?
+    };
+
+    let mut translated = Packet::new(kind, content[0]);
+    translated.append(&content[1..]);
+    Ok(translated)
+  }
+
+  fn push(mut self, codes: &[u8], rewrite: Rewrite) -> Self {
+    assert!(!codes.is_empty(), "rewritten codes must not be empty");
+    self.rewrites.push((codes.to_vec(), rewrite));
+    self
+  }
+}
+
+/// A collection of translations between protocol versions.
+#[derive(Clone, Debug, Default)]
+pub struct Translator {
+  translations: HashMap<(ProtocolVersion, ProtocolVersion), Translation>,
+}
+
+impl Translator {
+  /// Creates a translator without any translations.
+  pub fn new() -> Self { Self::default() }
+
+  /// Adds a translation, replacing any between the same versions.
+  ///
+  /// Translations are directional, so packets sent by the client and the
+  /// server each require their own (e.g older to newer, and vice versa).
+  pub fn translation(mut self, translation: Translation) -> Self {
+    self.translations.insert((translation.from, translation.to), translation);
+    self
+  }
+
+  /// Returns the translation between two versions.
+  pub fn get(&self, from: ProtocolVersion, to: ProtocolVersion) -> Option<&Translation> {
+    self.translations.get(&(from, to))
+  }
+
+  /// Translates a packet between two versions.
+  ///
+  /// Packets between the same versions are returned as-is, and an error is
+  /// returned if there is no translation between different ones.
+  pub fn translate(
+    &self,
+    from: ProtocolVersion,
+    to: ProtocolVersion,
+    packet: Packet,
+  ) -> io::Result<Packet> {
+    if from == to {
+      return Ok(packet);
+    }
+
+    self
+      .get(from, to)
+      .ok_or_else(|| {
+        let message = format!("no translation from {} to {}", from, to);
+        io::Error::new(io::ErrorKind::Unsupported, message)
+      })?
+      .translate(packet)
+  }
+}
+
+fn truncated(content: &[u8]) -> io::Error {
+  let message = format!("packet {:02X?} too short to translate", &content[..content.len().min(2)]);
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn translate() {
+    let (old, new) = (ProtocolVersion::new(1, 0, 0), ProtocolVersion::new(1, 4, 5));
+    assert_eq!(ProtocolVersion::from_client_version(b"10405"), Some(new));
+    assert_eq!(new.to_string(), "1.04.05");
+
+    let translator = Translator::new().translation(
+      Translation::new(old, new)
+        .renumber(&[0xD4], &[0xD7])
+        .insert(&[0xD4], 2, &[0x00])
+        .remove(&[0xF3, 0x03], 1, 2),
+    );
+
+    let walk = Packet::from_bytes(&[0xC1, 0x05, 0xD4, 0x10, 0x20]).unwrap();
+    let walk = translator.translate(old, new, walk).unwrap();
+    assert_eq!(walk.to_bytes(), [0xC1, 0x06, 0xD7, 0x10, 0x20, 0x00]);
+
+    let character = Packet::from_bytes(&[0xC1, 0x06, 0xF3, 0x03, 0x01, 0x02]).unwrap();
+    let character = translator.translate(old, new, character).unwrap();
+    assert_eq!(character.to_bytes(), [0xC1, 0x04, 0xF3, 0x03]);
+
+    let short = Packet::from_bytes(&[0xC1, 0x04, 0xF3, 0x03]).unwrap();
+    assert!(translator.translate(old, new, short.clone()).is_err());
+    assert!(translator.translate(new, old, short.clone()).is_err());
+    assert_eq!(translator.translate(new, new, short).unwrap().code(), 0xF3);
+
+    // Packets only change kind once they no longer fit in their own
+    let mut full = Packet::new(PacketKind::C1, 0xD4);
+    full.append(&[0x00; 252]);
+    let renumber = Translation::new(old, new).renumber(&[0xD4], &[0xD7]);
+    let renumber = Translator::new().translation(renumber);
+    assert_eq!(renumber.translate(old, new, full.clone()).unwrap().len(), 255);
+    assert_eq!(translator.translate(old, new, full).unwrap().kind(), PacketKind::C2);
+  }
+
+  #[test]
+  fn renumber_shrunk() {
+    let (old, new) = (ProtocolVersion::new(0, 97, 4), ProtocolVersion::new(1, 0, 0));
+    let translation = Translation::new(old, new)
+      .remove(&[0xF1], 0, 3)
+      .renumber(&[0xF1, 0x01], &[0xF2]);
+
+    let packet = Packet::from_bytes(&[0xC1, 0x06, 0xF1, 0x01, 0x02, 0x03]).unwrap();
+    assert!(translation.translate(packet).is_err());
+  }
+}
@@ -0,0 +1,145 @@
+//! Framed connections over any stream, including local inter-process links.
+//!
+//! Servers on the same host (e.g a game server and its data server) may use
+//! a Unix domain socket or a Windows named pipe instead of TCP loopback,
+//! while reusing the same framing. Sockets and pipes are registered with the
+//! default reactor of tokio.
+
+use crate::PacketCodec;
+use tokio_io::codec::Framed;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+#[cfg(unix)]
+pub use self::unix::{bind_unix, connect_unix};
+#[cfg(windows)]
+pub use self::pipe::{accept_pipe, connect_pipe};
+
+/// A framed connection, over any stream.
+pub type Connection<T> = Framed<T, PacketCodec>;
+
+/// Frames a stream using a codec.
+pub fn framed<T: AsyncRead + AsyncWrite>(io: T, codec: PacketCodec) -> Connection<T> {
+  io.framed(codec)
+}
+
+#[cfg(unix)]
+mod unix {
+  use super::{framed, Connection};
+  use crate::PacketCodec;
+  use futures::{Future, Stream};
+  use std::io;
+  use std::path::Path;
+  use tokio_uds::{UnixListener, UnixStream};
+
+  /// Connects to a Unix domain socket, framing it using a codec.
+  pub fn connect_unix<P: AsRef<Path>>(
+    path: P,
+    codec: PacketCodec,
+  ) -> impl Future<Item = Connection<UnixStream>, Error = io::Error> {
+    UnixStream::connect(path).map(move |stream| framed(stream, codec))
+  }
+
+  /// Binds a Unix domain socket, returning a stream of its connections.
+  ///
+  /// Each connection is framed using a clone of the codec.
+  pub fn bind_unix<P: AsRef<Path>>(
+    path: P,
+    codec: PacketCodec,
+  ) -> io::Result<impl Stream<Item = Connection<UnixStream>, Error = io::Error>> {
+    let listener = UnixListener::bind(path)?;
+    Ok(listener.incoming().map(move |stream| framed(stream, codec.clone())))
+  }
+}
+
+#[cfg(windows)]
+mod pipe {
+  use super::{framed, Connection};
+  use crate::PacketCodec;
+  use futures::{future, try_ready, Async, Future};
+  use std::ffi::OsStr;
+  use std::fs::OpenOptions;
+  use std::io;
+  use std::os::windows::fs::OpenOptionsExt;
+  use std::os::windows::io::{FromRawHandle, IntoRawHandle};
+  use tokio_named_pipes::NamedPipe;
+  use tokio_reactor::Handle;
+
+  /// Pipes must be opened for overlapped I/O to be used asynchronously.
+  const FILE_FLAG_OVERLAPPED: u32 = 0x4000_0000;
+
+  /// Connects to a named pipe (e.g `\\.\pipe\dataserver`), framing it using a
+  /// codec.
+  pub fn connect_pipe<P: AsRef<OsStr>>(
+    name: P,
+    codec: PacketCodec,
+  ) -> io::Result<Connection<NamedPipe>> {
+    let file = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .custom_flags(FILE_FLAG_OVERLAPPED)
+      .open(name.as_ref())?;
+
+    // The handle is owned, and was opened for overlapped I/O
+    let pipe = unsafe { mio_named_pipes::NamedPipe::from_raw_handle(file.into_raw_handle()) };
+    Ok(framed(NamedPipe::from_pipe(pipe, &Handle::default())?, codec))
+  }
+
+  /// Creates an instance of a named pipe, returning a future which resolves
+  /// once a client has connected to it.
+  ///
+  /// An instance serves a single client, so this is called again for each
+  /// connection to accept.
+  pub fn accept_pipe<P: AsRef<OsStr>>(
+    name: P,
+    codec: PacketCodec,
+  ) -> io::Result<impl Future<Item = Connection<NamedPipe>, Error = io::Error>> {
+    let pipe = NamedPipe::new(name, &Handle::default())?;
+
+    // A pending connection is completed once the pipe becomes writable
+    let pending = match pipe.connect() {
+      Ok(()) => false,
+      Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => true,
+      Err(error) => return Err(error),
+    };
+
+    let mut state = Some((pipe, codec));
+    Ok(future::poll_fn(move || {
+      if pending {
+        let (pipe, _) = state.as_mut().expect("polled after completion");
+        try_ready!(pipe.poll_write_ready());
+      }
+
+      let (pipe, codec) = state.take().expect("polled after completion");
+      Ok(Async::Ready(framed(pipe, codec)))
+    }))
+  }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+  use crate::{Packet, PacketCodecState, PacketKind};
+  use futures::{Future, Sink, Stream};
+  use std::{fs, process, thread};
+
+  #[test]
+  fn unix_socket() {
+    let path = std::env::temp_dir().join(format!("muonline-packet-{}.sock", process::id()));
+    let _ = fs::remove_file(&path);
+    let codec = || PacketCodec::new(PacketCodecState::new(), PacketCodecState::new());
+    let connections = bind_unix(&path, codec()).unwrap();
+
+    let client_path = path.clone();
+    let client = thread::spawn(move || {
+      let connection = connect_unix(client_path, codec()).wait().unwrap();
+      connection.send(Packet::new(PacketKind::C1, 0x0E)).wait().unwrap()
+    });
+
+    let (connection, _) = connections.into_future().wait().map_err(|(error, _)| error).unwrap();
+    let connection = connection.unwrap().into_future().wait().map_err(|(error, _)| error);
+    assert_eq!(connection.unwrap().0.unwrap().to_bytes(), [0xC1, 0x03, 0x0E]);
+
+    client.join().unwrap();
+    fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,170 @@
+//! Framed streams of typed packets.
+//!
+//! Instead of matching on the code (and subcodes) of each raw packet, the
+//! packet types of interest are registered, and each received packet is
+//! decoded into its type by a `PacketRegistry`. Any other packets are yielded
+//! as they are.
+
+use crate::{Packet, PacketDecodable, PacketRegistry};
+use futures::{try_ready, Async, Poll, Sink, StartSend, Stream};
+use std::any::Any;
+use std::{fmt, io};
+
+/// A received packet, decoded if its type is registered.
+pub enum TypedMessage {
+  /// A packet decoded into its registered type.
+  Decoded(Box<dyn Any + Send>),
+  /// A packet without a registered type.
+  Raw(Packet),
+}
+
+impl TypedMessage {
+  /// Returns whether the message is a decoded packet of a type.
+  pub fn is<T: Any>(&self) -> bool {
+    match self {
+      TypedMessage::Decoded(packet) => packet.is::<T>(),
+      TypedMessage::Raw(_) => false,
+    }
+  }
+
+  /// Returns the decoded packet of a type, or the message itself otherwise.
+  pub fn downcast<T: Any>(self) -> Result<T, Self> {
+    match self {
+      TypedMessage::Decoded(packet) => packet
+        .downcast::<T>()
+        .map(|packet| *packet)
+        .map_err(TypedMessage::Decoded),
+      raw => Err(raw),
+    }
+  }
+}
+
+impl fmt::Debug for TypedMessage {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      TypedMessage::Decoded(_) => fmt.write_str("Decoded(..)"),
+      TypedMessage::Raw(packet) => fmt.debug_tuple("Raw").field(packet).finish(),
+    }
+  }
+}
+
+/// A stream of packets (e.g a framed connection), yielding typed messages.
+///
+/// Packets are matched the same way as by a `PacketRegistry`, and packets
+/// failing to decode into their type are reported as errors. Sending is
+/// passed through to the underlying sink.
+pub struct TypedFramed<S> {
+  inner: S,
+  registry: PacketRegistry<Option<Box<dyn Any + Send>>>,
+}
+
+impl<S> TypedFramed<S> {
+  /// Creates a typed stream, without any registered types.
+  pub fn new(inner: S) -> Self {
+    TypedFramed {
+      inner,
+      registry: PacketRegistry::new(),
+    }
+  }
+
+  /// Registers a packet type, decoding all packets of its identifier.
+  pub fn register<T: PacketDecodable + Send + 'static>(mut self) -> Self {
+    self.registry = std::mem::take(&mut self.registry).on(|packet: T, decoded| {
+      *decoded = Some(Box::new(packet));
+      Ok(())
+    });
+    self
+  }
+
+  /// Returns a reference to the underlying stream.
+  pub fn get_ref(&self) -> &S { &self.inner }
+
+  /// Returns a mutable reference to the underlying stream.
+  pub fn get_mut(&mut self) -> &mut S { &mut self.inner }
+
+  /// Consumes the typed stream, returning the underlying stream.
+  pub fn into_inner(self) -> S { self.inner }
+}
+
+impl<S> Stream for TypedFramed<S>
+where
+  S: Stream<Item = Packet, Error = io::Error>,
+{
+  type Item = TypedMessage;
+  type Error = io::Error;
+
+  fn poll(&mut self) -> Poll<Option<TypedMessage>, io::Error> {
+    let packet = match try_ready!(self.inner.poll()) {
+      Some(packet) => packet,
+      None => return Ok(Async::Ready(None)),
+    };
+
+    let mut decoded = None;
+    self.registry.dispatch(&packet, &mut decoded)?;
+    Ok(Async::Ready(Some(match decoded {
+      Some(decoded) => TypedMessage::Decoded(decoded),
+      None => TypedMessage::Raw(packet),
+    })))
+  }
+}
+
+impl<S: Sink> Sink for TypedFramed<S> {
+  type SinkItem = S::SinkItem;
+  type SinkError = S::SinkError;
+
+  fn start_send(&mut self, item: S::SinkItem) -> StartSend<S::SinkItem, S::SinkError> {
+    self.inner.start_send(item)
+  }
+
+  fn poll_complete(&mut self) -> Poll<(), S::SinkError> { self.inner.poll_complete() }
+
+  fn close(&mut self) -> Poll<(), S::SinkError> { self.inner.close() }
+}
+
+impl<S: fmt::Debug> fmt::Debug for TypedFramed<S> {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    fmt
+      .debug_struct("TypedFramed")
+      .field("inner", &self.inner)
+      .field("registry", &self.registry)
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::packets::character::{CharacterListRequest, SelectCharacter};
+  use crate::{PacketEncodable, PacketKind};
+
+  #[test]
+  fn typed_messages() {
+    let mut truncated = Packet::new(PacketKind::C1, 0xF3);
+    truncated.append(&[0x03, b'N']);
+
+    let packets = vec![
+      SelectCharacter::new("Nova").unwrap().to_packet().unwrap(),
+      CharacterListRequest.to_packet().unwrap(),
+      Packet::new(PacketKind::C1, 0x0E),
+      truncated,
+    ];
+
+    let mut stream = TypedFramed::new(futures::stream::iter_ok(packets))
+      .register::<SelectCharacter>()
+      .register::<CharacterListRequest>()
+      .wait();
+
+    let select = stream.next().unwrap().unwrap().downcast::<SelectCharacter>().unwrap();
+    assert_eq!(select, SelectCharacter::new("Nova").unwrap());
+    assert!(stream.next().unwrap().unwrap().is::<CharacterListRequest>());
+
+    match stream.next().unwrap().unwrap() {
+      TypedMessage::Raw(packet) => assert_eq!(packet.code(), 0x0E),
+      message => panic!("unexpected message {:?}", message),
+    }
+
+    // Packets failing to decode into their type are errors
+    assert!(stream.next().unwrap().is_err());
+    assert!(stream.next().is_none());
+  }
+}
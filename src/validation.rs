@@ -0,0 +1,32 @@
+use std::{error, fmt};
+
+/// A description of a packet field with an invalid value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+  field: &'static str,
+  reason: String,
+}
+
+impl ValidationError {
+  /// Creates a new validation error for a field.
+  pub fn new<S: Into<String>>(field: &'static str, reason: S) -> Self {
+    ValidationError {
+      field,
+      reason: reason.into(),
+    }
+  }
+
+  /// Returns the name of the invalid field.
+  pub fn field(&self) -> &'static str { self.field }
+
+  /// Returns the reason the field is invalid.
+  pub fn reason(&self) -> &str { &self.reason }
+}
+
+impl fmt::Display for ValidationError {
+  fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+    write!(fmt, "invalid field '{}': {}", self.field, self.reason)
+  }
+}
+
+impl error::Error for ValidationError {}
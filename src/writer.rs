@@ -0,0 +1,90 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use crate::Packet;
+use std::io;
+
+/// A cursor for appending to a packet's data, for packets without a typed
+/// struct.
+///
+/// Writes are checked against the size budget of the packet's kind, and fail
+/// without modifying the packet if they would exceed it.
+#[derive(Debug)]
+pub struct PacketWriter<'a> {
+  packet: &'a mut Packet,
+}
+
+impl<'a> PacketWriter<'a> {
+  /// Creates a new writer for a packet's data.
+  pub(crate) fn new(packet: &'a mut Packet) -> Self { PacketWriter { packet } }
+
+  /// Returns the number of bytes that can be written.
+  pub fn remaining(&self) -> usize {
+    self.packet.kind().max_size().saturating_sub(self.packet.len())
+  }
+
+  /// Writes a byte.
+  pub fn put_u8(&mut self, value: u8) -> io::Result<&mut Self> { self.put_bytes(&[value]) }
+
+  /// Writes a little endian `u16`.
+  pub fn put_u16_le(&mut self, value: u16) -> io::Result<&mut Self> {
+    let mut bytes = [0; 2];
+    LittleEndian::write_u16(&mut bytes, value);
+    self.put_bytes(&bytes)
+  }
+
+  /// Writes a big endian `u16`.
+  pub fn put_u16_be(&mut self, value: u16) -> io::Result<&mut Self> {
+    let mut bytes = [0; 2];
+    BigEndian::write_u16(&mut bytes, value);
+    self.put_bytes(&bytes)
+  }
+
+  /// Writes a little endian `u32`.
+  pub fn put_u32_le(&mut self, value: u32) -> io::Result<&mut Self> {
+    let mut bytes = [0; 4];
+    LittleEndian::write_u32(&mut bytes, value);
+    self.put_bytes(&bytes)
+  }
+
+  /// Writes a big endian `u32`.
+  pub fn put_u32_be(&mut self, value: u32) -> io::Result<&mut Self> {
+    let mut bytes = [0; 4];
+    BigEndian::write_u32(&mut bytes, value);
+    self.put_bytes(&bytes)
+  }
+
+  /// Writes a string as a fixed-size, NUL padded field.
+  ///
+  /// Strings longer than `size` are rejected rather than truncated.
+  pub fn put_str_fixed(&mut self, value: &str, size: usize) -> io::Result<&mut Self> {
+    if value.len() > size {
+      let message = format!("string of {} bytes exceeds field of {}", value.len(), size);
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    }
+
+    self.reserve(size)?;
+    self.packet.append(value.as_bytes());
+    self.packet.append(&vec![0; size - value.len()]);
+    Ok(self)
+  }
+
+  /// Writes a number of bytes.
+  pub fn put_bytes(&mut self, bytes: &[u8]) -> io::Result<&mut Self> {
+    self.reserve(bytes.len())?;
+    self.packet.append(bytes);
+    Ok(self)
+  }
+
+  /// Ensures that a number of bytes fit within the packet's size budget.
+  fn reserve(&self, size: usize) -> io::Result<()> {
+    if size > self.remaining() {
+      let message = format!(
+        "packet would exceed its max size of {} bytes, {} bytes needed but {} remaining",
+        self.packet.kind().max_size(),
+        size,
+        self.remaining()
+      );
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    }
+    Ok(())
+  }
+}
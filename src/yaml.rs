@@ -0,0 +1,86 @@
+//! Conversion of packets to and from YAML, for hand-editing captured packets.
+//!
+//! Raw packets are represented by their kind, code and hexadecimal data:
+//!
+//! ```yaml
+//! kind: C1
+//! code: F4
+//! data: 03 00 00
+//! ```
+
+use crate::names::parse_byte;
+use crate::{Packet, PacketKind};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// The YAML representation of a raw packet.
+#[derive(Serialize, Deserialize)]
+struct RawPacket {
+  kind: String,
+  code: String,
+  data: String,
+}
+
+/// Converts a raw packet to YAML.
+pub fn packet_to_string(packet: &Packet) -> Result<String, io::Error> {
+  let data = packet
+    .data()
+    .iter()
+    .map(|byte| format!("{:02X}", byte))
+    .collect::<Vec<_>>()
+    .join(" ");
+
+  to_string(&RawPacket {
+    kind: format!("{:?}", packet.kind()),
+    code: format!("{:02X}", packet.code()),
+    data,
+  })
+}
+
+/// Converts YAML to a raw packet.
+pub fn packet_from_str(yaml: &str) -> Result<Packet, io::Error> {
+  let raw: RawPacket = from_str(yaml)?;
+  let kind = parse_byte(&raw.kind)
+    .and_then(PacketKind::from_byte)
+    .ok_or_else(|| invalid_data("invalid packet kind"))?;
+  let code = parse_byte(&raw.code).ok_or_else(|| invalid_data("invalid packet code"))?;
+
+  let mut packet = Packet::new(kind, code);
+  for byte in raw.data.split_whitespace() {
+    packet.append(&[parse_byte(byte).ok_or_else(|| invalid_data("invalid packet data"))?]);
+  }
+  Ok(packet)
+}
+
+/// Converts a typed packet (or any serializable value) to YAML.
+pub fn to_string<T: Serialize>(value: &T) -> Result<String, io::Error> {
+  serde_yaml::to_string(value).map_err(invalid_data)
+}
+
+/// Converts YAML to a typed packet (or any deserializable value).
+pub fn from_str<T: DeserializeOwned>(yaml: &str) -> Result<T, io::Error> {
+  serde_yaml::from_str(yaml).map_err(invalid_data)
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+  E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+  io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn packet_round_trip() {
+    let packet = Packet::from_bytes(&[0xC2, 0x00, 0x07, 0xF4, 0x06, 0x00, 0x01]).unwrap();
+    let yaml = packet_to_string(&packet).unwrap();
+    assert_eq!(yaml, "kind: C2\ncode: F4\ndata: 06 00 01\n");
+
+    let edited = packet_from_str(&yaml.replace("01", "02")).unwrap();
+    assert_eq!(edited.to_bytes(), [0xC2, 0x00, 0x07, 0xF4, 0x06, 0x00, 0x02]);
+  }
+}
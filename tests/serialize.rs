@@ -0,0 +1,164 @@
+#![cfg(feature = "serialize")]
+
+use muonline_packet::{Diagnostic, Packet, PacketDecodable, PacketEncodable, ValidationError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Packet, Debug, PartialEq, Eq)]
+#[packet(kind = "C1", code = "F3", subcode = "03")]
+#[packet(test_vector = "c1 05 f3 03 02", test_vector = "c1 05 f3 03 04")]
+struct CharacterSelect {
+  slot: u8,
+}
+
+#[test]
+fn runtime_subcodes() {
+  let select = CharacterSelect { slot: 2 };
+  let packet = select.to_packet_with(&[0x01]).unwrap();
+  assert_eq!(&packet.to_bytes(), &[0xC1, 0x06, 0xF3, 0x03, 0x01, 0x02]);
+
+  assert_eq!(CharacterSelect::from_packet_with(&packet, &[0x01]).unwrap(), select);
+  assert!(CharacterSelect::from_packet_with(&packet, &[0x02]).is_err());
+}
+
+#[derive(Serialize, Deserialize, Packet, Debug)]
+#[packet(kind = "C1", code = "20")]
+struct LevelUp {
+  #[mu(range = "1..=400")]
+  level: u16,
+  #[mu(max_len = 2)]
+  points: Vec<u8>,
+}
+
+#[test]
+fn field_validation() {
+  let valid = LevelUp { level: 400, points: vec![1, 2] };
+  assert!(LevelUp::from_packet(&valid.to_packet().unwrap()).is_ok());
+
+  let level = LevelUp { level: 401, points: vec![] }.to_packet().unwrap();
+  let error = LevelUp::from_packet(&level).unwrap_err();
+  let error = error.get_ref().unwrap().downcast_ref::<ValidationError>().unwrap();
+  assert_eq!(error.field(), "level");
+
+  let points = LevelUp { level: 1, points: vec![1, 2, 3] }.to_packet().unwrap();
+  assert!(LevelUp::from_packet(&points).is_err());
+}
+
+#[test]
+fn decode_offset() {
+  // The level is complete, but the points' length is not
+  let packet = Packet::from_bytes(&[0xC1, 0x07, 0x20, 0x01, 0x00, 0x02, 0x00]).unwrap();
+  let error = LevelUp::from_packet(&packet).unwrap_err();
+  let diagnostic = error.get_ref().unwrap().downcast_ref::<Diagnostic>().unwrap();
+  assert_eq!(diagnostic.offset(), 5);
+  assert!(error.to_string().starts_with("at byte 5 of packet C1/20: "));
+}
+
+#[derive(Serialize, Deserialize, Packet, Debug, PartialEq)]
+#[packet(kind = "C1", code = "F3", subcode = "01", builder)]
+struct CharacterCreate {
+  name: [u8; 10],
+  #[mu(range = "0..=6")]
+  class: u8,
+  #[mu(reserved)]
+  padding: u8,
+}
+
+#[test]
+fn builder() {
+  let create = CharacterCreate::builder()
+    .name(*b"elliott\0\0\0")
+    .class(2)
+    .build()
+    .unwrap();
+  assert_eq!(create.padding, 0);
+
+  let error = CharacterCreate::builder().class(2).build().unwrap_err();
+  assert_eq!(error.field(), "name");
+
+  let error = CharacterCreate::builder().name([0; 10]).class(7).build().unwrap_err();
+  assert_eq!(error.field(), "class");
+}
+
+#[derive(Serialize, Deserialize, Packet, Debug, PartialEq)]
+#[packet(kind = "C1", code = "D7", builder)]
+struct Move {
+  x: u8,
+  y: u8,
+  #[mu(skip)]
+  #[serde(skip)]
+  received: bool,
+}
+
+#[test]
+fn skipped_field() {
+  let step = Move::builder().x(130).y(125).build().unwrap();
+  assert!(!step.received);
+
+  let step = Move { received: true, ..step };
+  let packet = step.to_packet().unwrap();
+  assert_eq!(&packet.to_bytes(), &[0xC1, 0x05, 0xD7, 130, 125]);
+  assert_eq!(Move::from_packet(&packet).unwrap(), Move { received: false, ..step });
+}
+
+#[derive(Serialize, Deserialize, Packet, Debug, PartialEq)]
+#[packet(kind = "C1", code = "D8", builder)]
+struct Attack {
+  target: u16,
+  #[mu(default)]
+  #[serde(with = "muonline_packet::serialize::trailing")]
+  skill: u16,
+  #[mu(default)]
+  #[serde(with = "muonline_packet::serialize::trailing")]
+  combo: u8,
+}
+
+#[test]
+fn trailing_fields() {
+  let attack = Attack::builder().target(0x0102).build().unwrap();
+  assert_eq!((attack.skill, attack.combo), (0, 0));
+
+  let attack = Attack { skill: 0x0304, combo: 5, ..attack };
+  let packet = attack.to_packet().unwrap();
+  assert_eq!(&packet.to_bytes(), &[0xC1, 0x08, 0xD8, 0x02, 0x01, 0x04, 0x03, 0x05]);
+  assert_eq!(Attack::from_packet(&packet).unwrap(), attack);
+
+  // Packets of an earlier revision lack the trailing fields
+  let packet = Packet::from_bytes(&[0xC1, 0x07, 0xD8, 0x02, 0x01, 0x04, 0x03]).unwrap();
+  assert_eq!(Attack::from_packet(&packet).unwrap(), Attack { combo: 0, ..attack });
+
+  let packet = Packet::from_bytes(&[0xC1, 0x05, 0xD8, 0x02, 0x01]).unwrap();
+  let attack = Attack::from_packet(&packet).unwrap();
+  assert_eq!((attack.skill, attack.combo), (0, 0));
+
+  // Required fields remain required
+  let packet = Packet::from_bytes(&[0xC1, 0x04, 0xD8, 0x02]).unwrap();
+  assert!(Attack::from_packet(&packet).is_err());
+}
+
+#[derive(Serialize, Deserialize, Packet, Debug)]
+#[packet(kind = "C1", code = "00", min_size = 5)]
+struct Chat {
+  message: Vec<u8>,
+}
+
+#[test]
+fn min_size() {
+  let packet = Packet::from_bytes(&[0xC1, 0x04, 0x00, 0x00]).unwrap();
+  let error = Chat::from_packet(&packet).unwrap_err();
+  assert_eq!(error.to_string(), "at byte 4 of packet C1/00: packet size 4 is below the minimum 5");
+}
+
+#[derive(Serialize, Deserialize, Packet, Debug)]
+#[packet(kind = "C3", code = "F1", subcode = "01", display)]
+struct Login {
+  account: String,
+  #[mu(redact)]
+  password: String,
+  version: u16,
+}
+
+#[test]
+fn display() {
+  let login = Login { account: "foo".into(), password: "bar".into(), version: 104 };
+  assert_eq!(login.to_string(), r#"Login { account: "foo", password: ***, version: 104 }"#);
+}